@@ -1,3 +1,13 @@
+//! HTTP and HTTPS client of the device.
+//!
+//! Server certificates are NOT verified. reqwless 0.12 always opens TLS connections with
+//! `embedded_tls::NoVerify` unless a pre-shared key is used, and the certificate verifier
+//! of embedded-tls 0.17 needs `std`, so neither the certificate chain nor a pinned
+//! certificate of the api hosts can be checked on this TLS stack. Anyone who can
+//! intercept the traffic of the device, for example with a rogue access point or DNS
+//! spoofing, can read the ENTSO-E security token and the Fingrid api key sent with the
+//! requests. Use keys that can be revoked and only connect the device to trusted networks.
+
 use core::str::from_utf8;
 
 use embassy_net::{
@@ -5,9 +15,10 @@ use embassy_net::{
     tcp::client::{TcpClient, TcpClientState},
     Stack,
 };
+use embedded_io_async::Read;
 use esp_wifi::wifi::{WifiDevice, WifiStaDevice};
 use reqwless::{
-    client::HttpClient,
+    client::{HttpClient, TlsConfig, TlsVerify},
     headers::ContentType,
     request::{Method, RequestBuilder},
 };
@@ -21,6 +32,37 @@ static TCP_CLIENT: StaticCell<TcpClient<WifiDevice<'static, WifiStaDevice>, 1, 4
 
 static DNS_SOCKET: StaticCell<DnsSocket<WifiDevice<WifiStaDevice>>> = StaticCell::new();
 
+/// Largest TLS record is 16 KiB plus overhead
+static TLS_READ_BUFFER: ConstStaticCell<[u8; 16640]> = ConstStaticCell::new([0; 16640]);
+
+static TLS_WRITE_BUFFER: ConstStaticCell<[u8; 4096]> = ConstStaticCell::new([0; 4096]);
+
+/// Error from [Client::get_streaming]
+#[derive(Debug)]
+pub enum FetchError<E> {
+    Http(reqwless::Error),
+    /// Server responded with a status other than 2xx
+    Status(u16),
+    /// Body handler returned an error
+    Body(E),
+}
+
+impl<E> From<reqwless::Error> for FetchError<E> {
+    fn from(value: reqwless::Error) -> Self {
+        Self::Http(value)
+    }
+}
+
+impl<E: core::fmt::Display> core::fmt::Display for FetchError<E> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FetchError::Http(e) => write!(f, "http error : {e:?}"),
+            FetchError::Status(status) => write!(f, "http status {status}"),
+            FetchError::Body(e) => write!(f, "{e}"),
+        }
+    }
+}
+
 pub struct Client<S: BuildState, const N: usize = 4096> {
     buffer: [u8; N],
     // stack: &'static Stack<WifiDevice<'static, WifiStaDevice>>,
@@ -28,11 +70,21 @@ pub struct Client<S: BuildState, const N: usize = 4096> {
 }
 
 impl<'a> Client<NotStarted> {
-    pub fn new(stack: &'static Stack<WifiDevice<WifiStaDevice>>) -> Client<Ready<'a>> {
+    /// Creates client that supports both http and https urls.
+    ///
+    /// `seed` is used for TLS key generation and it should come from the hardware RNG.
+    /// Server certificates are not verified, see the [module](self) documentation.
+    pub fn new(stack: &'static Stack<WifiDevice<WifiStaDevice>>, seed: u64) -> Client<Ready<'a>> {
         let state = TCP_CLIENT_STATE.take();
         let tcp_client = &*TCP_CLIENT.init(TcpClient::new(stack, state));
         let dns_socket = &*DNS_SOCKET.init(DnsSocket::new(stack));
-        let client = reqwless::client::HttpClient::new(tcp_client, dns_socket);
+        let tls = TlsConfig::new(
+            seed,
+            TLS_READ_BUFFER.take(),
+            TLS_WRITE_BUFFER.take(),
+            TlsVerify::None,
+        );
+        let client = reqwless::client::HttpClient::new_with_tls(tcp_client, dns_socket, tls);
 
         Client::<Ready<'a>> {
             // buffer: todo!(),
@@ -58,6 +110,42 @@ impl Client<Ready<'_>> {
 
         body
     }
+
+    /// Sends GET request to `url` and passes the response body to `on_chunk` as it is received,
+    /// so the body does not have to fit in memory.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request fails, the response status is not
+    /// successful or `on_chunk` returns an error. Rest of the body is not read after an error.
+    pub async fn get_streaming<E>(
+        &mut self,
+        url: &str,
+        headers: &[(&str, &str)],
+        mut on_chunk: impl FnMut(&[u8]) -> Result<(), E>,
+    ) -> Result<(), FetchError<E>> {
+        let mut request = self
+            .state
+            .client
+            .request(Method::GET, url)
+            .await?
+            .headers(headers);
+
+        let response = request.send(&mut self.buffer).await?;
+        if !response.status.is_successful() {
+            return Err(FetchError::Status(response.status.0));
+        }
+
+        let mut reader = response.body().reader();
+        let mut chunk = [0u8; 512];
+        loop {
+            let len = reader.read(&mut chunk).await?;
+            if len == 0 {
+                return Ok(());
+            }
+            on_chunk(&chunk[..len]).map_err(FetchError::Body)?;
+        }
+    }
 }
 
 pub trait BuildState {}
@@ -0,0 +1,32 @@
+//! Wall clock of the device.
+//!
+//! Device has no real-time clock, so the time is unknown until the host sets it with
+//! [Message::SetTime](shared::Message::SetTime) and counted from the uptime after that.
+
+use core::cell::Cell;
+
+use embassy_sync::blocking_mutex::{raw::CriticalSectionRawMutex, Mutex};
+use embassy_time::Instant;
+use shared::price::Timestamp;
+
+/// Unix time at boot, [None] until the time is set
+static BOOT_TIME: Mutex<CriticalSectionRawMutex, Cell<Option<Timestamp>>> =
+    Mutex::new(Cell::new(None));
+
+/// Time of the firmware build, the clock is never set to an earlier time
+pub fn build_time() -> Timestamp {
+    // Set by the build script
+    env!("BUILD_TIME").parse().unwrap_or(0)
+}
+
+/// Sets the current time to `now` seconds since the Unix epoch
+pub fn set(now: Timestamp) {
+    let boot = now - Instant::now().as_secs() as Timestamp;
+    BOOT_TIME.lock(|time| time.set(Some(boot)));
+}
+
+/// Current time as seconds since the Unix epoch, [None] if it has not been set
+pub fn now() -> Option<Timestamp> {
+    let boot = BOOT_TIME.lock(Cell::get)?;
+    Some(boot + Instant::now().as_secs() as Timestamp)
+}
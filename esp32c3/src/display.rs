@@ -1,11 +1,15 @@
+use core::fmt::Write;
 use display_interface_spi::SPIInterface;
 use embassy_executor::SendSpawner;
 use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, channel::Receiver};
 use embassy_time::Delay;
+
 use embedded_graphics::{
     draw_target::DrawTarget,
-    geometry::Dimensions,
+    geometry::{Dimensions, Point, Size},
     pixelcolor::{Rgb565, RgbColor},
+    primitives::{Primitive, PrimitiveStyle, Rectangle},
+    Drawable,
 };
 use embedded_hal_bus::spi::{ExclusiveDevice, NoDelay};
 use esp_hal::{
@@ -13,16 +17,28 @@ use esp_hal::{
     peripherals::SPI2,
     spi::{master::Spi, FullDuplexMode},
 };
+use heapless::String;
 use mipidsi::{
     dcs::{SetDisplayOff, SetDisplayOn},
     models::ST7789,
     options::{ColorInversion, Orientation, Rotation},
     Display,
 };
-use shared::DisplayUpdate;
+use shared::{
+    emission::{grams_per_kwh, Emissions, TREND_HOURS},
+    grid::{AlertLevel, GridAlert, SystemState},
+    price::{PriceSeries, Resolution},
+    statistics::Statistics,
+    tariff::{cents_per_kwh, Tariff, DEFAULT_TARIFF},
+    DisplayUpdate, Page,
+};
 use u8g2_fonts::types::{FontColor, HorizontalAlignment, VerticalPosition};
 
-use crate::styles::FONT1_NORMAL;
+use crate::{
+    clock,
+    styles::FONT1_NORMAL,
+    tasks::{self, DAY_AHEAD_PRICES, EMISSIONS},
+};
 
 type ST7789Display = Display<DisplaySpiInterface, ST7789, Output<'static, Gpio8>>;
 
@@ -71,6 +87,12 @@ async fn update_display(
     mut display: ST7789Display,
     receiver: Receiver<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
 ) {
+    // Native resolution of the market
+    let mut resolution = Resolution::Minutes15;
+    let mut tariff = DEFAULT_TARIFF;
+    let mut page = Page::Prices;
+    let mut alert = GridAlert::default();
+
     loop {
         let msg = receiver.receive().await;
         // Safety : Rest  of the code is not aware of raw commands sent.
@@ -103,6 +125,312 @@ async fn update_display(
             DisplayUpdate::SetBrightness(b) => {
                 dcs.write_command(b).unwrap();
             }
+            DisplayUpdate::Prices(t) => {
+                tariff = t;
+                draw_page(&mut display, page, &alert, resolution, &tariff).await;
+            }
+            DisplayUpdate::PriceResolution(r) => {
+                resolution = r;
+                draw_page(&mut display, page, &alert, resolution, &tariff).await;
+            }
+            DisplayUpdate::ShowPage(p) => {
+                page = p;
+                draw_page(&mut display, page, &alert, resolution, &tariff).await;
+            }
+            DisplayUpdate::GridAlert(a) => {
+                alert = a;
+                draw_page(&mut display, page, &alert, resolution, &tariff).await;
+            }
+            DisplayUpdate::Emissions => {
+                if page == Page::Emissions {
+                    draw_page(&mut display, page, &alert, resolution, &tariff).await;
+                }
+            }
         }
     }
 }
+
+/// Draws `page`, or a banner instead of it while `alert` is not normal
+async fn draw_page(
+    display: &mut ST7789Display,
+    page: Page,
+    alert: &GridAlert,
+    resolution: Resolution,
+    tariff: &Tariff,
+) {
+    if !alert.is_normal() {
+        draw_alert(display, alert);
+        return;
+    }
+    match page {
+        Page::Prices => {
+            let series = DAY_AHEAD_PRICES.lock().await;
+            draw_prices(display, &series, resolution, tariff);
+        }
+        Page::Statistics => draw_statistics(display, &tasks::statistics().await),
+        Page::Emissions => {
+            let emissions = EMISSIONS.lock().await;
+            let series = DAY_AHEAD_PRICES.lock().await;
+            draw_emissions(display, &emissions, &series);
+        }
+    }
+}
+
+/// Height of a line of text in the summary above the price chart
+const LINE_HEIGHT: i32 = 28;
+
+/// Height of the summary above the price chart
+const HEADER_HEIGHT: i32 = 2 * LINE_HEIGHT;
+
+/// Height of the text above the hourly charts on the statistics and emissions pages
+const STATISTICS_HEADER_HEIGHT: i32 = 4 * LINE_HEIGHT;
+
+/// Draws a summary of wholesale and consumer prices and a bar for each time unit
+/// of `series` in `resolution`
+fn draw_prices(
+    display: &mut ST7789Display,
+    series: &PriceSeries,
+    resolution: Resolution,
+    tariff: &Tariff,
+) {
+    display.clear(Rgb565::WHITE).unwrap();
+    let area = display.bounding_box();
+
+    let mut lines = [String::<64>::new(), String::<64>::new()];
+    let minutes = resolution.seconds() / 60;
+    let _ = write!(lines[0], "{} {minutes} min", series.zone.code());
+    if let Some(average) = series.average() {
+        let _ = write!(lines[0], " | spot {}", cents_per_kwh(average));
+    }
+    if let Some(average) = tariff.average_retail_price(series) {
+        let _ = write!(lines[1], "all-in {} c/kWh", cents_per_kwh(average));
+    }
+    draw_lines(display, &lines);
+
+    let count = series.resampled(resolution).count() as i32;
+    if count == 0 {
+        return;
+    }
+    // Chart always includes zero so that negative prices go below it
+    let high = series.max().map_or(0, |p| p.price).max(0) as i64;
+    let low = series.min().map_or(0, |p| p.price).min(0) as i64;
+    let range = (high - low).max(1);
+
+    let height = (area.size.height as i32 - HEADER_HEIGHT) as i64;
+    let zero_y = HEADER_HEIGHT + (high * height / range) as i32;
+    let bar_width = (area.size.width as i32 / count).max(1);
+
+    for (i, point) in series.resampled(resolution).enumerate() {
+        let bar_height = (point.price as i64 * height / range) as i32;
+        let (top, color) = if bar_height >= 0 {
+            (zero_y - bar_height, Rgb565::BLUE)
+        } else {
+            (zero_y, Rgb565::GREEN)
+        };
+        Rectangle::new(
+            Point::new(i as i32 * bar_width, top),
+            Size::new(bar_width as u32, bar_height.unsigned_abs().max(1)),
+        )
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(display)
+        .unwrap();
+    }
+}
+
+/// Draws spot prices of the current day compared to the recent days and the average
+/// hourly profile of the history, with the hours of the current day that are cheaper
+/// than usual in green
+fn draw_statistics(display: &mut ST7789Display, statistics: &Statistics) {
+    display.clear(Rgb565::WHITE).unwrap();
+    let area = display.bounding_box();
+
+    let mut lines = [
+        String::<64>::new(),
+        String::<64>::new(),
+        String::<64>::new(),
+        String::<64>::new(),
+    ];
+    let Statistics { date, days, .. } = statistics;
+    let _ = write!(
+        lines[0],
+        "{}-{:02}-{:02} | {days} days",
+        date.year, date.month, date.day
+    );
+    match (statistics.today, statistics.week_average) {
+        (Some(today), Some(week)) => {
+            let _ = write!(
+                lines[1],
+                "today {} | 7 d {}",
+                cents_per_kwh(today.average),
+                cents_per_kwh(week)
+            );
+        }
+        (Some(today), None) => {
+            let _ = write!(lines[1], "today {}", cents_per_kwh(today.average));
+        }
+        _ => {
+            let _ = write!(lines[1], "no prices for today");
+        }
+    }
+    if let Some(percent) = statistics.today_vs_week() {
+        let _ = write!(lines[2], "{percent:+} % vs 7 days");
+    }
+    if let Some(month) = statistics.month_average {
+        let _ = write!(lines[3], "month {} c/kWh", cents_per_kwh(month));
+    }
+    draw_lines(display, &lines);
+
+    let Some(profile) = statistics.profile else {
+        return;
+    };
+    // Chart always includes zero so that negative prices go below it
+    let high = profile.iter().copied().max().unwrap_or(0).max(0) as i64;
+    let low = profile.iter().copied().min().unwrap_or(0).min(0) as i64;
+    let range = (high - low).max(1);
+
+    let height = (area.size.height as i32 - STATISTICS_HEADER_HEIGHT) as i64;
+    let zero_y = STATISTICS_HEADER_HEIGHT + (high * height / range) as i32;
+    let bar_width = area.size.width as i32 / profile.len() as i32;
+
+    for (hour, price) in profile.iter().enumerate() {
+        let cheaper = statistics
+            .today
+            .is_some_and(|today| today.hourly[hour] < *price);
+        let bar_height = (*price as i64 * height / range) as i32;
+        let top = if bar_height >= 0 {
+            zero_y - bar_height
+        } else {
+            zero_y
+        };
+        let color = if cheaper { Rgb565::GREEN } else { Rgb565::BLUE };
+        Rectangle::new(
+            Point::new(hour as i32 * bar_width, top),
+            Size::new(bar_width as u32 - 1, bar_height.unsigned_abs().max(1)),
+        )
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(display)
+        .unwrap();
+    }
+}
+
+/// Draws the latest emission factors next to the current spot price and the hourly
+/// trend of emissions, with the hours cleaner than average in green
+fn draw_emissions(display: &mut ST7789Display, emissions: &Emissions, series: &PriceSeries) {
+    display.clear(Rgb565::WHITE).unwrap();
+    let area = display.bounding_box();
+
+    let mut lines = [
+        String::<64>::new(),
+        String::<64>::new(),
+        String::<64>::new(),
+        String::<64>::new(),
+    ];
+    let average = emissions.average();
+    match emissions.consumption {
+        Some(latest) => {
+            let _ = write!(lines[0], "CO2 {} g/kWh", grams_per_kwh(latest.value));
+        }
+        None => {
+            let _ = write!(lines[0], "CO2 unknown");
+        }
+    }
+    if let Some(average) = average {
+        let _ = write!(lines[0], " | 24 h {}", grams_per_kwh(average));
+    }
+    if let Some(production) = emissions.production {
+        let _ = write!(lines[1], "production {}", grams_per_kwh(production.value));
+    }
+    if let Some(percent) = emissions.vs_average() {
+        let _ = write!(lines[1], " | {percent:+} %");
+    }
+
+    // Cheap when below the average of the known prices and clean when below the trend
+    let price = clock::now().and_then(|now| series.at(now));
+    let cheap = price
+        .zip(series.average())
+        .map(|(price, average)| price.price < average);
+    let clean = emissions.vs_average().map(|percent| percent < 0);
+    if let Some(price) = price {
+        let _ = write!(lines[2], "spot {} c/kWh", cents_per_kwh(price.price));
+    }
+    let verdict = match (cheap, clean) {
+        (Some(true), Some(true)) => "cheap and clean now",
+        (Some(true), Some(false)) => "cheap but not clean",
+        (Some(false), Some(true)) => "clean but not cheap",
+        (Some(false), Some(false)) => "neither cheap nor clean",
+        _ => "",
+    };
+    let _ = lines[3].push_str(verdict);
+    draw_lines(display, &lines);
+
+    let Some(average) = average else {
+        return;
+    };
+    // Emissions are never negative
+    let high = emissions.trend().map(|(_, v)| v).max().unwrap_or(0).max(1) as i64;
+    let height = (area.size.height as i32 - STATISTICS_HEADER_HEIGHT) as i64;
+    let bottom = area.size.height as i32;
+    let bar_width = area.size.width as i32 / TREND_HOURS as i32;
+
+    for (i, (_, value)) in emissions.trend().enumerate() {
+        let bar_height = ((value.max(0) as i64 * height / high) as i32).max(1);
+        let color = if value < average {
+            Rgb565::GREEN
+        } else {
+            Rgb565::BLUE
+        };
+        Rectangle::new(
+            Point::new(i as i32 * bar_width, bottom - bar_height),
+            Size::new(bar_width as u32 - 1, bar_height as u32),
+        )
+        .into_styled(PrimitiveStyle::with_fill(color))
+        .draw(display)
+        .unwrap();
+    }
+}
+
+/// Fills the display with the colour of the power system state, yellow for warnings,
+/// red for critical situations and black for a blackout, and describes the situation
+fn draw_alert(display: &mut ST7789Display, alert: &GridAlert) {
+    let (background, text) = match (alert.state, alert.level()) {
+        (SystemState::Blackout, _) => (Rgb565::BLACK, Rgb565::WHITE),
+        (SystemState::Restoration, AlertLevel::Warning) => (Rgb565::BLUE, Rgb565::WHITE),
+        (_, AlertLevel::Critical) => (Rgb565::RED, Rgb565::WHITE),
+        _ => (Rgb565::YELLOW, Rgb565::BLACK),
+    };
+    display.clear(background).unwrap();
+
+    let center = display.bounding_box().center();
+    let headlines = alert.headlines().chain(["Save electricity"]);
+    let count = alert.headlines().count() as i32 + 1;
+    for (i, line) in headlines.enumerate() {
+        let y = center.y + (2 * i as i32 + 1 - count) * LINE_HEIGHT / 2;
+        FONT1_NORMAL
+            .render_aligned(
+                line,
+                Point::new(center.x, y),
+                VerticalPosition::Center,
+                HorizontalAlignment::Center,
+                FontColor::Transparent(text),
+                display,
+            )
+            .unwrap();
+    }
+}
+
+/// Draws `lines` centered from the top of the display, one per [LINE_HEIGHT]
+fn draw_lines(display: &mut ST7789Display, lines: &[String<64>]) {
+    let center = display.bounding_box().center().x;
+    for (i, line) in lines.iter().enumerate() {
+        FONT1_NORMAL
+            .render_aligned(
+                line.as_str(),
+                Point::new(center, LINE_HEIGHT / 2 + i as i32 * LINE_HEIGHT),
+                VerticalPosition::Center,
+                HorizontalAlignment::Center,
+                FontColor::Transparent(Rgb565::BLACK),
+                display,
+            )
+            .unwrap();
+    }
+}
@@ -0,0 +1,78 @@
+//! Day-ahead prices from the ENTSO-E Transparency Platform.
+//!
+//! The XML response is parsed while it is received with [EntsoeParser], see
+//! <https://transparency.entsoe.eu/content/static_content/Static%20content/web%20api/Guide.html>
+//!
+//! The security token is sent in the url over TLS without certificate verification,
+//! see [client](crate::client).
+
+use core::fmt::Write;
+
+use heapless::String;
+use shared::{
+    entsoe::{EntsoeError, EntsoeParser},
+    price::{BiddingZone, PriceSeries, Timestamp},
+    time::UtcDateTime,
+};
+
+use crate::client::{Client, FetchError, Ready};
+
+const API_URL: &str = "https://web-api.tp.entsoe.eu/api";
+
+/// Fetches day-ahead prices of `zone` for the period from `start` to `end` to `series`.
+///
+/// `series` is cleared first, its currency and resolution are set from the response.
+///
+/// # Errors
+///
+/// This function will return an error if the request fails or the response is not a valid
+/// price document. `series` may contain some of the points after an error.
+pub async fn fetch_day_ahead<const N: usize>(
+    client: &mut Client<Ready<'_>>,
+    api_key: &String<64>,
+    zone: BiddingZone,
+    start: Timestamp,
+    end: Timestamp,
+    series: &mut PriceSeries<N>,
+) -> Result<(), FetchError<EntsoeError>> {
+    let url = day_ahead_url(api_key, zone, start, end);
+
+    series.clear();
+    series.zone = zone;
+
+    let mut parser = EntsoeParser::new();
+    client
+        .get_streaming(&url, &[], |chunk| parser.feed(chunk, series))
+        .await?;
+    parser.finish().map_err(FetchError::Body)
+}
+
+/// Longest possible url is 231 bytes with a 64 byte api key so this never fails
+fn day_ahead_url(
+    api_key: &String<64>,
+    zone: BiddingZone,
+    start: Timestamp,
+    end: Timestamp,
+) -> String<256> {
+    let mut url = String::new();
+    write!(
+        url,
+        "{API_URL}?securityToken={api_key}&documentType=A44&in_Domain={eic}&out_Domain={eic}",
+        eic = zone.eic()
+    )
+    .unwrap();
+    write_period(&mut url, "periodStart", start);
+    write_period(&mut url, "periodEnd", end);
+    url
+}
+
+/// Period parameters are in `yyyyMMddHHmm` format in UTC
+fn write_period(url: &mut String<256>, name: &str, timestamp: Timestamp) {
+    let dt = UtcDateTime::from_timestamp(timestamp);
+    write!(
+        url,
+        "&{name}={:04}{:02}{:02}{:02}{:02}",
+        dt.year, dt.month, dt.day, dt.hour, dt.minute
+    )
+    .unwrap();
+}
@@ -0,0 +1,133 @@
+//! Data from the Fingrid open data API v2, see <https://data.fingrid.fi/en/instructions>.
+//!
+//! Responses are parsed while they are received with [FingridParser].
+//! The api key is sent over TLS without certificate verification, see [client](crate::client).
+
+use core::fmt::Write;
+
+use heapless::{String, Vec};
+use shared::{
+    fingrid::{DataPoint, DatasetId, FingridError, FingridParser, DEFAULT_DATASETS, MAX_DATASETS},
+    price::Timestamp,
+    time::UtcDateTime,
+};
+
+use crate::client::{Client, FetchError, Ready};
+
+const API_URL: &str = "https://data.fingrid.fi/api/datasets";
+
+/// Values per page, largest allowed by the api is 20000
+const PAGE_SIZE: usize = 100;
+
+/// Fetches the latest value of `dataset`.
+///
+/// # Errors
+///
+/// This function will return an error if the request fails or the response does not
+/// contain a value.
+pub async fn fetch_latest(
+    client: &mut Client<Ready<'_>>,
+    api_key: &String<64>,
+    dataset: DatasetId,
+) -> Result<DataPoint, FetchError<FingridError>> {
+    let mut url = String::<64>::new();
+    // Longest url is 54 bytes so this never fails
+    write!(url, "{API_URL}/{dataset}/data/latest").unwrap();
+
+    let mut points = Vec::<DataPoint, 1>::new();
+    get(client, api_key, &url, &mut points).await?;
+    points.pop().ok_or(FetchError::Body(FingridError::NoData))
+}
+
+/// Fetches values of `dataset` from `start` to `end` to `points`, following pagination.
+///
+/// # Errors
+///
+/// This function will return an error if a request fails, a response is invalid or
+/// `points` runs out of capacity. `points` may contain some of the values after an error.
+pub async fn fetch_period<const N: usize>(
+    client: &mut Client<Ready<'_>>,
+    api_key: &String<64>,
+    dataset: DatasetId,
+    start: Timestamp,
+    end: Timestamp,
+    points: &mut Vec<DataPoint, N>,
+) -> Result<(), FetchError<FingridError>> {
+    let mut page = 1;
+    loop {
+        let url = period_url(dataset, start, end, page);
+        let Some(next_page) = get(client, api_key, &url, points).await? else {
+            return Ok(());
+        };
+        page = next_page;
+    }
+}
+
+/// Sends request to `url` and returns the next page if there is one
+async fn get<const N: usize>(
+    client: &mut Client<Ready<'_>>,
+    api_key: &String<64>,
+    url: &str,
+    points: &mut Vec<DataPoint, N>,
+) -> Result<Option<u32>, FetchError<FingridError>> {
+    let mut parser = FingridParser::new();
+    client
+        .get_streaming(url, &[("x-api-key", api_key)], |chunk| {
+            parser.feed(chunk, points)
+        })
+        .await?;
+    parser.finish().map_err(FetchError::Body)?;
+    Ok(parser.next_page())
+}
+
+/// Longest possible url is 179 bytes so this never fails
+fn period_url(dataset: DatasetId, start: Timestamp, end: Timestamp, page: u32) -> String<192> {
+    let mut url = String::new();
+    write!(url, "{API_URL}/{dataset}/data?").unwrap();
+    write_time(&mut url, "startTime", start);
+    write_time(&mut url, "&endTime", end);
+    write!(
+        url,
+        "&format=json&page={page}&pageSize={PAGE_SIZE}&sortBy=startTime&sortOrder=asc"
+    )
+    .unwrap();
+    url
+}
+
+fn write_time(url: &mut String<192>, name: &str, timestamp: Timestamp) {
+    let dt = UtcDateTime::from_timestamp(timestamp);
+    write!(
+        url,
+        "{name}={:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        dt.year, dt.month, dt.day, dt.hour, dt.minute, dt.second
+    )
+    .unwrap();
+}
+
+/// Formats dataset ids as a comma separated list for storage.
+///
+/// [MAX_DATASETS] ids take at most 47 bytes so this never fails.
+pub fn datasets_to_item(datasets: &[DatasetId]) -> String<64> {
+    let mut item = String::new();
+    for (i, dataset) in datasets.iter().enumerate() {
+        if i > 0 {
+            item.push(',').unwrap();
+        }
+        write!(item, "{dataset}").unwrap();
+    }
+    item
+}
+
+/// Parses list stored with [datasets_to_item], invalid ids are skipped.
+///
+/// Returns [DEFAULT_DATASETS] if `item` is [None].
+pub fn datasets_from_item(item: Option<&str>) -> Vec<DatasetId, MAX_DATASETS> {
+    match item {
+        Some(item) => item
+            .split(',')
+            .filter_map(|id| id.parse().ok())
+            .take(MAX_DATASETS)
+            .collect(),
+        None => Vec::from_slice(&DEFAULT_DATASETS).unwrap(),
+    }
+}
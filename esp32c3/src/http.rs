@@ -1,15 +1,31 @@
 use embassy_net::Stack;
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use esp_hal::rng::Rng;
 use esp_wifi::wifi::{WifiDevice, WifiStaDevice};
 use serde::{Deserialize, Serialize};
 use static_cell::StaticCell;
 
-use crate::client::{Client, Ready};
+use crate::{
+    client::{Client, Ready},
+    generate_rand_u64,
+};
 
-static CLIENT: StaticCell<Client<Ready>> = StaticCell::new();
+static CLIENT: StaticCell<Mutex<NoopRawMutex, Client<Ready>>> = StaticCell::new();
 
-pub fn setup(stack: &'static Stack<WifiDevice<'static, WifiStaDevice>>) -> Result<(), Error> {
-    CLIENT.init(Client::new(stack));
-    Ok(())
+/// Creates the http client shared by the fetch tasks, only one request can be made at a time
+///
+/// # Errors
+///
+/// This function will return an error if it is called more than once.
+pub fn setup(
+    stack: &'static Stack<WifiDevice<'static, WifiStaDevice>>,
+    mut rng: Rng,
+) -> Result<&'static Mutex<NoopRawMutex, Client<Ready<'static>>>, Error> {
+    let seed = generate_rand_u64(&mut rng);
+    CLIENT
+        .try_init(Mutex::new(Client::new(stack, seed)))
+        .map(|client| &*client)
+        .ok_or(Error::FailedSetup)
 }
 
 pub fn perform_get_request() {}
@@ -4,12 +4,20 @@
 use esp_hal::rng::Rng;
 
 pub mod client;
+pub mod clock;
 pub mod display;
+pub mod entsoe;
+pub mod fingrid;
 pub mod http;
+pub mod logger;
+pub mod provider;
+pub mod relay;
 pub mod serial;
 pub mod storage;
 pub mod styles;
+pub mod tariff;
 pub mod tasks;
+pub mod transfer;
 pub mod wifi;
 
 // use esp_hal::o
@@ -0,0 +1,84 @@
+use core::fmt::Write;
+
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    channel::{Channel, Receiver},
+};
+use heapless::String;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use shared::{LogLevel, LogRecord};
+
+/// Log records waiting for the serial_write task to send them to the host
+static LOG_CHANNEL: Channel<CriticalSectionRawMutex, LogRecord, 8> = Channel::new();
+
+static LOGGER: SerialLogger = SerialLogger;
+
+/// [Log] implementation that forwards records to the host as [shared::DeviceFrame::Log].
+///
+/// Records are dropped if the channel is full, logging never blocks.
+struct SerialLogger;
+
+impl Log for SerialLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut target = Truncating(String::new());
+        let _ = target.write_str(record.target());
+        let mut message = Truncating(String::new());
+        let _ = write!(message, "{}", record.args());
+
+        let _ = LOG_CHANNEL.try_send(LogRecord {
+            level: to_log_level(record.level()),
+            uptime_ms: embassy_time::Instant::now().as_millis(),
+            target: target.0,
+            message: message.0,
+        });
+    }
+
+    fn flush(&self) {}
+}
+
+/// Installs the serial logger, records above `level` are discarded
+pub fn init(level: LevelFilter) {
+    // SAFETY: called once from main before any other task is spawned
+    unsafe {
+        // riscv32imc has no atomic compare and swap so the racy variants are needed
+        let _ = log::set_logger_racy(&LOGGER);
+        log::set_max_level_racy(level);
+    }
+}
+
+/// Records logged with the `log` macros can be received from here
+pub fn receiver() -> Receiver<'static, CriticalSectionRawMutex, LogRecord, 8> {
+    LOG_CHANNEL.receiver()
+}
+
+fn to_log_level(level: Level) -> LogLevel {
+    match level {
+        Level::Error => LogLevel::Error,
+        Level::Warn => LogLevel::Warn,
+        Level::Info => LogLevel::Info,
+        Level::Debug => LogLevel::Debug,
+        Level::Trace => LogLevel::Trace,
+    }
+}
+
+/// Writes as much as fits and drops the rest instead of failing
+struct Truncating<const N: usize>(String<N>);
+
+impl<const N: usize> Write for Truncating<N> {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for c in s.chars() {
+            if self.0.push(c).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
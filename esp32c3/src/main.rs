@@ -3,13 +3,14 @@
 #![no_main]
 #![feature(type_alias_impl_trait)]
 
-use core::str::FromStr;
-
 use display_interface_spi::SPIInterface;
 use electricity_exhange::{
-    client::Client,
-    storage::NonVolatileStorage,
-    tasks::broker,
+    http,
+    storage::{NonVolatileStorage, PriceStorage},
+    tasks::{
+        broker, control_relays, get_day_ahead_prices, get_emissions, get_fingrid_data,
+        restore_day_ahead_prices, schedule_day_ahead_prices, watch_grid,
+    },
     wifi::{self, WifiPeripherals},
 };
 use embassy_executor::Spawner;
@@ -21,7 +22,7 @@ use embassy_sync::{
 use embedded_hal_bus::spi::ExclusiveDevice;
 use esp_hal::{
     clock::ClockControl,
-    gpio::{Io, Level, Output, NO_PIN},
+    gpio::{AnyOutput, Io, Level, Output, NO_PIN},
     interrupt::Priority,
     peripherals::Peripherals,
     prelude::*,
@@ -32,18 +33,21 @@ use esp_hal::{
 };
 use esp_hal_embassy::InterruptExecutor;
 // use esp_println::println;
-use heapless::String;
-use shared::{DisplayUpdate, Message, Response};
+use shared::{DisplayUpdate, Envelope, Event, Message, Response};
 use static_cell::{ConstStaticCell, StaticCell};
 
 use esp_backtrace as _; // Panic behaviour
 
 /// Send incoming messages to this channel for broker task to handle
-static BROKER_CHANNEL: ConstStaticCell<Channel<NoopRawMutex, Message, 10>> =
+static BROKER_CHANNEL: ConstStaticCell<Channel<NoopRawMutex, Envelope<Message>, 10>> =
     ConstStaticCell::new(Channel::new());
 
 /// Send responses to this channel for the serial_write task to handle
-static WRITER_CHANNEL: ConstStaticCell<Channel<NoopRawMutex, Response, 10>> =
+static WRITER_CHANNEL: ConstStaticCell<Channel<NoopRawMutex, Envelope<Response>, 10>> =
+    ConstStaticCell::new(Channel::new());
+
+/// Send events to this channel for the serial_write task to send to the host
+static EVENT_CHANNEL: ConstStaticCell<Channel<CriticalSectionRawMutex, Event, 10>> =
     ConstStaticCell::new(Channel::new());
 
 /// Send updates to display
@@ -65,6 +69,8 @@ async fn main(spawner: Spawner) {
     let timg0 = TimerGroup::new_async(peripherals.TIMG0, &clocks);
     esp_hal_embassy::init(&clocks, timg0);
 
+    electricity_exhange::logger::init(log::LevelFilter::Info);
+
     let io = Io::new(peripherals.GPIO, peripherals.IO_MUX);
 
     let sclk = io.pins.gpio12;
@@ -86,6 +92,12 @@ async fn main(spawner: Spawner) {
 
     let rst = Output::new(io.pins.gpio8, Level::High);
 
+    // Relay outputs in the order of relay indices, high switches the relay on
+    let relay_pins = [
+        AnyOutput::new(io.pins.gpio4, Level::Low),
+        AnyOutput::new(io.pins.gpio5, Level::Low),
+    ];
+
     let display_channel = DISPLAY_CHANNEL.take();
 
     let high_prio_executor = HIGH_PRIO_EXECUTOR.init(InterruptExecutor::new(
@@ -110,9 +122,49 @@ async fn main(spawner: Spawner) {
         wifi: peripherals.WIFI,
     };
 
-    let stack = wifi::connect(&spawner, rng, wifi_peripherals, display_sender, nvs_storage)
-        .await
-        .unwrap();
+    let event_channel = EVENT_CHANNEL.take();
+    let event_sender = event_channel.sender();
+
+    // Prices saved before the reboot are shown until new ones are fetched
+    let mut price_storage = PriceStorage::take();
+    restore_day_ahead_prices(&mut price_storage, display_sender, nvs_storage).await;
+
+    // Relays keep following the rules with the last prices even without a network
+    spawner.must_spawn(control_relays(relay_pins, event_sender, nvs_storage));
+
+    let stack = wifi::connect(
+        &spawner,
+        rng,
+        wifi_peripherals,
+        display_sender,
+        event_sender,
+        nvs_storage,
+    )
+    .await
+    .unwrap();
+
+    let http_client = http::setup(stack, rng).unwrap();
+    spawner.must_spawn(get_day_ahead_prices(
+        http_client,
+        display_sender,
+        event_sender,
+        nvs_storage,
+        price_storage,
+    ));
+    spawner.must_spawn(schedule_day_ahead_prices(nvs_storage));
+    spawner.must_spawn(get_fingrid_data(http_client, event_sender, nvs_storage));
+    spawner.must_spawn(get_emissions(
+        http_client,
+        display_sender,
+        event_sender,
+        nvs_storage,
+    ));
+    spawner.must_spawn(watch_grid(
+        http_client,
+        display_sender,
+        event_sender,
+        nvs_storage,
+    ));
 
     let broker_channel = BROKER_CHANNEL.take();
     let writer_channel = WRITER_CHANNEL.take();
@@ -121,6 +173,7 @@ async fn main(spawner: Spawner) {
         broker_channel.receiver(),
         writer_channel.sender(),
         display_sender,
+        event_sender,
         nvs_storage,
     ));
 
@@ -130,6 +183,7 @@ async fn main(spawner: Spawner) {
         &clocks,
         broker_channel.sender(),
         writer_channel.receiver(),
+        event_channel,
         display_sender,
     )
     .await
@@ -0,0 +1,260 @@
+//! Sources of day-ahead prices behind a common [PriceProvider] interface, so that another
+//! source can be used when one of them is down.
+
+use core::{fmt::Write, str::FromStr};
+
+use embassy_sync::{blocking_mutex::raw::NoopRawMutex, mutex::Mutex};
+use heapless::{String, Vec};
+use shared::{
+    entsoe::EntsoeError,
+    fingrid::{dataset, DataPoint, FingridError, VALUE_SCALE},
+    mirror::{MirrorError, MirrorFormat, MirrorParser, SPOT_HINTA},
+    price::{
+        delivery_period, BiddingZone, Currency, PricePoint, PriceSeries, PriceSource, Resolution,
+        DEFAULT_PRICE_SOURCES, PRICE_SCALE,
+    },
+    time::Date,
+};
+use strum::EnumCount;
+
+use crate::{
+    client::{Client, FetchError, Ready},
+    entsoe, fingrid,
+};
+
+/// Http client shared by all providers
+pub type SharedClient = Mutex<NoopRawMutex, Client<Ready<'static>>>;
+
+#[derive(Debug)]
+pub enum ProviderError {
+    /// Api key of the provider is not stored
+    MissingApiKey,
+    /// Provider does not have prices for the bidding zone
+    UnsupportedZone,
+    Http(reqwless::Error),
+    /// Server responded with a status other than 2xx
+    Status(u16),
+    Entsoe(EntsoeError),
+    Fingrid(FingridError),
+    Mirror(MirrorError),
+}
+
+impl core::fmt::Display for ProviderError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ProviderError::MissingApiKey => write!(f, "api key is not set"),
+            ProviderError::UnsupportedZone => write!(f, "bidding zone is not supported"),
+            ProviderError::Http(e) => write!(f, "http error : {e:?}"),
+            ProviderError::Status(status) => write!(f, "http status {status}"),
+            ProviderError::Entsoe(e) => write!(f, "{e}"),
+            ProviderError::Fingrid(e) => write!(f, "{e}"),
+            ProviderError::Mirror(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl<E: Into<ProviderError>> From<FetchError<E>> for ProviderError {
+    fn from(value: FetchError<E>) -> Self {
+        match value {
+            FetchError::Http(e) => Self::Http(e),
+            FetchError::Status(status) => Self::Status(status),
+            FetchError::Body(e) => e.into(),
+        }
+    }
+}
+
+impl From<EntsoeError> for ProviderError {
+    fn from(value: EntsoeError) -> Self {
+        Self::Entsoe(value)
+    }
+}
+
+impl From<FingridError> for ProviderError {
+    fn from(value: FingridError) -> Self {
+        Self::Fingrid(value)
+    }
+}
+
+impl From<MirrorError> for ProviderError {
+    fn from(value: MirrorError) -> Self {
+        Self::Mirror(value)
+    }
+}
+
+/// Api that has day-ahead prices
+#[allow(async_fn_in_trait)]
+pub trait PriceProvider {
+    /// Fetches prices of `zone` for the delivery day `date` to `series`.
+    ///
+    /// `series` is cleared first and its zone, currency and resolution are set.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the request fails or the response does not
+    /// contain prices. `series` may contain some of the points after an error, and may
+    /// cover only part of the day on success, see [PriceSeries::has_delivery_day].
+    async fn fetch_day_ahead<const N: usize>(
+        &mut self,
+        zone: BiddingZone,
+        date: Date,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), ProviderError>;
+}
+
+/// ENTSO-E Transparency Platform, see [entsoe]
+pub struct EntsoeProvider {
+    client: &'static SharedClient,
+    api_key: String<64>,
+}
+
+impl EntsoeProvider {
+    pub fn new(client: &'static SharedClient, api_key: String<64>) -> Self {
+        Self { client, api_key }
+    }
+}
+
+impl PriceProvider for EntsoeProvider {
+    async fn fetch_day_ahead<const N: usize>(
+        &mut self,
+        zone: BiddingZone,
+        date: Date,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), ProviderError> {
+        let (start, end) = delivery_period(date);
+        let mut client = self.client.lock().await;
+        entsoe::fetch_day_ahead(&mut client, &self.api_key, zone, start, end, series).await?;
+        Ok(())
+    }
+}
+
+/// Fingrid open data, see [fingrid].
+///
+/// Fingrid does not publish day-ahead prices so the imbalance price is used instead.
+/// It is close to the day-ahead price most of the time but it is published only after
+/// each time unit, so this provider can only fill in the past hours of today until
+/// another source has the whole day.
+pub struct FingridProvider {
+    client: &'static SharedClient,
+    api_key: String<64>,
+}
+
+impl FingridProvider {
+    pub fn new(client: &'static SharedClient, api_key: String<64>) -> Self {
+        Self { client, api_key }
+    }
+}
+
+impl PriceProvider for FingridProvider {
+    async fn fetch_day_ahead<const N: usize>(
+        &mut self,
+        zone: BiddingZone,
+        date: Date,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), ProviderError> {
+        if zone != BiddingZone::Fi {
+            return Err(ProviderError::UnsupportedZone);
+        }
+        let (start, end) = delivery_period(date);
+        let mut points = Vec::<DataPoint, N>::new();
+        let mut client = self.client.lock().await;
+        fingrid::fetch_period(
+            &mut client,
+            &self.api_key,
+            dataset::IMBALANCE_PRICE,
+            start,
+            end,
+            &mut points,
+        )
+        .await?;
+
+        series.clear();
+        series.zone = zone;
+        series.currency = Currency::Eur;
+        series.resolution = Resolution::Minutes15;
+        for point in points {
+            // Same capacity as points, values that overlap are skipped
+            let _ = series.push(PricePoint {
+                start: point.start,
+                duration: (point.end - point.start) as u32,
+                price: point.value / (VALUE_SCALE / PRICE_SCALE),
+            });
+        }
+        if series.is_empty() {
+            return Err(FingridError::NoData.into());
+        }
+        Ok(())
+    }
+}
+
+/// Community api that mirrors day-ahead prices, see [shared::mirror]
+pub struct MirrorProvider {
+    client: &'static SharedClient,
+    /// Url without the query, `region` parameter is added to it
+    url: &'static str,
+    format: MirrorFormat,
+}
+
+impl MirrorProvider {
+    /// Prices of today and tomorrow from spot-hinta.fi, prices are in euros
+    pub fn spot_hinta(client: &'static SharedClient) -> Self {
+        Self {
+            client,
+            url: "https://api.spot-hinta.fi/TodayAndDayForward",
+            format: SPOT_HINTA,
+        }
+    }
+}
+
+impl PriceProvider for MirrorProvider {
+    async fn fetch_day_ahead<const N: usize>(
+        &mut self,
+        zone: BiddingZone,
+        date: Date,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), ProviderError> {
+        let mut url = String::<128>::new();
+        // Urls of known mirrors are short enough that this never fails
+        write!(url, "{}?region={}", self.url, zone.code()).unwrap();
+
+        let (start, end) = delivery_period(date);
+        series.clear();
+        series.zone = zone;
+        series.currency = Currency::Eur;
+
+        let mut parser = MirrorParser::new(self.format, start, end);
+        let mut client = self.client.lock().await;
+        client
+            .get_streaming(&url, &[], |chunk| parser.feed(chunk, series))
+            .await?;
+        parser.finish(series)?;
+        Ok(())
+    }
+}
+
+/// Formats sources as a comma separated list for storage.
+///
+/// Every source fits to 64 bytes so this never fails.
+pub fn sources_to_item(sources: &[PriceSource]) -> String<64> {
+    let mut item = String::new();
+    for (i, source) in sources.iter().enumerate() {
+        if i > 0 {
+            item.push(',').unwrap();
+        }
+        item.push_str(source.into()).unwrap();
+    }
+    item
+}
+
+/// Parses list stored with [sources_to_item], unknown sources are skipped.
+///
+/// Returns [DEFAULT_PRICE_SOURCES] if `item` is [None].
+pub fn sources_from_item(item: Option<&str>) -> Vec<PriceSource, { PriceSource::COUNT }> {
+    match item {
+        Some(item) => item
+            .split(',')
+            .filter_map(|source| PriceSource::from_str(source).ok())
+            .take(PriceSource::COUNT)
+            .collect(),
+        None => Vec::from_slice(&DEFAULT_PRICE_SOURCES).unwrap(),
+    }
+}
@@ -0,0 +1,72 @@
+//! Storage of the [RelayRule]s, see [shared::relay]
+
+use core::fmt::Write;
+
+use embassy_sync::{blocking_mutex::raw::CriticalSectionRawMutex, signal::Signal};
+use heapless::String;
+use shared::{
+    price::Resolution,
+    relay::{Condition, RelayRule},
+};
+
+/// Number of relay output pins, see `main`. Rules of other relays are refused.
+pub const RELAY_COUNT: usize = 2;
+
+/// Makes [control_relays](crate::tasks::control_relays) check the rules right away,
+/// signaled when the rules, time or prices change
+pub static RELAYS_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Formats `rule` as comma separated relay, on or off, condition and its parameters
+/// for storage, for example `0,on,cheapest,4,60`. Empty if `rule` is [None].
+///
+/// Numbers take at most 22 bytes so this never fails.
+pub fn rule_to_item(rule: Option<&RelayRule>) -> String<64> {
+    let mut item = String::new();
+    let Some(rule) = rule else {
+        return item;
+    };
+    let on = if rule.on { "on" } else { "off" };
+    write!(item, "{},{on},", rule.relay).unwrap();
+    match rule.condition {
+        Condition::Cheapest { count, resolution } => {
+            write!(item, "cheapest,{count},{}", resolution.seconds() / 60)
+        }
+        Condition::PriceAbove(limit) => write!(item, "above,{limit}"),
+        Condition::PriceBelow(limit) => write!(item, "below,{limit}"),
+        Condition::Between { start, end } => write!(item, "between,{start},{end}"),
+    }
+    .unwrap();
+    item
+}
+
+/// Parses rule stored with [rule_to_item].
+///
+/// Returns [None] if `item` is empty or it is not a valid rule.
+pub fn rule_from_item(item: &str) -> Option<RelayRule> {
+    let mut fields = item.split(',');
+    let relay = fields.next()?.parse().ok()?;
+    let on = match fields.next()? {
+        "on" => true,
+        "off" => false,
+        _ => return None,
+    };
+    let condition = match fields.next()? {
+        "cheapest" => Condition::Cheapest {
+            count: fields.next()?.parse().ok()?,
+            resolution: Resolution::from_seconds(fields.next()?.parse::<u32>().ok()? * 60)?,
+        },
+        "above" => Condition::PriceAbove(fields.next()?.parse().ok()?),
+        "below" => Condition::PriceBelow(fields.next()?.parse().ok()?),
+        "between" => Condition::Between {
+            start: fields.next()?.parse().ok()?,
+            end: fields.next()?.parse().ok()?,
+        },
+        _ => return None,
+    };
+    let rule = RelayRule {
+        relay,
+        condition,
+        on,
+    };
+    rule.validate().ok().map(|()| rule)
+}
@@ -1,24 +1,30 @@
-use core::{fmt::Write, str::FromStr};
+use core::fmt::Write;
 use embassy_executor::Spawner;
+use embassy_futures::select::{select3, Either3};
 use embassy_sync::blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex};
-use embassy_sync::channel::{self, Sender};
+use embassy_sync::channel::{self, Channel, Sender};
 use embedded_io_async::Read;
 use esp_hal::peripherals::UART0;
 use esp_hal::uart::{UartRx, UartTx};
 use esp_hal::Async;
 use esp_hal::{clock::Clocks, uart::Uart};
-use heapless::{String, Vec};
+use heapless::String;
 use shared::{
-    deserialize_crc_cobs, serialize_crc_cobs, DisplayUpdate, Message, Response, MESSAGE_SIZE,
-    RESPONSE_SIZE,
+    deserialize_crc_cobs,
+    frame::{FeedResult, FrameDecoder},
+    serialize_crc_cobs, DeviceError, DeviceFrame, DisplayUpdate, Envelope, Event, Message,
+    ProtocolError, Response, MESSAGE_SIZE, RESPONSE_SIZE,
 };
 
-/// **TODO** Try to read multiple bytes at a time instead of one at a time?
-///
 /// Constructs Uart instance and starts serial read and write tasks
-/// When a full [Message] has been received in [read_serial] it is sent to `broker_sender` channel
+/// When a full [Message] has been received in [read_serial] it is sent to `broker_sender` channel,
+/// frames that fail to decode are dropped, counted and reported to `event_sender`.
 /// When `writer_receiver` receives [Response] it will encode and write it to serial
 ///
+/// Both are wrapped in [Envelope] so that responses carry the id of the message they answer.
+/// [Event]s received from `event_channel` and records from [logger](crate::logger)
+/// are sent to the host between responses.
+///
 /// # Errors
 ///
 /// This function will return an error if spawning a task fails.
@@ -26,8 +32,9 @@ pub async fn setup(
     spawner: &Spawner,
     uart: UART0,
     clocks: &Clocks<'_>,
-    broker_sender: channel::Sender<'static, NoopRawMutex, Message, 10>,
-    writer_receiver: channel::Receiver<'static, NoopRawMutex, Response, 10>,
+    broker_sender: channel::Sender<'static, NoopRawMutex, Envelope<Message>, 10>,
+    writer_receiver: channel::Receiver<'static, NoopRawMutex, Envelope<Response>, 10>,
+    event_channel: &'static Channel<CriticalSectionRawMutex, Event, 10>,
     display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
 ) -> Result<(), SerialError> {
     display_sender.send("Serial init".into()).await;
@@ -35,8 +42,13 @@ pub async fn setup(
     let uart = Uart::new_async(uart, clocks);
     let (tx, rx) = uart.split();
 
-    spawner.spawn(read_serial(rx, broker_sender, display_sender))?;
-    spawner.spawn(write_serial(tx, writer_receiver))?;
+    spawner.spawn(read_serial(
+        rx,
+        broker_sender,
+        event_channel.sender(),
+        display_sender,
+    ))?;
+    spawner.spawn(write_serial(tx, writer_receiver, event_channel.receiver()))?;
 
     display_sender.send("Serial init done".into()).await;
 
@@ -46,25 +58,46 @@ pub async fn setup(
 #[embassy_executor::task]
 async fn read_serial(
     mut rx: UartRx<'static, UART0, Async>,
-    broker_sender: channel::Sender<'static, NoopRawMutex, Message, 10>,
+    broker_sender: channel::Sender<'static, NoopRawMutex, Envelope<Message>, 10>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
     display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
 ) {
-    let mut message = Vec::<u8, MESSAGE_SIZE>::new();
-    let mut buf = [0; 1];
+    let mut decoder = FrameDecoder::<MESSAGE_SIZE>::new();
+    let mut buf = [0; 32];
+    let mut dropped_frames: u32 = 0;
 
     loop {
-        let _ = rx.read_exact(&mut buf).await;
-        if message.is_full() {
-            display_sender
-                .send("Message buffer is full - panicking".into())
-                .await;
-            panic!("Message buffer is full")
-        }
-        message.push(buf[0]).unwrap();
-        if buf[0] == corncobs::ZERO {
-            let deserialized = deserialize_crc_cobs(&message);
-            broker_sender.send(deserialized).await;
-            message.clear();
+        let Ok(bytes_read) = rx.read(&mut buf).await else {
+            continue;
+        };
+        let mut input = &buf[..bytes_read];
+
+        while !input.is_empty() {
+            let (result, remaining) = match decoder.feed(input) {
+                FeedResult::Consumed => break,
+                FeedResult::Frame { frame, remaining } => (
+                    deserialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(frame),
+                    remaining,
+                ),
+                FeedResult::Overflow { remaining } => {
+                    (Err(ProtocolError::BufferOverflow), remaining)
+                }
+            };
+            input = remaining;
+
+            match result {
+                Ok(deserialized) => broker_sender.send(deserialized).await,
+                Err(e) => {
+                    dropped_frames = dropped_frames.wrapping_add(1);
+                    log::warn!("Dropped frame ({dropped_frames}) : {e:?}");
+                    let mut msg = String::<64>::new();
+                    // Message always fits to the string so result can be ignored
+                    let _ = write!(msg, "Dropped frame ({dropped_frames})\n{e:?}");
+                    display_sender.send(DisplayUpdate::StatusUpdate(msg)).await;
+                    // Events are best effort, drop it if the host is not keeping up
+                    let _ = event_sender.try_send(Event::Error(DeviceError::DroppedFrame(e)));
+                }
+            }
         }
     }
 }
@@ -72,13 +105,37 @@ async fn read_serial(
 #[embassy_executor::task]
 async fn write_serial(
     mut tx: UartTx<'static, UART0, Async>,
-    writer_receiver: channel::Receiver<'static, NoopRawMutex, Response, 10>,
+    writer_receiver: channel::Receiver<'static, NoopRawMutex, Envelope<Response>, 10>,
+    event_receiver: channel::Receiver<'static, CriticalSectionRawMutex, Event, 10>,
 ) {
+    let log_receiver = crate::logger::receiver();
+
     loop {
-        let response = writer_receiver.receive().await;
+        let frame = match select3(
+            writer_receiver.receive(),
+            event_receiver.receive(),
+            log_receiver.receive(),
+        )
+        .await
+        {
+            Either3::First(response) => DeviceFrame::Response(response),
+            Either3::Second(event) => DeviceFrame::Event(event),
+            Either3::Third(record) => DeviceFrame::Log(record),
+        };
+        let is_log = matches!(frame, DeviceFrame::Log(_));
         let mut buf = [0; RESPONSE_SIZE];
-        let serialized = serialize_crc_cobs::<Response, RESPONSE_SIZE>(response, &mut buf);
-        tx.write_async(serialized).await.unwrap();
+        // Buffer is sized for the largest frame so this can only fail if the sizes are misconfigured
+        let Ok(serialized) = serialize_crc_cobs::<DeviceFrame, RESPONSE_SIZE>(frame, &mut buf)
+        else {
+            continue;
+        };
+        // Frame is dropped, host notices a missing response and retries the request.
+        // Failures to write log frames are not logged so that they do not feed themselves.
+        if let Err(e) = tx.write_async(serialized).await {
+            if !is_log {
+                log::warn!("Writing frame to serial failed : {e:?}");
+            }
+        }
     }
 }
 
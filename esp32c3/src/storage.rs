@@ -9,6 +9,14 @@ use sequential_storage::{
     map::{fetch_item, store_item, SerializationError},
 };
 use serde::{Deserialize, Serialize};
+use shared::{
+    price::PriceSeries,
+    price_store::{
+        load_history, load_series, save_day, save_series, PriceStoreError, DAY_BUFFER_SIZE,
+        SERIES_BUFFER_SIZE,
+    },
+    statistics::{DailyStats, History},
+};
 
 pub enum StorageMessage {
     Fetch,
@@ -38,8 +46,8 @@ impl NonVolatileStorage {
         critical_section::with(|_| unsafe {
             if _NON_VOLATILE_STORAGE_TAKEN {
                 panic!("NonVolatileStorage already taken");
-                _NON_VOLATILE_STORAGE_TAKEN = true;
             }
+            _NON_VOLATILE_STORAGE_TAKEN = true;
             Self::steal()
         })
     }
@@ -149,38 +157,26 @@ pub enum NonVolatileKey {
     WifiPassword,
     FingridApiKey,
     EntsoeApiKey,
+    /// Comma separated Fingrid dataset ids
+    FingridDatasets,
+    /// Comma separated [PriceSource](shared::price::PriceSource)s in the order they are tried
+    PriceSources,
+    /// [BiddingZone::code](shared::price::BiddingZone::code) of the selected market area
+    BiddingZone,
+    /// [Tariff](shared::tariff::Tariff) stored with [tariff_to_item](crate::tariff::tariff_to_item)
+    Tariff,
+    /// [RelayRule](shared::relay::RelayRule) at the index stored with
+    /// [rule_to_item](crate::relay::rule_to_item), empty if there is no rule
+    RelayRule(u8),
 }
 
 impl sequential_storage::map::Key for NonVolatileKey {
     fn serialize_into(&self, buffer: &mut [u8]) -> Result<usize, SerializationError> {
-        let buf = postcard::to_slice(self, buffer).map_err(|e| match e {
-            postcard::Error::SerializeBufferFull => SerializationError::BufferTooSmall,
-            postcard::Error::DeserializeBadVarint
-            | postcard::Error::DeserializeBadBool
-            | postcard::Error::DeserializeBadChar
-            | postcard::Error::DeserializeBadUtf8
-            | postcard::Error::DeserializeBadOption
-            | postcard::Error::DeserializeBadEnum
-            | postcard::Error::DeserializeBadEncoding => SerializationError::InvalidData,
-            _ => SerializationError::Custom(0),
-        })?;
-        Ok(buf.len())
+        shared::map_key::serialize_key(self, buffer)
     }
 
     fn deserialize_from(buffer: &[u8]) -> Result<(Self, usize), SerializationError> {
-        let key: NonVolatileKey = postcard::from_bytes(buffer).map_err(|e| match e {
-            postcard::Error::SerializeBufferFull => SerializationError::BufferTooSmall,
-            postcard::Error::DeserializeBadVarint
-            | postcard::Error::DeserializeBadBool
-            | postcard::Error::DeserializeBadChar
-            | postcard::Error::DeserializeBadUtf8
-            | postcard::Error::DeserializeBadOption
-            | postcard::Error::DeserializeBadEnum
-            | postcard::Error::DeserializeBadEncoding => SerializationError::InvalidData,
-            _ => SerializationError::Custom(0),
-        })?;
-
-        Ok((key, NonVolatileKey::POSTCARD_MAX_SIZE))
+        shared::map_key::deserialize_key(buffer)
     }
 }
 
@@ -202,6 +198,26 @@ pub enum StorageError {
     ItemTooBig,
 }
 
+impl From<StorageError> for shared::StorageFailure {
+    fn from(value: StorageError) -> Self {
+        match value {
+            StorageError::Storage => Self::Storage,
+            StorageError::FullStorage => Self::FullStorage,
+            StorageError::Corrupted => Self::Corrupted,
+            StorageError::BufferTooBig => Self::BufferTooBig,
+            StorageError::BufferTooSmall(s) => Self::BufferTooSmall(s as u32),
+            StorageError::SerializationError => Self::SerializationError,
+            StorageError::ItemTooBig => Self::ItemTooBig,
+        }
+    }
+}
+
+impl From<StorageError> for shared::ResponseError {
+    fn from(value: StorageError) -> Self {
+        Self::Storage(value.into())
+    }
+}
+
 impl From<sequential_storage::Error<esp_storage::FlashStorageError>> for StorageError {
     fn from(value: sequential_storage::Error<esp_storage::FlashStorageError>) -> Self {
         match value {
@@ -216,3 +232,97 @@ impl From<sequential_storage::Error<esp_storage::FlashStorageError>> for Storage
         }
     }
 }
+
+impl From<PriceStoreError<esp_storage::FlashStorageError>> for StorageError {
+    fn from(value: PriceStoreError<esp_storage::FlashStorageError>) -> Self {
+        match value {
+            PriceStoreError::Storage(e) => e.into(),
+            PriceStoreError::Serialization => Self::SerializationError,
+        }
+    }
+}
+
+/// Latest day-ahead prices and the history of daily statistics kept in flash over reboots,
+/// see [shared::price_store].
+///
+/// Uses the `prices` and `history` partitions of `partitions.csv`, separate from the NVS
+/// range of [NonVolatileStorage] so that rewriting prices does not wear it.
+pub struct PriceStorage {
+    flash: BlockingAsync<FlashStorage>,
+    buffer: [u8; SERIES_BUFFER_SIZE],
+}
+
+impl PriceStorage {
+    const FLASH_RANGE: core::ops::Range<u32> = 0x3F_0000..0x40_0000;
+    const HISTORY_RANGE: core::ops::Range<u32> = 0x3E_0000..0x3F_0000;
+
+    /// Returns instance of [PriceStorage] **once**.
+    ///
+    /// If called multiple times will panic
+    pub fn take() -> Self {
+        static mut _PRICE_STORAGE_TAKEN: bool = false;
+        critical_section::with(|_| unsafe {
+            if _PRICE_STORAGE_TAKEN {
+                panic!("PriceStorage already taken");
+            }
+            _PRICE_STORAGE_TAKEN = true;
+        });
+        Self {
+            flash: BlockingAsync::new(FlashStorage::new()),
+            buffer: [0; SERIES_BUFFER_SIZE],
+        }
+    }
+
+    /// Returns the newest stored series, [None] if there is none
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading the flash fails or it is corrupted.
+    pub async fn load(&mut self) -> Result<Option<PriceSeries>, StorageError> {
+        load_series(&mut self.flash, Self::FLASH_RANGE, &mut self.buffer)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    /// Stores `series` to be loaded with [Self::load] after a reboot
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing the flash fails or it is corrupted.
+    pub async fn save(&mut self, series: &PriceSeries) -> Result<(), StorageError> {
+        save_series(&mut self.flash, Self::FLASH_RANGE, series, &mut self.buffer)
+            .await
+            .map_err(|e| e.into())
+    }
+
+    /// Returns the days stored with [Self::save_day]
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if reading the flash fails or it is corrupted.
+    pub async fn load_history(&mut self) -> Result<History, StorageError> {
+        load_history(
+            &mut self.flash,
+            Self::HISTORY_RANGE,
+            &mut self.buffer[..DAY_BUFFER_SIZE],
+        )
+        .await
+        .map_err(|e| e.into())
+    }
+
+    /// Adds `day` to the history loaded with [Self::load_history] after a reboot
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if writing the flash fails or it is corrupted.
+    pub async fn save_day(&mut self, day: &DailyStats) -> Result<(), StorageError> {
+        save_day(
+            &mut self.flash,
+            Self::HISTORY_RANGE,
+            day,
+            &mut self.buffer[..DAY_BUFFER_SIZE],
+        )
+        .await
+        .map_err(|e| e.into())
+    }
+}
@@ -0,0 +1,54 @@
+//! Storage of the [Tariff] used for consumer prices, see [shared::tariff]
+
+use core::{fmt::Write, str::FromStr};
+
+use heapless::String;
+use shared::{
+    tariff::{Tariff, TaxClass, DEFAULT_TARIFF},
+    time_of_use::TransferSchedule,
+};
+
+/// Formats `tariff` as comma separated vat, margin, tax class and transfer fee for storage,
+/// followed by `night` or `winter` and its fee if the transfer fee has a schedule.
+///
+/// Fields take at most 46 bytes so this never fails.
+pub fn tariff_to_item(tariff: &Tariff) -> String<64> {
+    let mut item = String::new();
+    let tax_class: &str = tariff.tax_class.into();
+    write!(
+        item,
+        "{},{},{tax_class},{}",
+        tariff.vat, tariff.margin, tariff.transfer_fee
+    )
+    .unwrap();
+    match tariff.transfer_schedule {
+        TransferSchedule::Flat => {}
+        TransferSchedule::Night(fee) => write!(item, ",night,{fee}").unwrap(),
+        TransferSchedule::WinterWeekday(fee) => write!(item, ",winter,{fee}").unwrap(),
+    }
+    item
+}
+
+/// Parses tariff stored with [tariff_to_item].
+///
+/// Returns [DEFAULT_TARIFF] if `item` is [None] or it is not a valid tariff.
+pub fn tariff_from_item(item: Option<&str>) -> Tariff {
+    item.and_then(parse).unwrap_or(DEFAULT_TARIFF)
+}
+
+fn parse(item: &str) -> Option<Tariff> {
+    let mut fields = item.split(',');
+    let tariff = Tariff {
+        vat: fields.next()?.parse().ok()?,
+        margin: fields.next()?.parse().ok()?,
+        tax_class: TaxClass::from_str(fields.next()?).ok()?,
+        transfer_fee: fields.next()?.parse().ok()?,
+        transfer_schedule: match fields.next() {
+            None => TransferSchedule::Flat,
+            Some("night") => TransferSchedule::Night(fields.next()?.parse().ok()?),
+            Some("winter") => TransferSchedule::WinterWeekday(fields.next()?.parse().ok()?),
+            Some(_) => return None,
+        },
+    };
+    tariff.validate().ok().map(|()| tariff)
+}
@@ -1,54 +1,808 @@
-use core::str::FromStr;
+use core::{fmt::Write, str::FromStr};
 
+use embassy_futures::select::select;
 use embassy_sync::{
     blocking_mutex::raw::{CriticalSectionRawMutex, NoopRawMutex},
-    channel::{Receiver, Sender},
+    channel::{Channel, Receiver, Sender},
     mutex::Mutex,
+    signal::Signal,
+};
+use embassy_time::{Duration, Timer};
+use esp_hal::gpio::AnyOutput;
+use heapless::{String, Vec};
+use shared::{
+    emission::{Emissions, TREND_HOURS},
+    fingrid::{dataset, DataPoint, DatasetId, FingridError, MAX_DATASETS},
+    grid::GridAlert,
+    price::{
+        BiddingZone, Currency, DayAheadSchedule, PriceSeries, PriceSource, Resolution, Timestamp,
+        DEFAULT_BIDDING_ZONE,
+    },
+    relay::{relay_states, RelayRules, MAX_RELAYS, MAX_RELAY_RULES},
+    statistics::{DailyStats, History, Statistics},
+    tariff::{PriceSummary, Tariff, DEFAULT_TARIFF},
+    time::Date,
+    transfer::TransferFrame,
+    validate_api_key, validate_time, Capabilities, DeviceError, DeviceInfo, DisplayUpdate,
+    Envelope, Event, Message, Response, ResponseError, ValidationError, PROTOCOL_VERSION,
 };
-use heapless::String;
-use shared::{DisplayUpdate, Message, Response};
 
-use crate::storage::{NonVolatileKey, NonVolatileStorage};
+use crate::{
+    client::FetchError,
+    clock, fingrid,
+    provider::{
+        self, EntsoeProvider, FingridProvider, MirrorProvider, PriceProvider, ProviderError,
+        SharedClient,
+    },
+    relay,
+    storage::{NonVolatileKey, NonVolatileStorage, PriceStorage, StorageError},
+    tariff,
+    transfer::TransferBuffer,
+};
 
+/// Handles [Message]s received from serial.
+///
+/// Every message gets exactly one [Response] with the same id, sent to `serial_writer_sender`.
+/// Storage failures are also reported with [Event::StorageWarning].
 #[embassy_executor::task]
 pub async fn broker(
-    broker_receiver: Receiver<'static, NoopRawMutex, Message, 10>,
-    serial_writer_sender: Sender<'static, NoopRawMutex, Response, 10>,
+    broker_receiver: Receiver<'static, NoopRawMutex, Envelope<Message>, 10>,
+    serial_writer_sender: Sender<'static, NoopRawMutex, Envelope<Response>, 10>,
     display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
     nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
 ) {
+    let mut transfer = TransferBuffer::<TRANSFER_CAPACITY>::new();
+
     loop {
-        let message = broker_receiver.receive().await;
+        let Envelope { id, payload } = broker_receiver.receive().await;
         display_sender.send("Serial message received".into()).await;
 
-        match message {
-            Message::Wifi(_) => {
-                serial_writer_sender.send(Response::Ok).await;
-                display_sender.send("Wifi info got".into()).await;
+        let response = handle_message(payload, display_sender, nvs_storage, &mut transfer)
+            .await
+            .unwrap_or_else(Response::Error);
+
+        if let Response::Error(e) = &response {
+            log::warn!("Message {id} failed : {e}");
+        }
+        if let Response::Error(ResponseError::Storage(failure)) = response {
+            let _ = event_sender.try_send(Event::StorageWarning(failure));
+        }
+
+        serial_writer_sender.send(Envelope::new(id, response)).await;
+    }
+}
+
+async fn handle_message(
+    message: Message,
+    display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+    transfer: &mut TransferBuffer<TRANSFER_CAPACITY>,
+) -> Result<Response, ResponseError> {
+    match message {
+        Message::Hello(host_version) => {
+            if host_version != PROTOCOL_VERSION {
+                display_sender
+                    .send("Host protocol version\ndoes not match".into())
+                    .await;
             }
-            Message::FingridApiKey(key) => {
-                let mut nvs_guard = nvs_storage.lock().await;
-                nvs_guard
-                    .store(NonVolatileKey::FingridApiKey, key)
-                    .await
-                    .unwrap();
+            return Ok(Response::Hello(device_info()));
+        }
+        Message::Wifi(info) => {
+            info.validate()?;
+            let mut nvs_guard = nvs_storage.lock().await;
+            nvs_guard
+                .store(NonVolatileKey::WifiSsid, to_item(info.get_ssid()))
+                .await?;
+            nvs_guard
+                .store(NonVolatileKey::WifiPassword, to_item(info.get_password()))
+                .await?;
+            display_sender.send("Wifi info got".into()).await;
+        }
+        Message::FingridApiKey(key) => {
+            validate_api_key(&key)?;
+            let mut nvs_guard = nvs_storage.lock().await;
+            nvs_guard.store(NonVolatileKey::FingridApiKey, key).await?;
+        }
+        Message::EntsoeApiKey(key) => {
+            validate_api_key(&key)?;
+            let mut nvs_guard = nvs_storage.lock().await;
+            nvs_guard.store(NonVolatileKey::EntsoeApiKey, key).await?;
+        }
+        Message::FingridDatasets(datasets) => {
+            if datasets.is_empty() {
+                return Err(ValidationError::Empty.into());
             }
-            Message::EntsoeApiKey(key) => {
-                let mut nvs_guard = nvs_storage.lock().await;
-                nvs_guard
-                    .store(NonVolatileKey::EntsoeApiKey, key)
-                    .await
-                    .unwrap();
+            let mut nvs_guard = nvs_storage.lock().await;
+            nvs_guard
+                .store(
+                    NonVolatileKey::FingridDatasets,
+                    fingrid::datasets_to_item(&datasets),
+                )
+                .await?;
+        }
+        Message::PriceSources(sources) => {
+            if sources.is_empty() {
+                return Err(ValidationError::Empty.into());
             }
-            Message::Display(s) => {
-                display_sender.send(s.into()).await;
+            let mut nvs_guard = nvs_storage.lock().await;
+            nvs_guard
+                .store(
+                    NonVolatileKey::PriceSources,
+                    provider::sources_to_item(&sources),
+                )
+                .await?;
+        }
+        Message::SetBiddingZone(zone) => {
+            let mut nvs_guard = nvs_storage.lock().await;
+            nvs_guard
+                .store(NonVolatileKey::BiddingZone, to_item(zone.code()))
+                .await?;
+            let mut msg = String::<64>::new();
+            let _ = write!(msg, "Bidding zone {}", zone.code());
+            display_sender.send(DisplayUpdate::StatusUpdate(msg)).await;
+            SCHEDULE_CHANGED.signal(());
+        }
+        Message::SetTariff(tariff) => {
+            tariff.validate()?;
+            let mut nvs_guard = nvs_storage.lock().await;
+            nvs_guard
+                .store(NonVolatileKey::Tariff, tariff::tariff_to_item(&tariff))
+                .await?;
+            display_sender.send(DisplayUpdate::Prices(tariff)).await;
+        }
+        Message::Display(s) => {
+            display_sender.send(s.into()).await;
+        }
+        Message::Transfer(frame) => {
+            let ack = transfer.handle(&frame)?;
+            if let (TransferFrame::End { .. }, Some(payload)) = (&frame, transfer.payload()) {
+                let mut msg = String::<64>::new();
+                let _ = write!(
+                    msg,
+                    "Transfer {} done\n{} bytes",
+                    ack.transfer,
+                    payload.len()
+                );
+                display_sender.send(DisplayUpdate::StatusUpdate(msg)).await;
+            }
+            return Ok(Response::Transfer(ack));
+        }
+        Message::SetTime(now) => {
+            validate_time(now, clock::build_time())?;
+            clock::set(now);
+            relay::RELAYS_CHANGED.signal(());
+            SCHEDULE_CHANGED.signal(());
+        }
+        Message::PlanWindow(requests) => {
+            if requests.is_empty() {
+                return Err(ValidationError::Empty.into());
+            }
+            for request in &requests {
+                request.validate()?;
+            }
+            // Display locks the prices too, so the guard is dropped before sending to it
+            let (plans, msg) = {
+                let series = DAY_AHEAD_PRICES.lock().await;
+                let time_zone = series.zone.time_zone();
+                let mut msg = String::<64>::new();
+                let mut plans = Vec::new();
+                for request in &requests {
+                    let plan = request.plan(&series);
+                    if let Some(plan) = &plan {
+                        let start = time_zone.to_local(plan.start);
+                        if !msg.is_empty() {
+                            let _ = msg.push('\n');
+                        }
+                        let _ = write!(
+                            msg,
+                            "Run {} at {:02}:{:02}",
+                            request.name, start.hour, start.minute
+                        );
+                    }
+                    // Same capacity as requests
+                    let _ = plans.push(plan);
+                }
+                (plans, msg)
+            };
+            if !msg.is_empty() {
+                display_sender.send(DisplayUpdate::StatusUpdate(msg)).await;
             }
+            return Ok(Response::Plans(plans));
         }
+        Message::SetRelayRules(rules) => {
+            for rule in &rules {
+                rule.validate()?;
+                if rule.relay as usize >= relay::RELAY_COUNT {
+                    return Err(ValidationError::OutOfRange.into());
+                }
+            }
+            let mut nvs_guard = nvs_storage.lock().await;
+            for i in 0..MAX_RELAY_RULES {
+                nvs_guard
+                    .store(
+                        NonVolatileKey::RelayRule(i as u8),
+                        relay::rule_to_item(rules.get(i)),
+                    )
+                    .await?;
+            }
+            relay::RELAYS_CHANGED.signal(());
+        }
+        Message::GetStatistics => return Ok(Response::Statistics(statistics().await)),
     }
+    Ok(Response::Ok)
+}
+
+/// Largest payload accepted with chunked transfer
+const TRANSFER_CAPACITY: usize = 4096;
+
+/// Features compiled in to this firmware
+const CAPABILITIES: Capabilities = Capabilities::DISPLAY_STATUS
+    .union(Capabilities::CHUNKED_TRANSFER)
+    .union(Capabilities::LOG_FORWARDING)
+    .union(Capabilities::ENTSOE_PRICES)
+    .union(Capabilities::FINGRID_DATA)
+    .union(Capabilities::DISPLAY_PRICES)
+    .union(Capabilities::PRICE_SOURCES)
+    .union(Capabilities::TARIFF)
+    .union(Capabilities::PLANNER)
+    .union(Capabilities::RELAYS)
+    .union(Capabilities::STATISTICS)
+    .union(Capabilities::GRID_ALERTS)
+    .union(Capabilities::EMISSIONS);
+
+fn device_info() -> DeviceInfo {
+    DeviceInfo {
+        protocol_version: PROTOCOL_VERSION,
+        firmware_version: String::from_str(env!("CARGO_PKG_VERSION")).unwrap(),
+        build_hash: String::from_str(env!("BUILD_HASH")).unwrap_or_default(),
+        capabilities: CAPABILITIES,
+    }
+}
+
+/// [WifiInfo](shared::WifiInfo) fields have the same capacity as stored items and
+/// zone codes are short, so this never fails
+fn to_item(s: &str) -> String<64> {
+    String::from_str(s).unwrap()
 }
 
 #[embassy_executor::task]
 pub async fn perform_http_request() {}
 
+/// Delivery days requested with [request_day_ahead_prices]
+static DAY_AHEAD_REQUEST: Channel<CriticalSectionRawMutex, Date, 2> = Channel::new();
+
+/// Signaled when the time or the bidding zone changes, so that
+/// [schedule_day_ahead_prices] checks the prices again
+static SCHEDULE_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// Latest day-ahead prices fetched by [get_day_ahead_prices]
+pub static DAY_AHEAD_PRICES: Mutex<CriticalSectionRawMutex, PriceSeries> = Mutex::new(
+    PriceSeries::new(DEFAULT_BIDDING_ZONE, Currency::Eur, Resolution::Minutes60),
+);
+
+/// Daily statistics of the prices fetched by [get_day_ahead_prices] during the last month
+pub static PRICE_HISTORY: Mutex<CriticalSectionRawMutex, History> = Mutex::new(History::new());
+
+/// Asks [get_day_ahead_prices] to fetch prices for the delivery day `date`.
+///
+/// Dropped if two requests are already waiting.
+pub fn request_day_ahead_prices(date: Date) {
+    let _ = DAY_AHEAD_REQUEST.try_send(date);
+}
+
+/// Requests the prices of today and, once the auction results are published, tomorrow
+/// whenever they are missing from [DAY_AHEAD_PRICES].
+///
+/// Nothing is requested until the time is set with [Message::SetTime]. Missing prices
+/// are requested again every [RETRY_INTERVAL](shared::price::RETRY_INTERVAL) and
+/// the prices are checked again after every publication, time change and
+/// [Message::SetBiddingZone].
+#[embassy_executor::task]
+pub async fn schedule_day_ahead_prices(
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+) {
+    loop {
+        let Some(now) = clock::now() else {
+            SCHEDULE_CHANGED.wait().await;
+            continue;
+        };
+        let zone = bidding_zone(nvs_storage).await;
+        let schedule = DayAheadSchedule::new(&*DAY_AHEAD_PRICES.lock().await, zone, now);
+        for date in schedule.dates {
+            request_day_ahead_prices(date);
+        }
+        let wait = Duration::from_secs((schedule.next_check - now).max(0) as u64);
+        select(Timer::after(wait), SCHEDULE_CHANGED.wait()).await;
+    }
+}
+
+/// Loads the prices and history saved by [get_day_ahead_prices] before the reboot to
+/// [DAY_AHEAD_PRICES] and [PRICE_HISTORY] and draws them, so that they are shown before
+/// the network is up
+pub async fn restore_day_ahead_prices(
+    price_storage: &mut PriceStorage,
+    display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+) {
+    match price_storage.load_history().await {
+        Ok(history) => {
+            log::info!("Loaded {} days of price history", history.days().len());
+            *PRICE_HISTORY.lock().await = history;
+        }
+        Err(e) => log::warn!("Loading price history failed : {e:?}"),
+    }
+    match price_storage.load().await {
+        Ok(Some(series)) => {
+            log::info!("Loaded {} {} prices", series.len(), series.zone.code());
+            DAY_AHEAD_PRICES.lock().await.clone_from(&series);
+            relay::RELAYS_CHANGED.signal(());
+            let tariff = tariff(nvs_storage).await;
+            display_sender.send(DisplayUpdate::Prices(tariff)).await;
+        }
+        Ok(None) => log::info!("No saved prices"),
+        Err(e) => log::warn!("Loading saved prices failed : {e:?}"),
+    }
+}
+
+/// Fetches day-ahead prices whenever they are requested with [request_day_ahead_prices]
+/// and stores them to [DAY_AHEAD_PRICES] and `price_storage`.
+///
+/// Prices of the previous days are kept as long as they fit, so that today's prices are
+/// still shown after tomorrow's are fetched. Every complete local day is also added to
+/// [PRICE_HISTORY] and saved.
+/// Prices are fetched for the zone selected with [Message::SetBiddingZone] and
+/// sources configured with [Message::PriceSources] are tried in order until one has the
+/// whole delivery day. Prices of a source that has only part of it are kept meanwhile.
+/// Result is reported with [Event::PriceDataFetched] and [Event::PriceSummary] or
+/// [DeviceError::FetchFailed], and new prices are drawn to the display.
+#[embassy_executor::task]
+pub async fn get_day_ahead_prices(
+    client: &'static SharedClient,
+    display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+    mut price_storage: PriceStorage,
+) {
+    let mut series = PriceSeries::new(DEFAULT_BIDDING_ZONE, Currency::Eur, Resolution::Minutes60);
+
+    loop {
+        let date = DAY_AHEAD_REQUEST.receive().await;
+        let zone = bidding_zone(nvs_storage).await;
+
+        let sources = match nvs_storage
+            .lock()
+            .await
+            .fetch(NonVolatileKey::PriceSources)
+            .await
+        {
+            Ok(item) => provider::sources_from_item(item.as_ref().map(AsRef::as_ref)),
+            Err(e) => {
+                log::warn!("Reading price sources failed : {e:?}");
+                provider::sources_from_item(None)
+            }
+        };
+
+        let mut fetched = false;
+        for source in sources {
+            let name: &str = source.into();
+            let result =
+                fetch_day_ahead(source, client, nvs_storage, zone, date, &mut series).await;
+            match result {
+                Ok(()) if series.has_delivery_day(zone, date) => {
+                    log::info!(
+                        "Fetched {} {} prices from {name}",
+                        series.len(),
+                        zone.code()
+                    );
+                    fetched = true;
+                    break;
+                }
+                // Part of the day is shown until another source or a retry has all of it
+                Ok(()) => {
+                    log::warn!(
+                        "Fetched only {} {} prices from {name}",
+                        series.len(),
+                        zone.code()
+                    );
+                    publish_day_ahead_prices(
+                        &mut series,
+                        display_sender,
+                        event_sender,
+                        nvs_storage,
+                        &mut price_storage,
+                    )
+                    .await;
+                }
+                Err(e) => log::warn!("Fetching prices from {name} failed : {e}"),
+            }
+        }
+
+        if fetched {
+            publish_day_ahead_prices(
+                &mut series,
+                display_sender,
+                event_sender,
+                nvs_storage,
+                &mut price_storage,
+            )
+            .await;
+        } else {
+            let _ = event_sender.try_send(Event::Error(DeviceError::FetchFailed));
+        }
+    }
+}
+
+/// Adds the prices kept in [DAY_AHEAD_PRICES] to `series`, saves it and replaces
+/// [DAY_AHEAD_PRICES] with it
+async fn publish_day_ahead_prices(
+    series: &mut PriceSeries,
+    display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+    price_storage: &mut PriceStorage,
+) {
+    series.keep_previous(&*DAY_AHEAD_PRICES.lock().await);
+    if let Err(e) = price_storage.save(series).await {
+        log::warn!("Saving prices failed : {e:?}");
+        let _ = event_sender.try_send(Event::StorageWarning(e.into()));
+    }
+    if let Err(e) = record_history(series, price_storage).await {
+        log::warn!("Saving price history failed : {e:?}");
+        let _ = event_sender.try_send(Event::StorageWarning(e.into()));
+    }
+    let tariff = tariff(nvs_storage).await;
+    let _ = event_sender.try_send(Event::PriceDataFetched(series.len() as u16));
+    if let Some(summary) = PriceSummary::new(series, &tariff) {
+        let _ = event_sender.try_send(Event::PriceSummary(summary));
+    }
+    DAY_AHEAD_PRICES.lock().await.clone_from(series);
+    relay::RELAYS_CHANGED.signal(());
+    display_sender.send(DisplayUpdate::Prices(tariff)).await;
+}
+
+/// Adds the complete local days of `series` to [PRICE_HISTORY] and saves the days that
+/// changed to `price_storage`
+async fn record_history(
+    series: &PriceSeries,
+    price_storage: &mut PriceStorage,
+) -> Result<(), StorageError> {
+    let time_zone = series.zone.time_zone();
+    let mut history = PRICE_HISTORY.lock().await;
+    for (date, points) in series.local_days(time_zone) {
+        let Some(day) = DailyStats::new(date, points, time_zone) else {
+            continue;
+        };
+        if history.insert(day) {
+            price_storage.save_day(&day).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Statistics of the current local day in [PRICE_HISTORY], or the latest day in it
+/// if the time has not been set. History is empty if neither is known.
+pub async fn statistics() -> Statistics {
+    let time_zone = DAY_AHEAD_PRICES.lock().await.zone.time_zone();
+    let history = PRICE_HISTORY.lock().await;
+    let date = clock::now()
+        .map(|now| time_zone.date(now))
+        .or_else(|| history.latest().map(|day| day.date))
+        .unwrap_or(Date::from_days(0));
+    history.statistics(date)
+}
+
+/// Reads the selected zone from storage, [DEFAULT_BIDDING_ZONE] if it is not set
+async fn bidding_zone(
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+) -> BiddingZone {
+    match nvs_storage
+        .lock()
+        .await
+        .fetch(NonVolatileKey::BiddingZone)
+        .await
+    {
+        Ok(Some(item)) => BiddingZone::from_code(&item.0).unwrap_or_else(|| {
+            log::warn!("Unknown bidding zone {}", item.0.as_str());
+            DEFAULT_BIDDING_ZONE
+        }),
+        Ok(None) => DEFAULT_BIDDING_ZONE,
+        Err(e) => {
+            log::warn!("Reading bidding zone failed : {e:?}");
+            DEFAULT_BIDDING_ZONE
+        }
+    }
+}
+
+/// Reads the tariff from storage, [DEFAULT_TARIFF] if it is not set
+async fn tariff(nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>) -> Tariff {
+    match nvs_storage.lock().await.fetch(NonVolatileKey::Tariff).await {
+        Ok(item) => tariff::tariff_from_item(item.as_ref().map(AsRef::as_ref)),
+        Err(e) => {
+            log::warn!("Reading tariff failed : {e:?}");
+            DEFAULT_TARIFF
+        }
+    }
+}
+
+/// Fetches prices from a single `source`, reading its api key from storage
+async fn fetch_day_ahead<const N: usize>(
+    source: PriceSource,
+    client: &'static SharedClient,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+    zone: BiddingZone,
+    date: Date,
+    series: &mut PriceSeries<N>,
+) -> Result<(), ProviderError> {
+    match source {
+        PriceSource::Entsoe => {
+            let api_key = api_key(nvs_storage, NonVolatileKey::EntsoeApiKey)
+                .await
+                .ok_or(ProviderError::MissingApiKey)?;
+            EntsoeProvider::new(client, api_key)
+                .fetch_day_ahead(zone, date, series)
+                .await
+        }
+        PriceSource::Fingrid => {
+            let api_key = api_key(nvs_storage, NonVolatileKey::FingridApiKey)
+                .await
+                .ok_or(ProviderError::MissingApiKey)?;
+            FingridProvider::new(client, api_key)
+                .fetch_day_ahead(zone, date, series)
+                .await
+        }
+        PriceSource::SpotHinta => {
+            MirrorProvider::spot_hinta(client)
+                .fetch_day_ahead(zone, date, series)
+                .await
+        }
+    }
+}
+
+/// Fingrid real-time datasets are updated every 3 minutes
+const FINGRID_INTERVAL: Duration = Duration::from_secs(3 * 60);
+
+/// Latest values of the configured Fingrid datasets, see [get_fingrid_data]
+pub static FINGRID_DATA: Mutex<CriticalSectionRawMutex, Vec<DataPoint, MAX_DATASETS>> =
+    Mutex::new(Vec::new());
+
+/// Fetches the latest value of every dataset configured with [Message::FingridDatasets]
+/// to [FINGRID_DATA] every [FINGRID_INTERVAL].
+///
+/// Nothing is fetched until the api key is set. Failed datasets are reported with
+/// [DeviceError::FetchFailed] and their previous value is dropped.
+#[embassy_executor::task]
+pub async fn get_fingrid_data(
+    client: &'static SharedClient,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+) {
+    loop {
+        if let Some(api_key) = api_key(nvs_storage, NonVolatileKey::FingridApiKey).await {
+            let datasets = match nvs_storage
+                .lock()
+                .await
+                .fetch(NonVolatileKey::FingridDatasets)
+                .await
+            {
+                Ok(item) => fingrid::datasets_from_item(item.as_ref().map(AsRef::as_ref)),
+                Err(e) => {
+                    log::warn!("Reading Fingrid datasets failed : {e:?}");
+                    fingrid::datasets_from_item(None)
+                }
+            };
+
+            let mut values = Vec::new();
+            for dataset in datasets {
+                match fingrid::fetch_latest(&mut *client.lock().await, &api_key, dataset).await {
+                    Ok(point) => {
+                        // Same capacity as datasets
+                        let _ = values.push(point);
+                    }
+                    Err(e) => {
+                        log::warn!("Fingrid dataset {dataset} fetch failed : {e}");
+                        let _ = event_sender.try_send(Event::Error(DeviceError::FetchFailed));
+                    }
+                }
+            }
+            log::info!("Fetched {} Fingrid datasets", values.len());
+            *FINGRID_DATA.lock().await = values;
+        }
+
+        Timer::after(FINGRID_INTERVAL).await;
+    }
+}
+
+/// Datasets combined to a [GridAlert] by [watch_grid]
+const GRID_DATASETS: [DatasetId; 2] = [dataset::POWER_SYSTEM_STATE, dataset::SHORTAGE_STATUS];
+
+/// Fetches the power system state and shortage status every [FINGRID_INTERVAL] and
+/// reports every change with [Event::GridAlert] and [DisplayUpdate::GridAlert], which
+/// covers the display with a banner while the state is not normal.
+///
+/// Nothing is fetched until the Fingrid api key is set. A dataset that fails to fetch is
+/// reported with [DeviceError::FetchFailed] and keeps its previous value.
+#[embassy_executor::task]
+pub async fn watch_grid(
+    client: &'static SharedClient,
+    display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+) {
+    let mut alert = GridAlert::default();
+    loop {
+        if let Some(api_key) = api_key(nvs_storage, NonVolatileKey::FingridApiKey).await {
+            let previous = alert;
+            for dataset in GRID_DATASETS {
+                match fingrid::fetch_latest(&mut *client.lock().await, &api_key, dataset).await {
+                    Ok(point) => alert.update(&point),
+                    Err(e) => {
+                        log::warn!("Fingrid dataset {dataset} fetch failed : {e}");
+                        let _ = event_sender.try_send(Event::Error(DeviceError::FetchFailed));
+                    }
+                }
+            }
+            if alert != previous {
+                log::warn!(
+                    "Power system {:?}, shortage {:?}",
+                    alert.state,
+                    alert.shortage
+                );
+                let _ = event_sender.try_send(Event::GridAlert(alert));
+                display_sender.send(DisplayUpdate::GridAlert(alert)).await;
+            }
+        }
+
+        Timer::after(FINGRID_INTERVAL).await;
+    }
+}
+
+/// Latest emission factors of electricity and their trend, see [get_emissions]
+pub static EMISSIONS: Mutex<CriticalSectionRawMutex, Emissions> = Mutex::new(Emissions::new());
+
+/// Datasets fetched to [EMISSIONS] by [get_emissions]
+const EMISSION_DATASETS: [DatasetId; 2] = [
+    dataset::CONSUMPTION_EMISSIONS,
+    dataset::PRODUCTION_EMISSIONS,
+];
+
+/// Fetches the latest emission factors to [EMISSIONS] every [FINGRID_INTERVAL] and asks
+/// the display to redraw them with [DisplayUpdate::Emissions].
+///
+/// Once the time is set with [Message::SetTime], hours of the trend that have no values,
+/// such as the ones before the boot, are also fetched. Nothing is fetched
+/// until the Fingrid api key is set. Failed fetches are reported with
+/// [DeviceError::FetchFailed].
+#[embassy_executor::task]
+pub async fn get_emissions(
+    client: &'static SharedClient,
+    display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+) {
+    loop {
+        if let Some(api_key) = api_key(nvs_storage, NonVolatileKey::FingridApiKey).await {
+            for dataset in EMISSION_DATASETS {
+                match fingrid::fetch_latest(&mut *client.lock().await, &api_key, dataset).await {
+                    Ok(point) => EMISSIONS.lock().await.update(&point),
+                    Err(e) => {
+                        log::warn!("Fingrid dataset {dataset} fetch failed : {e}");
+                        let _ = event_sender.try_send(Event::Error(DeviceError::FetchFailed));
+                    }
+                }
+            }
+            if let Some(now) = clock::now() {
+                if let Err(e) = fill_emission_trend(client, &api_key, now).await {
+                    log::warn!("Fetching emission trend failed : {e}");
+                    let _ = event_sender.try_send(Event::Error(DeviceError::FetchFailed));
+                }
+            }
+            display_sender.send(DisplayUpdate::Emissions).await;
+        }
+
+        Timer::after(FINGRID_INTERVAL).await;
+    }
+}
+
+/// Fetches the consumption values from the oldest hour missing from the trend of
+/// [EMISSIONS] to the current hour with a single [fetch_period](fingrid::fetch_period)
+async fn fill_emission_trend(
+    client: &'static SharedClient,
+    api_key: &String<64>,
+    now: Timestamp,
+) -> Result<(), FetchError<FingridError>> {
+    let Some(start) = EMISSIONS.lock().await.missing_hours(now).next() else {
+        return Ok(());
+    };
+    let end = now - now.rem_euclid(3600);
+    // Values every 3 minutes and the one at the end of the window
+    let mut points = Vec::<DataPoint, { TREND_HOURS * 20 + 1 }>::new();
+    fingrid::fetch_period(
+        &mut *client.lock().await,
+        api_key,
+        dataset::CONSUMPTION_EMISSIONS,
+        start,
+        end,
+        &mut points,
+    )
+    .await?;
+    let mut emissions = EMISSIONS.lock().await;
+    for point in &points {
+        emissions.update(point);
+    }
+    Ok(())
+}
+
+/// Switches `pins` by the rules set with [Message::SetRelayRules] at the start of every
+/// quarter-hour and whenever [RELAYS_CHANGED](relay::RELAYS_CHANGED) is signaled.
+///
+/// Relays are off until the time is set with [Message::SetTime].
+/// Every switch is reported with [Event::RelaySwitched].
 #[embassy_executor::task]
-pub async fn get_price_from_entsoe() {}
+pub async fn control_relays(
+    mut pins: [AnyOutput<'static>; relay::RELAY_COUNT],
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+) {
+    let quarter = Resolution::Minutes15.seconds() as i64;
+    loop {
+        let mut wait = Duration::from_secs(quarter as u64);
+        let states = match clock::now() {
+            Some(now) => {
+                let rules = relay_rules(nvs_storage).await;
+                let tariff = tariff(nvs_storage).await;
+                let series = DAY_AHEAD_PRICES.lock().await;
+                wait = Duration::from_secs((quarter - now.rem_euclid(quarter)) as u64);
+                relay_states(&rules, now, &series, &tariff)
+            }
+            None => [false; MAX_RELAYS],
+        };
+
+        for (i, (pin, on)) in pins.iter_mut().zip(states).enumerate() {
+            if pin.is_set_high() != on {
+                pin.set_level(on.into());
+                log::info!("Relay {i} switched {}", if on { "on" } else { "off" });
+                let _ = event_sender.try_send(Event::RelaySwitched { relay: i as u8, on });
+            }
+        }
+
+        select(Timer::after(wait), relay::RELAYS_CHANGED.wait()).await;
+    }
+}
+
+/// Reads the relay rules from storage, skipping ones that can not be read
+async fn relay_rules(nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>) -> RelayRules {
+    let mut rules = RelayRules::new();
+    let mut nvs_guard = nvs_storage.lock().await;
+    for i in 0..MAX_RELAY_RULES {
+        match nvs_guard.fetch(NonVolatileKey::RelayRule(i as u8)).await {
+            Ok(Some(item)) => {
+                if let Some(rule) = relay::rule_from_item(&item.0) {
+                    // Same capacity as rules stored
+                    let _ = rules.push(rule);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Reading relay rule {i} failed : {e:?}"),
+        }
+    }
+    rules
+}
+
+/// Reads api key from storage, logging why if it is not available
+async fn api_key(
+    nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
+    key: NonVolatileKey,
+) -> Option<String<64>> {
+    match nvs_storage.lock().await.fetch(key.clone()).await {
+        Ok(Some(item)) => Some(item.0),
+        Ok(None) => {
+            log::warn!("{key:?} is not set");
+            None
+        }
+        Err(e) => {
+            log::warn!("Reading {key:?} failed : {e:?}");
+            None
+        }
+    }
+}
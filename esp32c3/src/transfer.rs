@@ -0,0 +1,66 @@
+use heapless::Vec;
+use shared::transfer::{Accepted, TransferAck, TransferError, TransferFrame, TransferReceiver};
+
+/// Receives payload of a chunked transfer to RAM.
+///
+/// Holds at most `N` bytes, the payload of the last completed transfer is available
+/// from [TransferBuffer::payload] until next transfer is started.
+pub struct TransferBuffer<const N: usize> {
+    receiver: TransferReceiver,
+    data: Vec<u8, N>,
+    complete: bool,
+}
+
+impl<const N: usize> TransferBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            receiver: TransferReceiver::new(N as u32),
+            data: Vec::new(),
+            complete: false,
+        }
+    }
+
+    /// Handles `frame` and returns acknowledgement to send back.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the frame is rejected by [TransferReceiver].
+    pub fn handle(&mut self, frame: &TransferFrame) -> Result<TransferAck, TransferError> {
+        let transfer = match frame {
+            TransferFrame::Begin { transfer, .. }
+            | TransferFrame::Chunk { transfer, .. }
+            | TransferFrame::End { transfer } => *transfer,
+        };
+
+        match self.receiver.accept(frame)? {
+            Accepted::Started => {
+                self.complete = false;
+                // Keep data received so far in case the transfer is resumed
+                let next_offset = self.receiver.ack(transfer).next_offset as usize;
+                self.data.truncate(next_offset);
+            }
+            Accepted::Write { offset, data } => {
+                self.data.truncate(offset as usize);
+                // Receiver never accepts more than N bytes so this can not fail
+                self.data
+                    .extend_from_slice(data)
+                    .map_err(|_| TransferError::TooLarge)?;
+            }
+            Accepted::Duplicate => {}
+            Accepted::Complete { .. } => self.complete = true,
+        }
+
+        Ok(self.receiver.ack(transfer))
+    }
+
+    /// Returns payload of the last completed transfer
+    pub fn payload(&self) -> Option<&[u8]> {
+        self.complete.then_some(self.data.as_slice())
+    }
+}
+
+impl<const N: usize> Default for TransferBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
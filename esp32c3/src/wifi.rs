@@ -1,6 +1,5 @@
 use core::{fmt::Error, str::FromStr};
 
-use core::fmt::Write;
 use embassy_executor::Spawner;
 use embassy_net::{Stack, StackResources};
 use embassy_sync::blocking_mutex::raw::NoopRawMutex;
@@ -18,7 +17,7 @@ use esp_wifi::{
     EspWifiInitFor,
 };
 use heapless::String;
-use shared::DisplayUpdate;
+use shared::{DeviceError, DisplayUpdate, Event, WifiState};
 use static_cell::StaticCell;
 
 use crate::generate_rand_u64;
@@ -39,9 +38,11 @@ pub async fn connect(
     mut rng: Rng,
     wifi_peripherals: WifiPeripherals<'_>,
     display_sender: Sender<'static, CriticalSectionRawMutex, DisplayUpdate, 10>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
     nvs_storage: &'static Mutex<NoopRawMutex, NonVolatileStorage>,
 ) -> Result<&'static Stack<WifiDevice<'static, WifiStaDevice>>, Error> {
-    display_sender.send("started Wifi init".into()).await;
+    // Display keeps showing the saved prices while connecting
+    log::info!("Starting wifi init");
 
     let seed = generate_rand_u64(&mut rng);
 
@@ -82,13 +83,14 @@ pub async fn connect(
             .unwrap()
             .0;
 
-        // Todo remove showing wifi credentials in final build
-        let mut msg = String::<64>::new();
+        log::info!("Connecting to {wifi_ssid}");
 
-        write!(msg, "{wifi_ssid}\n************",).unwrap();
-        display_sender.send(msg.as_str().into()).await;
-
-        spawner.must_spawn(connection(controller, wifi_password, wifi_ssid));
+        spawner.must_spawn(connection(
+            controller,
+            wifi_password,
+            wifi_ssid,
+            event_sender,
+        ));
     }
 
     spawner.must_spawn(net_task(stack));
@@ -97,20 +99,26 @@ pub async fn connect(
         Timer::after(Duration::from_millis(500)).await;
     }
 
-    while stack.config_v4().is_none() {
+    let address = loop {
+        if let Some(config) = stack.config_v4() {
+            break config.address.address();
+        }
         Timer::after(Duration::from_millis(500)).await;
-    }
+    };
+    log::info!("Got IP address {address}");
+    let _ = event_sender.try_send(Event::IpAcquired(address.0));
     display_sender.send("Wifi init done".into()).await;
 
     Ok(stack)
 }
 
-// Todo error propagation to host program if wifi connection fails
+/// Keeps the wifi connected and reports state changes to `event_sender`
 #[embassy_executor::task]
 async fn connection(
     mut controller: WifiController<'static>,
     wifi_password: String<64>,
     wifi_ssid: String<64>,
+    event_sender: Sender<'static, CriticalSectionRawMutex, Event, 10>,
 ) {
     let wifi_ssid = String::from_str(wifi_ssid.as_ref()).unwrap();
 
@@ -119,6 +127,7 @@ async fn connection(
         if wifi::get_wifi_state() == wifi::WifiState::StaConnected {
             // Wait until device is no longer connected to the wifi
             controller.wait_for_event(WifiEvent::StaDisconnected).await;
+            let _ = event_sender.try_send(Event::WifiStateChanged(WifiState::Disconnected));
             // Wait before trying to connect again?
             Timer::after(Duration::from_millis(3000)).await
         }
@@ -136,9 +145,14 @@ async fn connection(
                 .expect("Failed to start the controller");
         }
 
+        let _ = event_sender.try_send(Event::WifiStateChanged(WifiState::Connecting));
         match controller.connect().await {
-            Ok(_) => {}
-            Err(_) => {
+            Ok(_) => {
+                let _ = event_sender.try_send(Event::WifiStateChanged(WifiState::Connected));
+            }
+            Err(e) => {
+                log::warn!("Wifi connection failed : {e:?}");
+                let _ = event_sender.try_send(Event::Error(DeviceError::WifiConnectFailed));
                 Timer::after(Duration::from_millis(3000)).await;
             }
         }
@@ -31,6 +31,26 @@ pub enum Action {
     #[strum(message = "Show keybindings, these can be configured in the settings.toml file")]
     ShowKeyBindings,
     SerialPortConnectionFail,
+    #[strum(message = "Switch between the message list and plain text printed by the device")]
+    ToggleDeviceConsole,
+    #[strum(message = "Open device configuration, such as the bidding zone")]
+    StateChangeFromMainToConfigure,
+    #[strum(message = "Return to the main view without changing the configuration")]
+    StateChangeFromConfigureToMain,
+    #[strum(message = "Send selected bidding zone to the device")]
+    SelectBiddingZone,
+    #[strum(
+        message = "Switch prices on the device display between quarter-hours and hourly averages"
+    )]
+    TogglePriceResolution,
+    #[strum(
+        message = "Ask the device for the cheapest time to run the appliances in settings.toml"
+    )]
+    PlanAppliances,
+    #[strum(message = "Compare today's prices to the price history kept by the device")]
+    ShowStatistics,
+    #[strum(message = "Switch the device display between prices, statistics and emissions")]
+    CycleDisplayPage,
 }
 
 /// Implemented only to get error message with list of acceptable enum variants
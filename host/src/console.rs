@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+
+/// Longest text line or frame kept in the buffer, longer input is dropped
+const MAX_PENDING: usize = 1024;
+
+/// Splits bytes read from the device serial port to protocol frames and plain text lines.
+///
+/// ROM bootloader and the panic handler print text to the same UART that carries the COBS frames.
+/// Text lines never contain [corncobs::ZERO] and COBS frames never contain it
+/// except as the terminator, so:
+/// - bytes up to a zero are a frame
+/// - bytes up to a `\n` are a text line if all of them are printable
+///
+/// A frame whose start happens to look like a text line is split and fails its CRC check,
+/// it is then counted as dropped like any other corrupted frame.
+#[derive(Debug)]
+pub struct SerialDemux {
+    pending: Vec<u8>,
+    printable: bool,
+    overflowed: bool,
+}
+
+impl Default for SerialDemux {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Output of [SerialDemux::feed]
+#[derive(Debug, PartialEq)]
+pub enum SerialOutput {
+    /// Complete COBS frame including the terminating zero
+    Frame(Vec<u8>),
+    /// Text line without the line ending
+    Text(String),
+    /// Frame or line was longer than the buffer and it was dropped
+    Overflow,
+}
+
+impl SerialDemux {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            printable: true,
+            overflowed: false,
+        }
+    }
+
+    /// Feeds `input` and returns everything completed by it.
+    ///
+    /// Incomplete frames and lines are kept until the next call.
+    pub fn feed(&mut self, input: &[u8]) -> Vec<SerialOutput> {
+        let mut output = Vec::new();
+
+        for byte in input {
+            if self.overflowed {
+                if *byte == corncobs::ZERO || *byte == b'\n' {
+                    self.overflowed = false;
+                    output.push(SerialOutput::Overflow);
+                }
+                continue;
+            }
+
+            if *byte == corncobs::ZERO {
+                if !self.pending.is_empty() {
+                    self.pending.push(*byte);
+                    output.push(SerialOutput::Frame(self.take_pending()));
+                }
+                continue;
+            }
+
+            if *byte == b'\n' && self.printable {
+                let line = self.take_pending();
+                let line = String::from_utf8_lossy(&line);
+                output.push(SerialOutput::Text(line.trim_end_matches('\r').to_string()));
+                continue;
+            }
+
+            if self.pending.len() == MAX_PENDING {
+                self.take_pending();
+                self.overflowed = true;
+                continue;
+            }
+
+            self.printable &= is_text(*byte);
+            self.pending.push(*byte);
+        }
+
+        output
+    }
+
+    fn take_pending(&mut self) -> Vec<u8> {
+        self.printable = true;
+        std::mem::take(&mut self.pending)
+    }
+}
+
+/// Bytes that can appear in the plain text output of the device
+fn is_text(byte: u8) -> bool {
+    byte.is_ascii_graphic() || matches!(byte, b' ' | b'\t' | b'\r' | 0x1b)
+}
+
+/// Plain text lines printed by the device, oldest lines are dropped when full
+#[derive(Debug)]
+pub struct DeviceConsole {
+    lines: VecDeque<String>,
+    capacity: usize,
+}
+
+impl Default for DeviceConsole {
+    fn default() -> Self {
+        Self::new(500)
+    }
+}
+
+impl DeviceConsole {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            lines: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, line: String) {
+        if self.lines.len() == self.capacity {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    pub fn lines(&self) -> impl DoubleEndedIterator<Item = &String> + ExactSizeIterator {
+        self.lines.iter()
+    }
+}
+
+/// Returns true if `line` is part of a panic message or backtrace
+pub fn is_panic_line(line: &str) -> bool {
+    line.contains("panicked at")
+        || line.starts_with("Backtrace")
+        || line.starts_with("Exception")
+        || line.starts_with("0x")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceConsole, SerialDemux, SerialOutput};
+
+    #[test]
+    fn splits_text_and_frames() {
+        let mut demux = SerialDemux::new();
+        let mut input = b"ESP-ROM:esp32c3-api1-20210207\r\nboot:0xc\r\n".to_vec();
+        input.extend_from_slice(&[3, 0xff, 0x0a, 0]);
+        input.extend_from_slice(b"!! A panic occured in 'src/main.rs'\n");
+
+        let output = demux.feed(&input);
+        assert_eq!(
+            output,
+            vec![
+                SerialOutput::Text("ESP-ROM:esp32c3-api1-20210207".to_string()),
+                SerialOutput::Text("boot:0xc".to_string()),
+                SerialOutput::Frame(vec![3, 0xff, 0x0a, 0]),
+                SerialOutput::Text("!! A panic occured in 'src/main.rs'".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_partial_input_between_calls() {
+        let mut demux = SerialDemux::new();
+        assert!(demux.feed(b"Back").is_empty());
+        assert!(demux.feed(b"\r").is_empty());
+        assert_eq!(demux.feed(&[b'\n', 2, 0x80]).len(), 1);
+        assert_eq!(
+            demux.feed(&[0]),
+            vec![SerialOutput::Frame(vec![2, 0x80, 0])]
+        );
+    }
+
+    #[test]
+    fn drops_overlong_input() {
+        let mut demux = SerialDemux::new();
+        let mut input = vec![b'a'; 2000];
+        input.extend_from_slice(b"\nok\n");
+        assert_eq!(
+            demux.feed(&input),
+            vec![SerialOutput::Overflow, SerialOutput::Text("ok".to_string())]
+        );
+    }
+
+    #[test]
+    fn console_drops_oldest_lines() {
+        let mut console = DeviceConsole::new(2);
+        for line in ["a", "b", "c"] {
+            console.push(line.to_string());
+        }
+        assert_eq!(console.lines().collect::<Vec<_>>(), vec!["b", "c"]);
+    }
+}
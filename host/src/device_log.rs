@@ -0,0 +1,49 @@
+use shared::{LogLevel, LogRecord};
+use tracing::{debug, error, info, trace, warn};
+
+/// Emits a log record received from the device as a tracing event with `device` target,
+/// so it is shown in the same log view as the host logs.
+pub fn forward(record: &LogRecord) {
+    let line = format_record(record);
+    match record.level {
+        LogLevel::Error => error!(target:"device", "{line}"),
+        LogLevel::Warn => warn!(target:"device", "{line}"),
+        LogLevel::Info => info!(target:"device", "{line}"),
+        LogLevel::Debug => debug!(target:"device", "{line}"),
+        LogLevel::Trace => trace!(target:"device", "{line}"),
+    }
+}
+
+/// Formats record as `[uptime] target : message` with uptime in seconds
+fn format_record(record: &LogRecord) -> String {
+    format!(
+        "[{:>4}.{:03}] {} : {}",
+        record.uptime_ms / 1000,
+        record.uptime_ms % 1000,
+        record.target,
+        record.message
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use shared::{LogLevel, LogRecord};
+
+    use super::format_record;
+
+    #[test]
+    fn record_is_formatted_with_uptime() {
+        let record = LogRecord {
+            level: LogLevel::Info,
+            uptime_ms: 12_045,
+            target: heapless::String::from_str("electricity_exhange::wifi").unwrap(),
+            message: heapless::String::from_str("Got IP address 10.0.0.2").unwrap(),
+        };
+        assert_eq!(
+            format_record(&record),
+            "[  12.045] electricity_exhange::wifi : Got IP address 10.0.0.2"
+        );
+    }
+}
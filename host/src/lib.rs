@@ -1,6 +1,10 @@
 pub mod action;
+pub mod console;
+pub mod device_log;
+pub mod requests;
 pub mod settings;
 pub mod styles;
+pub mod symbolize;
 
 use std::io::stdout;
 
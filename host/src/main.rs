@@ -14,12 +14,19 @@ use std::{
     time::Duration,
 };
 
-use host::{init_terminal, install_panic_hook, restore_terminal};
+use host::{init_terminal, install_panic_hook, requests::RequestOutcome, restore_terminal};
 // use log::{info, warn, LevelFilter};
-use ::tracing::info;
-use model::{Model, RunningState};
+use ::tracing::{debug, info, warn};
+use model::{Handshake, Model, PopUpState, RunningState};
 use shared::{
-    deserialize_crc_cobs, serialize_crc_cobs, Message, Response, WifiInfo, MESSAGE_SIZE,
+    deserialize_crc_cobs,
+    frame::{FeedResult, FrameDecoder},
+    planner::{Plan, PlanRequest},
+    serialize_crc_cobs,
+    statistics::Statistics,
+    tariff::cents_per_kwh,
+    time::TimeZone,
+    DeviceFrame, Envelope, Message, Response, WifiInfo, MESSAGE_SIZE, PROTOCOL_VERSION,
     RESPONSE_SIZE,
 };
 use tracing::initialize_logging;
@@ -39,6 +46,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     while quit_state != std::mem::discriminant(&model.running_state) {
         terminal.draw(|frame| ui_render::view(&mut model, frame))?;
 
+        poll_serial(&mut model);
+
         let mut current_msg = handle_event(&model)?;
 
         while let Some(msg) = current_msg {
@@ -50,6 +59,150 @@ fn main() -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+/// Handles responses from the device and reports requests that failed
+fn poll_serial(model: &mut Model) {
+    if let Some(state) = model.running_state.connection_mut() {
+        for outcome in state.poll_serial() {
+            match outcome {
+                RequestOutcome::Answered {
+                    id,
+                    message,
+                    response,
+                } => {
+                    let name: &str = (&message).into();
+                    match *response {
+                        Response::Hello(info) => {
+                            info!(target:"serial", "Device firmware {} ({}), protocol {}", info.firmware_version, info.build_hash, info.protocol_version);
+                            if info.is_compatible() {
+                                state.handshake = Handshake::Compatible(info);
+                                state.configure_device(&model.settings);
+                            } else {
+                                warn!(target:"serial", "Incompatible device protocol {}, expected {}", info.protocol_version, PROTOCOL_VERSION);
+                                model.popup = Some(PopUpState::Message(format!(
+                                    "Device uses protocol version {} but this program uses version {}\nUpdate the firmware or the host program, no messages will be sent to the device",
+                                    info.protocol_version, PROTOCOL_VERSION
+                                )));
+                                state.handshake = Handshake::Incompatible(info);
+                            }
+                        }
+                        Response::Ok => {
+                            info!(target:"serial", "Request {id} {name} succeeded")
+                        }
+                        Response::Transfer(ack) => {
+                            debug!(target:"serial", "Transfer {} at offset {}", ack.transfer, ack.next_offset)
+                        }
+                        Response::Plans(plans) => {
+                            info!(target:"serial", "Request {id} {name} succeeded");
+                            if let Message::PlanWindow(requests) = &message {
+                                let time_zone = state.device_status.time_zone();
+                                model.popup = Some(PopUpState::Message(describe_plans(
+                                    requests, &plans, time_zone,
+                                )));
+                            }
+                        }
+                        Response::Statistics(statistics) => {
+                            info!(target:"serial", "Request {id} {name} succeeded");
+                            model.popup =
+                                Some(PopUpState::Message(describe_statistics(&statistics)));
+                        }
+                        Response::Error(e) => {
+                            warn!(target:"serial", "Request {id} {name} failed : {e}");
+                            model.popup = Some(PopUpState::Message(format!(
+                                "Device failed to handle {name}\n{e}"
+                            )));
+                        }
+                    }
+                }
+                RequestOutcome::TimedOut { id, message } => {
+                    let name: &str = (&message).into();
+                    warn!(target:"serial", "Request {id} {name} was not answered");
+                    model.popup = Some(PopUpState::Message(format!(
+                        "Device did not answer to {name}\nCheck that the device is connected and running"
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Spot prices of the day compared to the history kept by the device, in c/kWh
+fn describe_statistics(statistics: &Statistics) -> String {
+    if statistics.days == 0 {
+        return "No price history on the device yet".to_string();
+    }
+    let Statistics { date, days, .. } = statistics;
+    let mut lines = vec![format!(
+        "{}-{:02}-{:02}, {days} days of history",
+        date.year, date.month, date.day
+    )];
+    let average = |label: &str, price: Option<i32>| {
+        let price = price.map_or_else(|| "-".to_string(), |p| cents_per_kwh(p).to_string());
+        format!("{label} : {price}")
+    };
+    match statistics.today {
+        Some(today) => lines.push(format!(
+            "Today : {} (min {}, max {})",
+            cents_per_kwh(today.average),
+            cents_per_kwh(today.min),
+            cents_per_kwh(today.max)
+        )),
+        None => lines.push(average("Today", None)),
+    }
+    lines.push(average("Previous 7 days", statistics.week_average));
+    lines.push(average("Month to date", statistics.month_average));
+    lines.push(average("Whole history", statistics.history_average));
+    if let Some(percent) = statistics.today_vs_week() {
+        let relative = if percent < 0 {
+            "cheaper"
+        } else {
+            "more expensive"
+        };
+        lines.push(format!(
+            "Today is {} % {relative} than the previous 7 days",
+            percent.abs()
+        ));
+    }
+    if let (Some(today), Some(profile)) = (statistics.today, statistics.profile) {
+        let cheaper: Vec<String> = (0..24)
+            .filter(|h| today.hourly[*h] < profile[*h])
+            .map(|h| format!("{h:02}"))
+            .collect();
+        if !cheaper.is_empty() {
+            lines.push(format!("Cheaper than usual at {}", cheaper.join(" ")));
+        }
+    }
+    lines.join("\n")
+}
+
+/// One line for each appliance with the run times planned by the device in its `time_zone`
+/// and the average spot price
+fn describe_plans(requests: &[PlanRequest], plans: &[Option<Plan>], time_zone: TimeZone) -> String {
+    let local = |t| {
+        let local = time_zone.to_local(t);
+        format!("{:02}:{:02}", local.hour, local.minute)
+    };
+    requests
+        .iter()
+        .zip(plans)
+        .map(|(request, plan)| match plan {
+            Some(plan) => {
+                let runs: Vec<String> = plan
+                    .runs()
+                    .map(|(start, end)| format!("{}-{}", local(start), local(end)))
+                    .collect();
+                format!(
+                    "{} : {} ({} c/kWh)",
+                    request.name,
+                    runs.join(", "),
+                    cents_per_kwh(plan.average)
+                )
+            }
+            None => format!("{} : not enough prices before the deadline", request.name),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 #[allow(unused)]
 fn main2() {
     let ports = serialport::available_ports().expect("No ports found");
@@ -79,7 +232,11 @@ fn main2() {
 
     let mut out_buf = [0u8; MESSAGE_SIZE];
     // let mut response_buf = Vec::with_capacity(RESPONSE_SIZE);
-    let serialized_obj = serialize_crc_cobs::<Message, MESSAGE_SIZE>(test_object, &mut out_buf);
+    let serialized_obj = serialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(
+        Envelope::new(0, test_object),
+        &mut out_buf,
+    )
+    .unwrap();
 
     // loop {
     //     let _ = writer.write_all(serialized_obj);
@@ -102,21 +259,40 @@ fn main2() {
 
     let mut reader: Box<dyn Read> = port.try_clone().unwrap();
     let mut writer: Box<dyn Write> = port;
+    let mut dropped_frames: u32 = 0;
+    let mut decoder = FrameDecoder::<RESPONSE_SIZE>::new();
 
     loop {
         let _ = writer.write(serialized_obj);
-        let mut read_buf = [0u8; 1];
-        let mut response_buf = Vec::new();
-        loop {
-            if reader.read_exact(&mut read_buf).is_ok() {
-                response_buf.push(read_buf[0]);
-                if read_buf[0] == corncobs::ZERO {
-                    break;
-                }
+        let mut read_buf = [0u8; 64];
+        let mut got_response = false;
+        while !got_response {
+            let Ok(bytes_read) = reader.read(&mut read_buf) else {
+                continue;
+            };
+            let mut input = &read_buf[..bytes_read];
+            while !input.is_empty() {
+                input = match decoder.feed(input) {
+                    FeedResult::Consumed => break,
+                    FeedResult::Frame { frame, remaining } => {
+                        match deserialize_crc_cobs::<DeviceFrame, RESPONSE_SIZE>(frame) {
+                            Ok(response) => println!("{response:?}"),
+                            Err(e) => {
+                                dropped_frames += 1;
+                                warn!("Dropped frame ({dropped_frames} total) : {e:?}");
+                            }
+                        }
+                        got_response = true;
+                        remaining
+                    }
+                    FeedResult::Overflow { remaining } => {
+                        dropped_frames += 1;
+                        warn!("Dropped over-long frame ({dropped_frames} total)");
+                        remaining
+                    }
+                };
             }
         }
-        let response = deserialize_crc_cobs::<Response>(&response_buf);
-        println!("{response:?}");
 
         sleep(Duration::from_millis(2000));
         i = (i + 1) % 255;
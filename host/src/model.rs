@@ -1,8 +1,26 @@
-use std::io::{Read, Write};
+use std::io::Write;
 
-use host::settings::{keybindings::KeyBindings, Settings};
+use host::{
+    console::{is_panic_line, DeviceConsole, SerialDemux, SerialOutput},
+    requests::{RequestError, RequestOutcome, RequestTracker},
+    settings::{keybindings::KeyBindings, Settings},
+    symbolize::Symbolizer,
+};
 use ratatui::widgets::ListState;
 use serialport::{SerialPort, SerialPortInfo};
+use shared::{
+    deserialize_crc_cobs,
+    fingrid::MAX_DATASETS,
+    grid::GridAlert,
+    price::{PriceSource, Resolution, DEFAULT_BIDDING_ZONE},
+    relay::MAX_RELAYS,
+    tariff::PriceSummary,
+    time::TimeZone,
+    Capabilities, DeviceError, DeviceFrame, DeviceInfo, DisplayMessage, Event, Message, Page,
+    RequestId, StorageFailure, WifiState, PROTOCOL_VERSION, RESPONSE_SIZE,
+};
+use strum::EnumCount;
+use tracing::{info, warn};
 
 // #[derive(Debug)]
 pub struct Model {
@@ -24,7 +42,7 @@ impl Model {
         match self.running_state {
             RunningState::SelectSerialPort(_) => &self.settings.serialport_keybindings,
             RunningState::Main(_) => &self.settings.main_keybindings,
-            RunningState::Configure(_) => todo!(),
+            RunningState::Configure(_) => &self.settings.configure_keybindings,
             RunningState::GetInformation(_) => todo!(),
             RunningState::Quit(_) => todo!(),
             RunningState::ForceQuit => todo!(),
@@ -43,6 +61,17 @@ pub enum RunningState {
     ForceQuit,
 }
 
+impl RunningState {
+    /// Connection to the device, available after the serial port has been selected
+    pub fn connection_mut(&mut self) -> Option<&mut MainScreenState> {
+        match self {
+            RunningState::Main(state) => Some(state),
+            RunningState::Configure(state) => state.main.as_mut(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 pub struct SerialPortScreenState {
     pub ports: Vec<SerialPortInfo>,
@@ -69,9 +98,28 @@ impl Default for SerialPortScreenState {
 // #[derive(Debug)]
 pub struct MainScreenState {
     pub port_name: String,
-    pub reader: Box<dyn Read>,
+    pub reader: Box<dyn SerialPort>,
     pub writer: Box<dyn Write>,
     pub list_state: ListState,
+    /// Splits received bytes to frames and plain text printed by the device
+    pub demux: SerialDemux,
+    /// Plain text lines, such as boot messages and panics, printed by the device
+    pub console: DeviceConsole,
+    /// Show [Self::console] instead of the message list
+    pub show_console: bool,
+    /// Resolves backtrace addresses printed to [Self::console] if firmware ELF is configured
+    pub symbolizer: Option<Symbolizer>,
+    /// Count of received frames that could not be decoded
+    pub dropped_frames: u32,
+    /// Messages sent to the device that are waiting for a response
+    pub requests: RequestTracker,
+    pub handshake: Handshake,
+    /// Latest state reported by the device with [Event]s
+    pub device_status: DeviceStatus,
+    /// Resolution of the prices drawn on the device display
+    pub price_resolution: Resolution,
+    /// What the device display shows
+    pub display_page: Page,
 }
 
 impl MainScreenState {
@@ -81,12 +129,264 @@ impl MainScreenState {
             reader: serial_port.try_clone().unwrap(),
             writer: serial_port,
             list_state: ListState::default(),
+            demux: SerialDemux::new(),
+            console: DeviceConsole::default(),
+            show_console: false,
+            symbolizer: None,
+            dropped_frames: 0,
+            requests: RequestTracker::default(),
+            handshake: Handshake::NotStarted,
+            device_status: DeviceStatus::default(),
+            price_resolution: Resolution::Minutes15,
+            display_page: Page::default(),
+        }
+    }
+
+    /// Sends [Message::Hello] to the device, other messages are refused until it is answered
+    pub fn start_handshake(&mut self) -> Result<RequestId, RequestError> {
+        let id = self.requests.send(
+            Message::Hello(PROTOCOL_VERSION),
+            &mut self.writer,
+            std::time::Instant::now(),
+        )?;
+        self.handshake = Handshake::Pending;
+        Ok(id)
+    }
+
+    /// Sends the current time, configuration from `settings`, the current
+    /// [Self::price_resolution] and [Self::display_page] to the device, called after
+    /// a compatible handshake. Settings of features missing from the device [Capabilities]
+    /// are not sent.
+    pub fn configure_device(&mut self, settings: &Settings) {
+        if let Err(e) = self.send(Message::SetTime(chrono::Utc::now().timestamp())) {
+            warn!("Failed to send time : {e:?}");
+        }
+        let resolution = DisplayMessage::PriceResolution(self.price_resolution);
+        if let Err(e) = self.send(Message::Display(resolution)) {
+            warn!("Failed to send price resolution : {e:?}");
+        }
+        let page = DisplayMessage::ShowPage(self.display_page);
+        if let Err(e) = self.send(Message::Display(page)) {
+            warn!("Failed to send display page : {e:?}");
+        }
+        if let Some(tariff) = settings
+            .tariff
+            .as_ref()
+            .filter(|_| self.supports(Capabilities::TARIFF))
+        {
+            if let Err(e) = self.send(Message::SetTariff(tariff.to_tariff())) {
+                warn!("Failed to send tariff : {e:?}");
+            }
+        }
+        if !settings.relay_rules.is_empty() && self.supports(Capabilities::RELAYS) {
+            match settings.relay_rules() {
+                Some(rules) => {
+                    if let Err(e) = self.send(Message::SetRelayRules(rules)) {
+                        warn!("Failed to send relay rules : {e:?}");
+                    }
+                }
+                None => warn!("Relay rules in settings are not valid, device keeps its rules"),
+            }
+        }
+        if !settings.fingrid_datasets.is_empty() && self.supports(Capabilities::FINGRID_DATA) {
+            match settings.fingrid_datasets() {
+                Some(datasets) => {
+                    if let Err(e) = self.send(Message::FingridDatasets(datasets)) {
+                        warn!("Failed to send Fingrid datasets : {e:?}");
+                    }
+                }
+                None => warn!(
+                    "Settings have more than {MAX_DATASETS} Fingrid datasets, device keeps its datasets"
+                ),
+            }
+        }
+        if !settings.price_sources.is_empty() && self.supports(Capabilities::PRICE_SOURCES) {
+            let sources = settings
+                .price_sources
+                .iter()
+                .copied()
+                .take(PriceSource::COUNT)
+                .collect();
+            if let Err(e) = self.send(Message::PriceSources(sources)) {
+                warn!("Failed to send price sources : {e:?}");
+            }
+        }
+    }
+
+    /// True if the device completed a compatible handshake and reported `capability`
+    pub fn supports(&self, capability: Capabilities) -> bool {
+        matches!(&self.handshake, Handshake::Compatible(info) if info.capabilities.contains(capability))
+    }
+
+    /// Sends `message` to the device and starts waiting for its response.
+    ///
+    /// # Errors
+    ///
+    /// Returns [RequestError::Refused] if the device has not completed a compatible handshake.
+    pub fn send(&mut self, message: Message) -> Result<RequestId, RequestError> {
+        if !matches!(self.handshake, Handshake::Compatible(_)) {
+            return Err(RequestError::Refused);
+        }
+        self.requests
+            .send(message, &mut self.writer, std::time::Instant::now())
+    }
+
+    /// Handles incoming frames and resends requests that have timed out.
+    ///
+    /// [Event]s update [Self::device_status] and device logs are forwarded to tracing.
+    /// Returns outcomes of requests that got answered or ran out of retries.
+    pub fn poll_serial(&mut self) -> Vec<RequestOutcome> {
+        let mut outcomes = Vec::new();
+
+        for frame in self.read_frames() {
+            match frame {
+                DeviceFrame::Response(response) => outcomes.extend(self.requests.resolve(response)),
+                DeviceFrame::Event(event) => {
+                    info!(target:"serial", "Device event : {event:?}");
+                    self.device_status.apply(event);
+                }
+                DeviceFrame::Log(record) => host::device_log::forward(&record),
+            }
+        }
+
+        outcomes.extend(
+            self.requests
+                .retry_expired(&mut self.writer, std::time::Instant::now()),
+        );
+        outcomes
+    }
+
+    /// Reads bytes that are already available from the serial port without blocking
+    /// and returns all [DeviceFrame]s completed by them.
+    ///
+    /// Plain text lines are added to [Self::console].
+    /// Frames that fail to decode are logged, counted to [Self::dropped_frames] and dropped.
+    fn read_frames(&mut self) -> Vec<DeviceFrame> {
+        let mut frames = Vec::new();
+
+        let available = self.reader.bytes_to_read().unwrap_or(0) as usize;
+        if available == 0 {
+            return frames;
+        }
+
+        let mut read_buf = vec![0u8; available];
+        let bytes_read = match self.reader.read(&mut read_buf) {
+            Ok(n) => n,
+            Err(e) => {
+                warn!("Failed to read serial port {} : {e}", self.port_name);
+                return frames;
+            }
+        };
+
+        for output in self.demux.feed(&read_buf[..bytes_read]) {
+            match output {
+                SerialOutput::Frame(frame) => {
+                    match deserialize_crc_cobs::<DeviceFrame, RESPONSE_SIZE>(&frame) {
+                        Ok(frame) => frames.push(frame),
+                        Err(e) => {
+                            self.dropped_frames += 1;
+                            warn!("Dropped frame ({} total) : {e:?}", self.dropped_frames);
+                        }
+                    }
+                }
+                SerialOutput::Text(line) => {
+                    let line = self
+                        .symbolizer
+                        .as_ref()
+                        .and_then(|s| s.symbolize_line(&line))
+                        .unwrap_or(line);
+                    if is_panic_line(&line) {
+                        warn!(target:"console", "{line}");
+                    }
+                    self.console.push(line);
+                }
+                SerialOutput::Overflow => {
+                    self.dropped_frames += 1;
+                    warn!("Dropped over-long frame ({} total)", self.dropped_frames);
+                }
+            }
         }
+
+        frames
     }
 }
 
-#[derive(Debug, PartialEq, Default)]
-pub struct ConfigureScreenState {}
+/// Device state collected from [Event]s
+#[derive(Debug, Default)]
+pub struct DeviceStatus {
+    pub wifi: Option<WifiState>,
+    pub ip: Option<[u8; 4]>,
+    /// Number of price points in the latest fetch and when it was reported
+    pub last_price_fetch: Option<(u16, chrono::DateTime<chrono::Local>)>,
+    pub last_error: Option<DeviceError>,
+    pub storage_warning: Option<StorageFailure>,
+    /// Latest fetched prices and the tariff of the device
+    pub prices: Option<PriceSummary>,
+    /// Last reported state of each relay
+    pub relays: [Option<bool>; MAX_RELAYS],
+    /// Power system state, [None] until the device reports a change
+    pub grid_alert: Option<GridAlert>,
+}
+
+impl DeviceStatus {
+    /// Time zone of the bidding zone of the latest prices, the zone the device plans and
+    /// draws prices in. Device uses [DEFAULT_BIDDING_ZONE] until it reports prices.
+    pub fn time_zone(&self) -> TimeZone {
+        self.prices
+            .map_or(DEFAULT_BIDDING_ZONE, |summary| summary.zone)
+            .time_zone()
+    }
+
+    pub fn apply(&mut self, event: Event) {
+        match event {
+            Event::WifiStateChanged(state) => {
+                if state != WifiState::Connected {
+                    self.ip = None;
+                }
+                self.wifi = Some(state);
+            }
+            Event::IpAcquired(ip) => self.ip = Some(ip),
+            Event::PriceDataFetched(count) => {
+                self.last_price_fetch = Some((count, chrono::Local::now()))
+            }
+            Event::Error(e) => self.last_error = Some(e),
+            Event::StorageWarning(failure) => self.storage_warning = Some(failure),
+            Event::PriceSummary(summary) => self.prices = Some(summary),
+            Event::RelaySwitched { relay, on } => {
+                if let Some(state) = self.relays.get_mut(relay as usize) {
+                    *state = Some(on);
+                }
+            }
+            Event::GridAlert(alert) => self.grid_alert = Some(alert),
+        }
+    }
+}
+
+/// State of the [Message::Hello] handshake with the device
+#[derive(Debug)]
+pub enum Handshake {
+    NotStarted,
+    Pending,
+    Compatible(DeviceInfo),
+    Incompatible(DeviceInfo),
+}
+
+#[derive(Default)]
+pub struct ConfigureScreenState {
+    /// Main screen that is restored when configuring is done, keeps the connection open
+    pub main: Option<MainScreenState>,
+    /// Selection in [BiddingZone::VARIANTS](shared::price::BiddingZone)
+    pub zone_list: ListState,
+}
+
+impl ConfigureScreenState {
+    pub fn new(main: MainScreenState) -> Self {
+        Self {
+            main: Some(main),
+            zone_list: ListState::default(),
+        }
+    }
+}
 
 #[derive(Debug, PartialEq, Default)]
 pub struct GetInformationScreenState {}
@@ -102,7 +402,15 @@ pub enum PopUpState {
 
 #[cfg(test)]
 mod tests {
-    use super::RunningState;
+    use shared::{
+        grid::{GridAlert, ShortageStatus, SystemState},
+        price::BiddingZone,
+        tariff::PriceSummary,
+        time::TimeZone,
+        DeviceError, Event, WifiState,
+    };
+
+    use super::{DeviceStatus, RunningState};
 
     #[test]
     fn test_enum_display() {
@@ -114,4 +422,46 @@ mod tests {
 
         println!("{e}\n{e1}\n{e2}\n{e3}\n");
     }
+
+    #[test]
+    fn events_update_device_status() {
+        let mut status = DeviceStatus::default();
+        status.apply(Event::WifiStateChanged(WifiState::Connected));
+        status.apply(Event::IpAcquired([192, 168, 1, 10]));
+        status.apply(Event::Error(DeviceError::FetchFailed));
+        assert_eq!(status.wifi, Some(WifiState::Connected));
+        assert_eq!(status.ip, Some([192, 168, 1, 10]));
+        assert_eq!(status.last_error, Some(DeviceError::FetchFailed));
+
+        // Address is no longer valid after disconnecting
+        status.apply(Event::WifiStateChanged(WifiState::Disconnected));
+        assert_eq!(status.ip, None);
+
+        status.apply(Event::RelaySwitched { relay: 1, on: true });
+        assert_eq!(status.relays, [None, Some(true), None, None]);
+
+        let alert = GridAlert {
+            state: SystemState::Endangered,
+            shortage: ShortageStatus::Normal,
+        };
+        status.apply(Event::GridAlert(alert));
+        assert_eq!(status.grid_alert, Some(alert));
+    }
+
+    #[test]
+    fn time_zone_follows_prices() {
+        let mut status = DeviceStatus::default();
+        assert_eq!(status.time_zone(), TimeZone::Eet);
+
+        status.apply(Event::PriceSummary(PriceSummary {
+            zone: BiddingZone::Se3,
+            min: 0,
+            max: 0,
+            average: 0,
+            retail_min: 0,
+            retail_max: 0,
+            retail_average: 0,
+        }));
+        assert_eq!(status.time_zone(), TimeZone::Cet);
+    }
 }
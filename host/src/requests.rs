@@ -0,0 +1,256 @@
+use std::{
+    collections::BTreeMap,
+    io::Write,
+    time::{Duration, Instant},
+};
+
+use shared::{serialize_crc_cobs, Envelope, Message, RequestId, Response, MESSAGE_SIZE};
+use tracing::{debug, warn};
+
+/// Keeps track of [Message]s sent to the device that have not been answered yet.
+///
+/// Each message gets an unique [RequestId] that the device echoes back in its [Response].
+/// Messages that are not answered within `timeout` are sent again with the same id
+/// until `max_retries` is reached, after which they are reported as failed.
+#[derive(Debug)]
+pub struct RequestTracker {
+    next_id: RequestId,
+    pending: BTreeMap<RequestId, PendingRequest>,
+    timeout: Duration,
+    max_retries: u8,
+}
+
+#[derive(Debug)]
+struct PendingRequest {
+    message: Message,
+    sent_at: Instant,
+    retries: u8,
+}
+
+/// Outcome of a request that is no longer tracked
+#[derive(Debug)]
+pub enum RequestOutcome {
+    /// Device answered to the request
+    Answered {
+        id: RequestId,
+        message: Message,
+        /// Boxed because some responses, such as statistics, are much larger than the others
+        response: Box<Response>,
+    },
+    /// Device did not answer even after all retries
+    TimedOut { id: RequestId, message: Message },
+}
+
+#[derive(Debug)]
+pub enum RequestError {
+    Protocol(shared::ProtocolError),
+    Io(std::io::Error),
+    /// Device has not completed a compatible handshake so messages are not sent to it
+    Refused,
+}
+
+impl From<shared::ProtocolError> for RequestError {
+    fn from(value: shared::ProtocolError) -> Self {
+        Self::Protocol(value)
+    }
+}
+
+impl From<std::io::Error> for RequestError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Default for RequestTracker {
+    fn default() -> Self {
+        Self::new(Duration::from_millis(1000), 3)
+    }
+}
+
+impl RequestTracker {
+    pub fn new(timeout: Duration, max_retries: u8) -> Self {
+        Self {
+            next_id: 0,
+            pending: BTreeMap::new(),
+            timeout,
+            max_retries,
+        }
+    }
+
+    /// Number of requests waiting for response
+    pub fn pending_count(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Sends `message` to `writer` and starts tracking it.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the message can not be encoded or written.
+    pub fn send(
+        &mut self,
+        message: Message,
+        writer: &mut dyn Write,
+        now: Instant,
+    ) -> Result<RequestId, RequestError> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1);
+
+        write_message(id, &message, writer)?;
+        let name: &str = (&message).into();
+        debug!(target:"serial", "Sent request {id} : {name}");
+
+        self.pending.insert(
+            id,
+            PendingRequest {
+                message,
+                sent_at: now,
+                retries: 0,
+            },
+        );
+        Ok(id)
+    }
+
+    /// Matches `response` to a pending request.
+    ///
+    /// Returns [None] if there is no pending request with the same id,
+    /// for example when the response to an already answered retry arrives late.
+    pub fn resolve(&mut self, response: Envelope<Response>) -> Option<RequestOutcome> {
+        match self.pending.remove(&response.id) {
+            Some(request) => Some(RequestOutcome::Answered {
+                id: response.id,
+                message: request.message,
+                response: Box::new(response.payload),
+            }),
+            None => {
+                warn!(target:"serial", "Response to unknown request {} : {:?}", response.id, response.payload);
+                None
+            }
+        }
+    }
+
+    /// Resends requests that have been waiting longer than the timeout.
+    ///
+    /// Returns requests that ran out of retries, they are no longer tracked.
+    pub fn retry_expired(&mut self, writer: &mut dyn Write, now: Instant) -> Vec<RequestOutcome> {
+        let expired: Vec<RequestId> = self
+            .pending
+            .iter()
+            .filter(|(_, r)| now.duration_since(r.sent_at) >= self.timeout)
+            .map(|(id, _)| *id)
+            .collect();
+
+        let mut failed = Vec::new();
+
+        for id in expired {
+            let request = self.pending.get_mut(&id).unwrap();
+            if request.retries >= self.max_retries {
+                let request = self.pending.remove(&id).unwrap();
+                failed.push(RequestOutcome::TimedOut {
+                    id,
+                    message: request.message,
+                });
+                continue;
+            }
+
+            request.retries += 1;
+            request.sent_at = now;
+            warn!(target:"serial", "Request {id} timed out, retry {}/{}", request.retries, self.max_retries);
+            if let Err(e) = write_message(id, &request.message, writer) {
+                warn!(target:"serial", "Failed to resend request {id} : {e:?}");
+            }
+        }
+
+        failed
+    }
+}
+
+fn write_message(
+    id: RequestId,
+    message: &Message,
+    writer: &mut dyn Write,
+) -> Result<(), RequestError> {
+    let mut buf = [0u8; MESSAGE_SIZE];
+    let encoded = serialize_crc_cobs::<Envelope<&Message>, MESSAGE_SIZE>(
+        Envelope::new(id, message),
+        &mut buf,
+    )?;
+    writer.write_all(encoded)?;
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, time::Duration};
+
+    use shared::{deserialize_crc_cobs, DisplayMessage, Envelope, Message, Response, MESSAGE_SIZE};
+
+    use super::{RequestOutcome, RequestTracker};
+
+    fn status_message() -> Message {
+        Message::Display(DisplayMessage::StatusUpdate(
+            heapless::String::from_str("hello").unwrap(),
+        ))
+    }
+
+    fn sent_ids(written: &[u8]) -> Vec<u16> {
+        written
+            .split_inclusive(|b| *b == corncobs::ZERO)
+            .map(|frame| {
+                deserialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(frame)
+                    .unwrap()
+                    .id
+            })
+            .collect()
+    }
+
+    #[test]
+    fn response_resolves_request() {
+        let mut tracker = RequestTracker::default();
+        let mut written = Vec::new();
+        let now = std::time::Instant::now();
+
+        let first = tracker.send(status_message(), &mut written, now).unwrap();
+        let second = tracker.send(status_message(), &mut written, now).unwrap();
+        assert_ne!(first, second);
+        assert_eq!(sent_ids(&written), vec![first, second]);
+
+        let outcome = tracker.resolve(Envelope::new(second, Response::Ok));
+        assert!(matches!(outcome, Some(RequestOutcome::Answered { id, .. }) if id == second));
+        assert_eq!(tracker.pending_count(), 1);
+
+        assert!(tracker
+            .resolve(Envelope::new(second, Response::Ok))
+            .is_none());
+    }
+
+    #[test]
+    fn retries_then_times_out() {
+        let timeout = Duration::from_millis(100);
+        let mut tracker = RequestTracker::new(timeout, 2);
+        let mut written = Vec::new();
+        let start = std::time::Instant::now();
+
+        let id = tracker.send(status_message(), &mut written, start).unwrap();
+
+        // Not yet expired
+        assert!(tracker
+            .retry_expired(&mut written, start + timeout / 2)
+            .is_empty());
+        assert_eq!(sent_ids(&written), vec![id]);
+
+        assert!(tracker
+            .retry_expired(&mut written, start + timeout)
+            .is_empty());
+        assert!(tracker
+            .retry_expired(&mut written, start + timeout * 2)
+            .is_empty());
+        // Retries are sent with the same id
+        assert_eq!(sent_ids(&written), vec![id, id, id]);
+
+        let failed = tracker.retry_expired(&mut written, start + timeout * 3);
+        assert!(matches!(failed.as_slice(), [RequestOutcome::TimedOut { id: i, .. }] if *i == id));
+        assert_eq!(tracker.pending_count(), 0);
+    }
+}
@@ -1,13 +1,46 @@
 pub mod keybindings;
 
+use std::{path::PathBuf, str::FromStr};
+
+use chrono::{DateTime, Days, NaiveTime, TimeZone, Timelike};
 use keybindings::KeyBindings;
 use serde::Deserialize;
+use shared::{
+    fingrid::{DatasetId, MAX_DATASETS},
+    planner::PlanRequest,
+    price::{PriceSource, Resolution},
+    relay::{Condition, RelayRule, RelayRules},
+    tariff::{Tariff, TaxClass, CENT_PER_KWH},
+    time_of_use::TransferSchedule,
+};
 use tracing::{info, instrument, Level};
 
 #[derive(Debug, Deserialize)]
 pub struct Settings {
+    /// Firmware ELF flashed to the device, used to symbolize panic backtraces
+    #[serde(default)]
+    pub firmware_elf: Option<PathBuf>,
+    /// Sources of day-ahead prices in the order the device tries them,
+    /// sent to the device after connecting. Device keeps its stored order if this is empty.
+    #[serde(default)]
+    pub price_sources: Vec<PriceSource>,
+    /// Fingrid datasets fetched by the device, sent to the device after connecting.
+    /// Device keeps its stored datasets if this is empty.
+    #[serde(default)]
+    pub fingrid_datasets: Vec<DatasetId>,
+    /// Sent to the device after connecting, device keeps its stored tariff if this is not set
+    #[serde(default)]
+    pub tariff: Option<TariffSettings>,
+    /// Appliances whose cheapest run time is asked from the device
+    #[serde(default)]
+    pub appliances: Vec<ApplianceSettings>,
+    /// Rules that switch the relays of the device in the order they are checked,
+    /// sent to the device after connecting. Device keeps its stored rules if this is empty.
+    #[serde(default)]
+    pub relay_rules: Vec<RelayRuleSettings>,
     pub serialport_keybindings: KeyBindings,
     pub main_keybindings: KeyBindings,
+    pub configure_keybindings: KeyBindings,
 }
 
 impl Settings {
@@ -19,12 +52,193 @@ impl Settings {
         let settings = config::Config::builder().add_source(file).build().unwrap();
         settings.try_deserialize().unwrap()
     }
+
+    /// [Self::fingrid_datasets] for the device, [None] if there are too many
+    pub fn fingrid_datasets(&self) -> Option<heapless::Vec<DatasetId, MAX_DATASETS>> {
+        heapless::Vec::from_slice(&self.fingrid_datasets).ok()
+    }
+
+    /// [Self::relay_rules] for the device, [None] if there are too many or some is not valid
+    pub fn relay_rules(&self) -> Option<RelayRules> {
+        self.relay_rules
+            .iter()
+            .map(|rule| rule.to_rule().filter(|r| r.validate().is_ok()))
+            .collect::<Option<Vec<_>>>()
+            .and_then(|rules| RelayRules::from_slice(&rules).ok())
+    }
+}
+
+/// [Tariff] in the units used on electricity bills
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct TariffSettings {
+    /// Percent, for example 25.5
+    pub vat: f64,
+    /// Margin of the spot contract in c/kWh without VAT
+    pub margin: f64,
+    pub tax_class: TaxClass,
+    /// Transfer fee of the grid operator in c/kWh without VAT
+    pub transfer_fee: f64,
+    /// When another transfer fee applies, the same fee all the time if this is not set
+    #[serde(default)]
+    pub transfer_schedule: TransferScheduleSettings,
+}
+
+/// [TransferSchedule] with the fee in c/kWh without VAT
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferScheduleSettings {
+    #[default]
+    Flat,
+    /// Fee between 22 and 07 every day
+    Night(f64),
+    /// Fee between 07 and 22 from Monday to Saturday from November to March,
+    /// except on holidays
+    WinterWeekday(f64),
+}
+
+impl TariffSettings {
+    /// Rounds values to the fixed point units of [Tariff]
+    pub fn to_tariff(&self) -> Tariff {
+        let cents = |c: f64| (c * CENT_PER_KWH as f64).round() as i32;
+        Tariff {
+            vat: (self.vat * 100.0).round() as u16,
+            margin: cents(self.margin),
+            tax_class: self.tax_class,
+            transfer_fee: cents(self.transfer_fee),
+            transfer_schedule: match self.transfer_schedule {
+                TransferScheduleSettings::Flat => TransferSchedule::Flat,
+                TransferScheduleSettings::Night(c) => TransferSchedule::Night(cents(c)),
+                TransferScheduleSettings::WinterWeekday(c) => {
+                    TransferSchedule::WinterWeekday(cents(c))
+                }
+            },
+        }
+    }
+}
+
+/// [RelayRule] with prices in c/kWh and times as `HH:MM`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct RelayRuleSettings {
+    pub relay: u8,
+    pub on: bool,
+    #[serde(flatten)]
+    pub condition: ConditionSettings,
+}
+
+/// [Condition] of a [RelayRuleSettings]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConditionSettings {
+    /// Hour is one of the cheapest hours of the day
+    CheapestHours(u16),
+    /// Quarter-hour is one of the cheapest quarter-hours of the day
+    CheapestQuarters(u16),
+    /// Consumer price is above this many c/kWh
+    Above(f64),
+    /// Consumer price is below this many c/kWh
+    Below(f64),
+    /// Local time is between these, for example `["06:00", "07:00"]`
+    Between(String, String),
+}
+
+impl RelayRuleSettings {
+    /// Returns [None] if a time of [ConditionSettings::Between] is not valid
+    pub fn to_rule(&self) -> Option<RelayRule> {
+        let cents = |c: f64| (c * CENT_PER_KWH as f64).round() as i32;
+        let minutes = |time: &str| {
+            let time = NaiveTime::parse_from_str(time, "%H:%M").ok()?;
+            Some((time.hour() * 60 + time.minute()) as u16)
+        };
+        let condition = match &self.condition {
+            ConditionSettings::CheapestHours(count) => Condition::Cheapest {
+                count: *count,
+                resolution: Resolution::Minutes60,
+            },
+            ConditionSettings::CheapestQuarters(count) => Condition::Cheapest {
+                count: *count,
+                resolution: Resolution::Minutes15,
+            },
+            ConditionSettings::Above(c) => Condition::PriceAbove(cents(*c)),
+            ConditionSettings::Below(c) => Condition::PriceBelow(cents(*c)),
+            ConditionSettings::Between(start, end) => Condition::Between {
+                start: minutes(start)?,
+                end: minutes(end)?,
+            },
+        };
+        Some(RelayRule {
+            relay: self.relay,
+            condition,
+            on: self.on,
+        })
+    }
+}
+
+/// Appliance to plan a run for with [PlanRequest]
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ApplianceSettings {
+    /// Shown on the device display, truncated to 16 bytes
+    pub name: String,
+    /// Run time, rounded up to whole time units
+    pub minutes: u32,
+    /// Local hour the appliance must be done by, today if it is still ahead and tomorrow otherwise
+    pub ready_by: u32,
+    /// Appliance can be paused, so the cheapest time units are used instead of
+    /// the cheapest window
+    #[serde(default)]
+    pub interruptible: bool,
+}
+
+impl ApplianceSettings {
+    /// Request for running the appliance from `now` on with prices in `resolution`.
+    ///
+    /// Returns [None] if [Self::ready_by] is not an hour of the day.
+    pub fn to_request<Tz: TimeZone>(
+        &self,
+        now: &DateTime<Tz>,
+        resolution: Resolution,
+    ) -> Option<PlanRequest> {
+        let today = now.date_naive().and_hms_opt(self.ready_by, 0, 0)?;
+        let mut deadline = now.timezone().from_local_datetime(&today).earliest()?;
+        if deadline <= *now {
+            let tomorrow = today.checked_add_days(Days::new(1))?;
+            deadline = now.timezone().from_local_datetime(&tomorrow).earliest()?;
+        }
+
+        let mut name = self.name.clone();
+        while name.len() > 16 {
+            name.pop();
+        }
+        let unit = resolution.seconds();
+        Some(PlanRequest {
+            name: heapless::String::from_str(name.trim_end()).ok()?,
+            slots: (self.minutes * 60).div_ceil(unit).max(1) as u16,
+            resolution,
+            earliest: now.timestamp(),
+            deadline: deadline.timestamp(),
+            contiguous: !self.interruptible,
+        })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Settings;
-    use config::Config;
+    use chrono::{TimeZone, Utc};
+    use shared::{
+        deserialize_crc_cobs,
+        fingrid::dataset,
+        price::Resolution,
+        relay::{Condition, RelayRule},
+        serialize_crc_cobs,
+        tariff::{Tariff, TaxClass, FINNISH_VAT},
+        time_of_use::TransferSchedule,
+        Envelope, Message, MESSAGE_SIZE,
+    };
+
+    use super::{
+        ApplianceSettings, ConditionSettings, RelayRuleSettings, Settings, TariffSettings,
+        TransferScheduleSettings,
+    };
+    use config::{Config, FileFormat};
 
     #[test]
     fn deserialize_keybindings() {
@@ -37,4 +251,200 @@ mod tests {
             println!("{:?} -  {:?}", key.code, action,);
         }
     }
+
+    #[test]
+    fn fingrid_datasets_round_trip() {
+        let file = config::File::with_name("./configs/settings.toml");
+        let datasets =
+            config::File::from_str("fingrid_datasets = [193, 181, 194]", FileFormat::Toml);
+        let settings = Config::builder()
+            .add_source(file)
+            .add_source(datasets)
+            .build()
+            .unwrap();
+        let settings: Settings = settings.try_deserialize().unwrap();
+
+        let datasets = settings.fingrid_datasets().unwrap();
+        let message = Envelope::new(3, Message::FingridDatasets(datasets));
+        let mut buffer = [0; MESSAGE_SIZE];
+        let frame =
+            serialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(message, &mut buffer).unwrap();
+        let decoded = deserialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(frame).unwrap();
+        let Message::FingridDatasets(decoded) = decoded.payload else {
+            panic!("Decoded {decoded:?}");
+        };
+        assert_eq!(
+            decoded,
+            [
+                dataset::CONSUMPTION,
+                dataset::WIND_GENERATION,
+                dataset::NET_IMPORT
+            ]
+        );
+
+        let too_many = Settings {
+            fingrid_datasets: vec![dataset::CONSUMPTION; 9],
+            ..settings
+        };
+        assert_eq!(too_many.fingrid_datasets(), None);
+    }
+
+    #[test]
+    fn tariff_in_fixed_point() {
+        let settings = TariffSettings {
+            vat: 25.5,
+            margin: 0.49,
+            tax_class: TaxClass::I,
+            transfer_fee: 3.27,
+            transfer_schedule: TransferScheduleSettings::Flat,
+        };
+        assert_eq!(
+            settings.to_tariff(),
+            Tariff {
+                vat: FINNISH_VAT,
+                margin: 490,
+                tax_class: TaxClass::I,
+                transfer_fee: 3270,
+                transfer_schedule: TransferSchedule::Flat,
+            }
+        );
+    }
+
+    #[test]
+    fn night_transfer_fee_from_toml() {
+        let file = config::File::with_name("./configs/settings.toml");
+        let tariff = config::File::from_str(
+            r#"
+            [tariff]
+            vat = 25.5
+            margin = 0.49
+            tax_class = "I"
+            transfer_fee = 4.1
+            transfer_schedule = { night = 2.05 }
+            "#,
+            FileFormat::Toml,
+        );
+        let settings = Config::builder()
+            .add_source(file)
+            .add_source(tariff)
+            .build()
+            .unwrap();
+        let settings: Settings = settings.try_deserialize().unwrap();
+        let tariff = settings.tariff.unwrap().to_tariff();
+        assert_eq!(tariff.transfer_fee, 4100);
+        assert_eq!(tariff.transfer_schedule, TransferSchedule::Night(2050));
+    }
+
+    #[test]
+    fn appliance_request() {
+        let dishwasher = ApplianceSettings {
+            name: "dishwasher".into(),
+            minutes: 100,
+            ready_by: 7,
+            interruptible: false,
+        };
+        let evening = Utc.with_ymd_and_hms(2025, 3, 10, 18, 30, 0).unwrap();
+        let request = dishwasher
+            .to_request(&evening, Resolution::Minutes15)
+            .unwrap();
+        assert_eq!(request.name.as_str(), "dishwasher");
+        assert_eq!(request.slots, 7);
+        assert_eq!(request.earliest, evening.timestamp());
+        assert_eq!(
+            request.deadline,
+            Utc.with_ymd_and_hms(2025, 3, 11, 7, 0, 0)
+                .unwrap()
+                .timestamp()
+        );
+        assert!(request.contiguous);
+
+        let heater = ApplianceSettings {
+            name: "water heater in the basement".into(),
+            minutes: 120,
+            ready_by: 21,
+            interruptible: true,
+        };
+        let request = heater.to_request(&evening, Resolution::Minutes60).unwrap();
+        assert_eq!(request.name.as_str(), "water heater in");
+        assert_eq!(request.slots, 2);
+        assert_eq!(
+            request.deadline,
+            Utc.with_ymd_and_hms(2025, 3, 10, 21, 0, 0)
+                .unwrap()
+                .timestamp()
+        );
+        assert!(!request.contiguous);
+    }
+
+    #[test]
+    fn relay_rules_from_toml() {
+        let toml = r#"
+            [[relay_rules]]
+            relay = 0
+            on = true
+            between = ["22:30", "06:00"]
+
+            [[relay_rules]]
+            relay = 0
+            on = false
+            above = 20.5
+
+            [[relay_rules]]
+            relay = 1
+            on = true
+            cheapest_hours = 4
+        "#;
+        #[derive(serde::Deserialize)]
+        struct Rules {
+            relay_rules: Vec<RelayRuleSettings>,
+        }
+        let rules: Rules = Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap();
+        assert_eq!(
+            rules.relay_rules[2].condition,
+            ConditionSettings::CheapestHours(4)
+        );
+        let rules: Vec<RelayRule> = rules
+            .relay_rules
+            .iter()
+            .map(|r| r.to_rule().unwrap())
+            .collect();
+        assert_eq!(
+            rules,
+            [
+                RelayRule {
+                    relay: 0,
+                    condition: Condition::Between {
+                        start: 22 * 60 + 30,
+                        end: 6 * 60
+                    },
+                    on: true,
+                },
+                RelayRule {
+                    relay: 0,
+                    condition: Condition::PriceAbove(20_500),
+                    on: false,
+                },
+                RelayRule {
+                    relay: 1,
+                    condition: Condition::Cheapest {
+                        count: 4,
+                        resolution: Resolution::Minutes60
+                    },
+                    on: true,
+                },
+            ]
+        );
+
+        let invalid = RelayRuleSettings {
+            relay: 0,
+            on: true,
+            condition: ConditionSettings::Between("25:00".into(), "06:00".into()),
+        };
+        assert_eq!(invalid.to_rule(), None);
+    }
 }
@@ -1,9 +1,3 @@
-use ratatui::{
-    prelude::*,
-    // style::Color,
-    widgets::{Block, Borders},
-};
-
 // pub const LIST_BLOCK: ratatui::widgets::Block<'_> = Block::new().borders(Borders::ALL);
 
 #[macro_export]
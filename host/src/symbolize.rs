@@ -0,0 +1,177 @@
+use std::{borrow::Cow, fmt::Display, path::Path};
+
+use addr2line::{
+    gimli::{EndianRcSlice, RunTimeEndian},
+    Context,
+};
+
+/// Translates program counter addresses printed by `esp-backtrace` to function names
+/// and source locations using the DWARF debug info of the firmware ELF.
+pub struct Symbolizer {
+    context: Context<EndianRcSlice<RunTimeEndian>>,
+}
+
+#[derive(Debug)]
+pub enum SymbolizeError {
+    Io(std::io::Error),
+    /// File is not an ELF or it does not contain debug info
+    InvalidElf(String),
+}
+
+impl From<std::io::Error> for SymbolizeError {
+    fn from(value: std::io::Error) -> Self {
+        Self::Io(value)
+    }
+}
+
+impl Display for SymbolizeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolizeError::Io(e) => write!(f, "failed to read firmware ELF : {e}"),
+            SymbolizeError::InvalidElf(e) => write!(f, "invalid firmware ELF : {e}"),
+        }
+    }
+}
+
+/// Single, possibly inlined, function at an address
+#[derive(Debug, PartialEq)]
+pub struct SourceFrame {
+    pub function: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl Display for SourceFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.function.as_deref().unwrap_or("??"))?;
+        if let Some(file) = &self.file {
+            write!(f, " at {file}")?;
+            if let Some(line) = self.line {
+                write!(f, ":{line}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Symbolizer {
+    /// Loads debug info from the ELF at `path`
+    pub fn from_elf(path: &Path) -> Result<Self, SymbolizeError> {
+        let data = std::fs::read(path)?;
+        let file = object::File::parse(data.as_slice())
+            .map_err(|e| SymbolizeError::InvalidElf(e.to_string()))?;
+        let context = Context::new(&file).map_err(|e| SymbolizeError::InvalidElf(e.to_string()))?;
+        Ok(Self { context })
+    }
+
+    /// Returns frames at `address`, innermost inlined function first.
+    ///
+    /// Returns empty vec if the address is not covered by the debug info.
+    pub fn lookup(&self, address: u64) -> Vec<SourceFrame> {
+        let mut frames = Vec::new();
+        let Ok(mut iter) = self.context.find_frames(address).skip_all_loads() else {
+            return frames;
+        };
+
+        while let Ok(Some(frame)) = iter.next() {
+            let function = frame
+                .function
+                .as_ref()
+                .and_then(|f| f.demangle().ok())
+                .map(Cow::into_owned);
+            let (file, line) = match frame.location {
+                Some(location) => (location.file.map(str::to_string), location.line),
+                None => (None, None),
+            };
+            frames.push(SourceFrame {
+                function,
+                file,
+                line,
+            });
+        }
+        frames
+    }
+
+    /// Appends source locations to a backtrace line printed by the device.
+    ///
+    /// Returns [None] if the line does not contain an address or it could not be resolved.
+    pub fn symbolize_line(&self, line: &str) -> Option<String> {
+        let address = parse_backtrace_address(line)?;
+        let frames = self.lookup(address);
+        if frames.is_empty() {
+            return None;
+        }
+        let frames: Vec<String> = frames.iter().map(ToString::to_string).collect();
+        Some(format!("{line} - {}", frames.join(" inlined into ")))
+    }
+}
+
+/// Parses the program counter from a line printed by `esp-backtrace`.
+///
+/// Backtrace lines contain only the address, for example `0x42000a2e`,
+/// and exception dumps contain `MEPC=0x42000a2e` or `mepc=0x42000a2e`.
+pub fn parse_backtrace_address(line: &str) -> Option<u64> {
+    let line = line.trim();
+    let hex = match line.to_ascii_lowercase().find("mepc=0x") {
+        Some(idx) => line[idx + "mepc=0x".len()..]
+            .split(|c: char| !c.is_ascii_hexdigit())
+            .next()?,
+        None => line.strip_prefix("0x")?,
+    };
+    u64::from_str_radix(hex, 16).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{parse_backtrace_address, Symbolizer};
+
+    /// Built from `tests/fixtures/backtrace_fixture.rs`
+    fn fixture() -> Symbolizer {
+        let path =
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/backtrace_fixture.elf");
+        Symbolizer::from_elf(&path).unwrap()
+    }
+
+    #[test]
+    fn parses_backtrace_lines() {
+        assert_eq!(parse_backtrace_address("0x42000a2e"), Some(0x4200_0a2e));
+        assert_eq!(parse_backtrace_address("  0x42000a2e\r"), Some(0x4200_0a2e));
+        assert_eq!(
+            parse_backtrace_address("Exception 'Illegal Instruction' mepc=0x42000010, mtval=0x0"),
+            Some(0x4200_0010)
+        );
+        assert_eq!(
+            parse_backtrace_address("MEPC=0x4200001a  RA=0x42000020"),
+            Some(0x4200_001a)
+        );
+        assert_eq!(parse_backtrace_address("Backtrace:"), None);
+        assert_eq!(parse_backtrace_address("0xzz"), None);
+    }
+
+    #[test]
+    fn resolves_function_and_line() {
+        let symbolizer = fixture();
+        let frames = symbolizer.lookup(0x4200_01c2);
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].function.as_deref(), Some("read_price"));
+        assert_eq!(
+            frames[0].file.as_deref(),
+            Some("backtrace_fixture/src/main.rs")
+        );
+        assert_eq!(frames[0].line, Some(12));
+    }
+
+    #[test]
+    fn symbolizes_backtrace_line() {
+        let symbolizer = fixture();
+        assert_eq!(
+            symbolizer.symbolize_line("0x420001ea").as_deref(),
+            Some("0x420001ea - backtrace_fixture::fail at backtrace_fixture/src/main.rs:19")
+        );
+        // Outside of the firmware
+        assert_eq!(symbolizer.symbolize_line("0x40380000"), None);
+        assert_eq!(symbolizer.symbolize_line("Backtrace:"), None);
+    }
+}
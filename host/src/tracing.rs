@@ -9,6 +9,8 @@ use tui_logger::{set_default_level, TuiTracingSubscriberLayer};
 #[instrument(level = "info")]
 pub fn initialize_logging() -> Result<()> {
     tui_logger::set_level_for_target("log", log::LevelFilter::Info);
+    // Device filters its own logs before sending them so show everything that arrives
+    tui_logger::set_level_for_target("device", log::LevelFilter::Trace);
     set_default_level(log::LevelFilter::Info);
 
     let format = fmt::format()
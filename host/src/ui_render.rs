@@ -1,10 +1,14 @@
 use crate::model::{
-    ConfigureScreenState, GetInformationScreenState, MainScreenState, Model, PopUpState,
-    QuitScreenState, RunningState, SerialPortScreenState,
+    ConfigureScreenState, DeviceStatus, GetInformationScreenState, Handshake, MainScreenState,
+    Model, PopUpState, QuitScreenState, RunningState, SerialPortScreenState,
 };
 use crossterm::event::KeyEvent;
 use host::{
-    action::Action, centered_rect, list_block, settings::keybindings::key_event_to_string,
+    action::Action,
+    centered_rect,
+    console::{is_panic_line, DeviceConsole},
+    list_block,
+    settings::keybindings::key_event_to_string,
     title_block,
 };
 use ratatui::{
@@ -12,8 +16,13 @@ use ratatui::{
     widgets::{block::Title, Block, Borders, Clear, List, ListItem, Paragraph, Wrap},
 };
 use serialport::SerialPortInfo;
+use shared::{
+    grid::AlertLevel,
+    price::{BiddingZone, Resolution},
+    tariff::cents_per_kwh,
+};
 use std::collections::HashMap;
-use strum::VariantNames;
+use strum::{VariantArray, VariantNames};
 use tui_logger::{TuiLoggerLevelOutput, TuiLoggerWidget};
 
 pub fn view(model: &mut Model, f: &mut Frame) {
@@ -22,7 +31,7 @@ pub fn view(model: &mut Model, f: &mut Frame) {
     match &mut model.running_state {
         RunningState::SelectSerialPort(state) => render_select_serialport_screen(state, f, &area),
         RunningState::Main(state) => render_main_screen(state, f, &area),
-        RunningState::Configure(state) => render_configure_screen(state, f),
+        RunningState::Configure(state) => render_configure_screen(state, f, &area),
         RunningState::GetInformation(state) => render_get_information_screen(state, f),
         RunningState::Quit(state) => render_quit_screen(state, f),
         RunningState::ForceQuit => todo!(),
@@ -173,11 +182,28 @@ fn serial_port_to_list_item<'a>(p: &SerialPortInfo) -> ListItem<'a> {
 fn render_main_screen(state: &mut MainScreenState, f: &mut Frame, area: &Rect) {
     let chunks = Layout::default()
         .direction(Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Fill(1)])
+        .constraints([
+            Constraint::Length(3),
+            Constraint::Length(3),
+            Constraint::Fill(1),
+        ])
         .split(*area);
 
+    let device = match &state.handshake {
+        Handshake::NotStarted | Handshake::Pending => "waiting for device".to_string(),
+        Handshake::Compatible(info) => {
+            format!("firmware {} ({})", info.firmware_version, info.build_hash)
+        }
+        Handshake::Incompatible(info) => {
+            format!("incompatible protocol {}", info.protocol_version)
+        }
+    };
+
     let title = Paragraph::new(Span::styled(
-        format!("Main View - selected port : {}", state.port_name),
+        format!(
+            "Main View - selected port : {} | {} | dropped frames : {}",
+            state.port_name, device, state.dropped_frames
+        ),
         Style::default()
             .fg(Color::White)
             .add_modifier(Modifier::BOLD),
@@ -186,6 +212,16 @@ fn render_main_screen(state: &mut MainScreenState, f: &mut Frame, area: &Rect) {
     .block(title_block!());
     f.render_widget(title, chunks[0]);
 
+    f.render_widget(
+        device_status_line(&state.device_status, state.price_resolution),
+        chunks[1],
+    );
+
+    if state.show_console {
+        render_device_console(&state.console, f, chunks[2]);
+        return;
+    }
+
     let list_items: Vec<ListItem> = shared::Message::VARIANTS
         .iter()
         .map(|v| ListItem::new(Line::from(Span::styled(*v, Style::default()))))
@@ -199,12 +235,137 @@ fn render_main_screen(state: &mut MainScreenState, f: &mut Frame, area: &Rect) {
         )
         .highlight_symbol("> ")
         .block(list_block!());
-    f.render_stateful_widget(list, chunks[1], &mut state.list_state);
+    f.render_stateful_widget(list, chunks[2], &mut state.list_state);
 }
 
-#[allow(unused)]
-fn render_configure_screen(state: &ConfigureScreenState, f: &mut Frame) {
-    todo!()
+fn render_device_console(console: &DeviceConsole, f: &mut Frame, area: Rect) {
+    // Show the newest lines that fit inside the borders
+    let visible = area.height.saturating_sub(2) as usize;
+    let lines: Vec<Line> = console
+        .lines()
+        .skip(console.lines().len().saturating_sub(visible))
+        .map(|line| {
+            let style = if is_panic_line(line) {
+                Style::default().fg(Color::LightRed)
+            } else {
+                Style::default()
+            };
+            Line::styled(line.as_str(), style)
+        })
+        .collect();
+
+    let block = Block::bordered()
+        .title("Device console")
+        .title_alignment(Alignment::Center);
+    f.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+fn device_status_line(status: &DeviceStatus, resolution: Resolution) -> Paragraph<'static> {
+    let unknown = || "-".to_string();
+
+    let wifi = status.wifi.map_or_else(unknown, |w| format!("{w:?}"));
+    let ip = status
+        .ip
+        .map_or_else(unknown, |[a, b, c, d]| format!("{a}.{b}.{c}.{d}"));
+    let prices = status.last_price_fetch.map_or_else(unknown, |(count, at)| {
+        format!("{count} points at {}", at.format("%H:%M"))
+    });
+
+    let minutes = resolution.seconds() / 60;
+
+    let mut spans = Vec::new();
+    if let Some(alert) = status.grid_alert.filter(|a| !a.is_normal()) {
+        let color = match alert.level() {
+            AlertLevel::Critical => Color::Red,
+            _ => Color::Yellow,
+        };
+        let headlines: Vec<&str> = alert.headlines().collect();
+        spans.push(Span::styled(
+            format!(" {} ", headlines.join(", ")),
+            Style::default()
+                .fg(Color::Black)
+                .bg(color)
+                .add_modifier(Modifier::BOLD),
+        ));
+        spans.push(Span::raw(" "));
+    }
+    spans.push(Span::raw(format!(
+        "wifi : {wifi} | ip : {ip} | prices : {prices} | display : {minutes} min"
+    )));
+    if let Some(summary) = &status.prices {
+        spans.push(Span::raw(format!(
+            " | {} spot avg {} (all-in {}) min {} ({}) max {} ({}) c/kWh",
+            summary.zone.code(),
+            cents_per_kwh(summary.average),
+            cents_per_kwh(summary.retail_average),
+            cents_per_kwh(summary.min),
+            cents_per_kwh(summary.retail_min),
+            cents_per_kwh(summary.max),
+            cents_per_kwh(summary.retail_max),
+        )));
+    }
+    let relays: Vec<String> = status
+        .relays
+        .iter()
+        .enumerate()
+        .filter_map(|(i, on)| on.map(|on| format!("{i} {}", if on { "on" } else { "off" })))
+        .collect();
+    if !relays.is_empty() {
+        spans.push(Span::raw(format!(" | relays : {}", relays.join(", "))));
+    }
+    if let Some(e) = status.last_error {
+        spans.push(Span::styled(
+            format!(" | error : {e}"),
+            Style::default().fg(Color::LightRed),
+        ));
+    }
+    if let Some(failure) = status.storage_warning {
+        spans.push(Span::styled(
+            format!(" | storage : {failure:?}"),
+            Style::default().fg(Color::LightYellow),
+        ));
+    }
+
+    Paragraph::new(Line::from(spans))
+        .alignment(Alignment::Center)
+        .block(title_block!())
+}
+
+fn render_configure_screen(state: &mut ConfigureScreenState, f: &mut Frame, area: &Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Fill(1)])
+        .split(*area);
+
+    let title = Paragraph::new(Span::styled(
+        "Configure - select bidding zone of the prices shown by the device",
+        Style::default()
+            .fg(Color::White)
+            .add_modifier(Modifier::BOLD),
+    ))
+    .alignment(Alignment::Center)
+    .block(title_block!());
+    f.render_widget(title, chunks[0]);
+
+    let list_items: Vec<ListItem> = BiddingZone::VARIANTS
+        .iter()
+        .map(|zone| {
+            ListItem::new(Line::from(Span::styled(
+                format!("{:<5} | {}", zone.code(), zone.eic()),
+                Style::default(),
+            )))
+        })
+        .collect();
+
+    let list = List::new(list_items)
+        .highlight_style(
+            Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("> ")
+        .block(list_block!());
+    f.render_stateful_widget(list, chunks[1], &mut state.zone_list);
 }
 
 #[allow(unused)]
@@ -1,10 +1,17 @@
 use color_eyre::eyre::Context;
-use host::{action::Action, title_block};
+use host::{action::Action, symbolize::Symbolizer};
 use ratatui::widgets::ListState;
-use strum::{EnumCount, VariantNames};
+use shared::{
+    planner::MAX_APPLIANCES,
+    price::{BiddingZone, Resolution},
+    Capabilities, DisplayMessage, Message, Page,
+};
+use strum::{EnumCount, VariantArray};
 use tracing::{info, instrument, trace, warn, Level};
 
-use crate::model::{MainScreenState, Model, PopUpState, RunningState, SerialPortScreenState};
+use crate::model::{
+    ConfigureScreenState, Handshake, MainScreenState, Model, PopUpState, RunningState,
+};
 
 pub fn update(model: &mut Model, message: &Action) -> Option<Action> {
     match message {
@@ -20,6 +27,14 @@ pub fn update(model: &mut Model, message: &Action) -> Option<Action> {
         Action::ClosePopUp => close_popup(model),
         Action::ShowKeyBindings => show_keybindings(model),
         Action::SerialPortConnectionFail => serial_connection_failed(model),
+        Action::ToggleDeviceConsole => toggle_device_console(model),
+        Action::StateChangeFromMainToConfigure => move_from_main_to_configure(model),
+        Action::StateChangeFromConfigureToMain => move_from_configure_to_main(model),
+        Action::SelectBiddingZone => select_bidding_zone(model),
+        Action::TogglePriceResolution => toggle_price_resolution(model),
+        Action::PlanAppliances => plan_appliances(model),
+        Action::ShowStatistics => show_statistics(model),
+        Action::CycleDisplayPage => cycle_display_page(model),
     }
 }
 
@@ -51,17 +66,13 @@ fn force_quit(model: &mut Model) -> Option<Action> {
 fn move_selection_up(model: &mut Model) -> Option<Action> {
     // trace!("selection up");
     match &mut model.running_state {
-        RunningState::SelectSerialPort(state) => {
-            return select_previous_wrapping(&mut state.list_state)
-        }
-        RunningState::Main(state) => return select_previous_wrapping(&mut state.list_state),
-        RunningState::Configure(_) => todo!(),
+        RunningState::SelectSerialPort(state) => select_previous_wrapping(&mut state.list_state),
+        RunningState::Main(state) => select_previous_wrapping(&mut state.list_state),
+        RunningState::Configure(state) => select_previous_wrapping(&mut state.zone_list),
         RunningState::GetInformation(_) => todo!(),
         RunningState::Quit(_) => todo!(),
         RunningState::ForceQuit => todo!(),
     }
-
-    None
 }
 
 #[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
@@ -73,8 +84,9 @@ fn move_selection_down(model: &mut Model) -> Option<Action> {
         RunningState::Main(state) => {
             return select_next_wrapping(&mut state.list_state, shared::Message::COUNT - 1)
         }
-
-        RunningState::Configure(_) => todo!(),
+        RunningState::Configure(state) => {
+            return select_next_wrapping(&mut state.zone_list, BiddingZone::COUNT - 1)
+        }
         RunningState::GetInformation(_) => todo!(),
         RunningState::Quit(_) => todo!(),
         RunningState::ForceQuit => todo!(),
@@ -104,7 +116,7 @@ fn move_selection_to_last(model: &mut Model) -> Option<Action> {
     match &mut model.running_state {
         RunningState::SelectSerialPort(state) => state.list_state.select_last(),
         RunningState::Main(state) => state.list_state.select_last(),
-        RunningState::Configure(_) => todo!(),
+        RunningState::Configure(state) => state.zone_list.select_last(),
         RunningState::GetInformation(_) => todo!(),
         RunningState::Quit(_) => todo!(),
         RunningState::ForceQuit => todo!(),
@@ -118,7 +130,7 @@ fn move_selection_to_first(model: &mut Model) -> Option<Action> {
     match &mut model.running_state {
         RunningState::SelectSerialPort(state) => state.list_state.select_first(),
         RunningState::Main(state) => state.list_state.select_first(),
-        RunningState::Configure(_) => todo!(),
+        RunningState::Configure(state) => state.zone_list.select_first(),
         RunningState::GetInformation(_) => todo!(),
         RunningState::Quit(_) => todo!(),
         RunningState::ForceQuit => todo!(),
@@ -133,7 +145,7 @@ fn clear_selection(model: &mut Model) -> Option<Action> {
     match &mut model.running_state {
         RunningState::SelectSerialPort(state) => state.list_state.select(None),
         RunningState::Main(_) => todo!(),
-        RunningState::Configure(_) => todo!(),
+        RunningState::Configure(state) => state.zone_list.select(None),
         RunningState::GetInformation(_) => todo!(),
         RunningState::Quit(_) => todo!(),
         RunningState::ForceQuit => todo!(),
@@ -165,8 +177,18 @@ fn move_from_serialport_to_main(model: &mut Model) -> Option<Action> {
                     .unwrap();
                 serial_port.set_parity(serialport::Parity::None).unwrap();
 
-                model.running_state =
-                    RunningState::Main(MainScreenState::with_serial_port(serial_port));
+                let mut main_state = MainScreenState::with_serial_port(serial_port);
+                if let Some(path) = &model.settings.firmware_elf {
+                    match Symbolizer::from_elf(path) {
+                        Ok(symbolizer) => main_state.symbolizer = Some(symbolizer),
+                        Err(e) => warn!("Backtraces are not symbolized, {} : {e}", path.display()),
+                    }
+                }
+                if let Err(e) = main_state.start_handshake() {
+                    warn!("Failed to send handshake to {} : {:?}", selected_port, e);
+                }
+
+                model.running_state = RunningState::Main(main_state);
             }
             None => return Some(Action::MustSelectOne),
         };
@@ -188,7 +210,11 @@ fn must_select_one(model: &mut Model) -> Option<Action> {
             ))
         }
         RunningState::Main(_) => todo!(),
-        RunningState::Configure(_) => todo!(),
+        RunningState::Configure(_) => {
+            model.popup = Some(PopUpState::Message(
+                "Select a bidding zone to continue".to_string(),
+            ))
+        }
         RunningState::GetInformation(_) => todo!(),
         RunningState::Quit(_) => todo!(),
         RunningState::ForceQuit => todo!(),
@@ -227,6 +253,226 @@ fn serial_connection_failed(model: &mut Model) -> Option<Action> {
     None
 }
 
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn toggle_device_console(model: &mut Model) -> Option<Action> {
+    if let RunningState::Main(state) = &mut model.running_state {
+        state.show_console = !state.show_console;
+    } else {
+        panic!(
+            "Illegal action ToggleDeviceConsole in state : {}",
+            model.running_state
+        );
+    }
+    None
+}
+
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn move_from_main_to_configure(model: &mut Model) -> Option<Action> {
+    match std::mem::replace(&mut model.running_state, RunningState::ForceQuit) {
+        RunningState::Main(state) => {
+            info!(target:"state_change", "from main to configure");
+            model.running_state = RunningState::Configure(ConfigureScreenState::new(state));
+        }
+        state => panic!("Illegal action StateChangeFromMainToConfigure in state : {state}"),
+    }
+    None
+}
+
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn move_from_configure_to_main(model: &mut Model) -> Option<Action> {
+    match std::mem::replace(&mut model.running_state, RunningState::ForceQuit) {
+        RunningState::Configure(ConfigureScreenState {
+            main: Some(state), ..
+        }) => {
+            info!(target:"state_change", "from configure to main");
+            model.running_state = RunningState::Main(state);
+        }
+        state => panic!("Illegal action StateChangeFromConfigureToMain in state : {state}"),
+    }
+    None
+}
+
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn select_bidding_zone(model: &mut Model) -> Option<Action> {
+    let RunningState::Configure(state) = &mut model.running_state else {
+        panic!(
+            "Illegal action SelectBiddingZone in state : {}",
+            model.running_state
+        );
+    };
+    let Some(idx) = state.zone_list.selected() else {
+        return Some(Action::MustSelectOne);
+    };
+    let zone = BiddingZone::VARIANTS[idx];
+    let connection = state
+        .main
+        .as_mut()
+        .expect("Configure screen is only opened from the main screen");
+
+    match connection.send(Message::SetBiddingZone(zone)) {
+        Ok(id) => {
+            info!("Request {id} sets bidding zone to {}", zone.code());
+            Some(Action::StateChangeFromConfigureToMain)
+        }
+        Err(e) => {
+            warn!("Failed to send bidding zone {} : {e:?}", zone.code());
+            model.popup = Some(PopUpState::Message(format!(
+                "Bidding zone was not sent to the device\n{e:?}"
+            )));
+            None
+        }
+    }
+}
+
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn toggle_price_resolution(model: &mut Model) -> Option<Action> {
+    let RunningState::Main(state) = &mut model.running_state else {
+        panic!(
+            "Illegal action TogglePriceResolution in state : {}",
+            model.running_state
+        );
+    };
+    let resolution = match state.price_resolution {
+        Resolution::Minutes15 => Resolution::Minutes60,
+        Resolution::Minutes60 => Resolution::Minutes15,
+    };
+
+    match state.send(Message::Display(DisplayMessage::PriceResolution(
+        resolution,
+    ))) {
+        Ok(id) => {
+            info!("Request {id} shows prices in {resolution:?}");
+            state.price_resolution = resolution;
+        }
+        Err(e) => {
+            warn!("Failed to send price resolution : {e:?}");
+            model.popup = Some(PopUpState::Message(format!(
+                "Price resolution was not sent to the device\n{e:?}"
+            )));
+        }
+    }
+    None
+}
+
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn plan_appliances(model: &mut Model) -> Option<Action> {
+    let RunningState::Main(state) = &mut model.running_state else {
+        panic!(
+            "Illegal action PlanAppliances in state : {}",
+            model.running_state
+        );
+    };
+    if let Some(popup) = missing_capability(state, Capabilities::PLANNER, "planning") {
+        model.popup = Some(popup);
+        return None;
+    }
+    let now = chrono::Local::now();
+    let requests = model
+        .settings
+        .appliances
+        .iter()
+        .filter_map(|a| a.to_request(&now, state.price_resolution))
+        .take(MAX_APPLIANCES)
+        .collect::<heapless::Vec<_, MAX_APPLIANCES>>();
+    if requests.is_empty() {
+        model.popup = Some(PopUpState::Message(
+            "No appliances to plan\nAdd them to settings.toml".to_string(),
+        ));
+        return None;
+    }
+
+    match state.send(Message::PlanWindow(requests)) {
+        Ok(id) => info!("Request {id} plans appliances"),
+        Err(e) => {
+            warn!("Failed to send plan request : {e:?}");
+            model.popup = Some(PopUpState::Message(format!(
+                "Plan request was not sent to the device\n{e:?}"
+            )));
+        }
+    }
+    None
+}
+
+/// True if the device firmware can draw `page`
+fn page_supported(state: &MainScreenState, page: Page) -> bool {
+    match page {
+        Page::Prices => true,
+        Page::Statistics => state.supports(Capabilities::STATISTICS),
+        Page::Emissions => state.supports(Capabilities::EMISSIONS),
+    }
+}
+
+/// Popup telling that the device firmware lacks `feature`, [None] if it has the
+/// `capability` or the handshake has not completed
+fn missing_capability(
+    state: &MainScreenState,
+    capability: Capabilities,
+    feature: &str,
+) -> Option<PopUpState> {
+    let Handshake::Compatible(info) = &state.handshake else {
+        return None;
+    };
+    (!state.supports(capability)).then(|| {
+        PopUpState::Message(format!(
+            "Device firmware {} does not support {feature}\nUpdate the firmware",
+            info.firmware_version
+        ))
+    })
+}
+
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn show_statistics(model: &mut Model) -> Option<Action> {
+    let RunningState::Main(state) = &mut model.running_state else {
+        panic!(
+            "Illegal action ShowStatistics in state : {}",
+            model.running_state
+        );
+    };
+    if let Some(popup) = missing_capability(state, Capabilities::STATISTICS, "statistics") {
+        model.popup = Some(popup);
+        return None;
+    }
+    match state.send(Message::GetStatistics) {
+        Ok(id) => info!("Request {id} gets statistics"),
+        Err(e) => {
+            warn!("Failed to send statistics request : {e:?}");
+            model.popup = Some(PopUpState::Message(format!(
+                "Statistics request was not sent to the device\n{e:?}"
+            )));
+        }
+    }
+    None
+}
+
+#[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
+fn cycle_display_page(model: &mut Model) -> Option<Action> {
+    let RunningState::Main(state) = &mut model.running_state else {
+        panic!(
+            "Illegal action CycleDisplayPage in state : {}",
+            model.running_state
+        );
+    };
+    let mut page = state.display_page.next();
+    // Prices are always supported so this ends
+    while !page_supported(state, page) {
+        page = page.next();
+    }
+
+    match state.send(Message::Display(DisplayMessage::ShowPage(page))) {
+        Ok(id) => {
+            info!("Request {id} shows {page:?} page");
+            state.display_page = page;
+        }
+        Err(e) => {
+            warn!("Failed to send display page : {e:?}");
+            model.popup = Some(PopUpState::Message(format!(
+                "Display page was not sent to the device\n{e:?}"
+            )));
+        }
+    }
+    None
+}
+
 #[instrument(ret(level=Level::TRACE), skip_all, fields(state = model.running_state.to_string()))]
 fn close_popup(model: &mut Model) -> Option<Action> {
     model.popup = None;
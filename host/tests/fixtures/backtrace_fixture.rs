@@ -0,0 +1,36 @@
+//! Source of `backtrace_fixture.elf` used by the symbolication tests.
+//!
+//! Built with `cargo +nightly build -Z build-std=core --target riscv32imc-unknown-none-elf`
+//! and `-C link-arg=-Ttext=0x42000000`, the address where esp32c3 maps flash.
+//! Paths are remapped with `--remap-path-prefix` so that they do not depend on the build machine.
+#![no_std]
+#![no_main]
+
+#[inline(never)]
+#[no_mangle]
+pub extern "C" fn read_price(index: u32) -> u32 {
+    if index > 96 {
+        fail();
+    }
+    index * 3
+}
+
+#[inline(never)]
+fn fail() -> ! {
+    panic!("price index out of range");
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    let mut total = 0;
+    for i in 0..100 {
+        total += read_price(i);
+    }
+    let _ = total;
+    loop {}
+}
+
+#[panic_handler]
+fn panic(_info: &core::panic::PanicInfo) -> ! {
+    loop {}
+}
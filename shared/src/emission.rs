@@ -0,0 +1,238 @@
+//! Carbon dioxide emissions of electricity in Finland, estimated by Fingrid every 3 minutes.
+//!
+//! Latest emission factors of consumed and produced electricity are kept together with
+//! hourly averages of the consumed electricity for the last [TREND_HOURS] hours, so that
+//! the current value can be compared to the recent ones, like prices with [statistics].
+//!
+//! [statistics]: crate::statistics
+
+use heapless::Vec;
+
+use crate::{
+    fingrid::{dataset, DataPoint, VALUE_SCALE},
+    price::Timestamp,
+};
+
+/// Hours in [Emissions::trend]
+pub const TREND_HOURS: usize = 24;
+
+const SECONDS_PER_HOUR: Timestamp = 3600;
+
+/// Values of an hour of [dataset::CONSUMPTION_EMISSIONS]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Hour {
+    start: Timestamp,
+    sum: i64,
+    count: u32,
+    /// Start of the newest value, so that values fetched again are not counted twice
+    last: Timestamp,
+}
+
+/// Latest emission factors and the trend of the consumed electricity
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Emissions {
+    /// Latest value of [dataset::CONSUMPTION_EMISSIONS]
+    pub consumption: Option<DataPoint>,
+    /// Latest value of [dataset::PRODUCTION_EMISSIONS]
+    pub production: Option<DataPoint>,
+    /// Hours with values in time order, none older than [TREND_HOURS] before the newest
+    hours: Vec<Hour, TREND_HOURS>,
+}
+
+impl Emissions {
+    pub const fn new() -> Self {
+        Self {
+            consumption: None,
+            production: None,
+            hours: Vec::new(),
+        }
+    }
+
+    /// Records `point` of [dataset::CONSUMPTION_EMISSIONS] or [dataset::PRODUCTION_EMISSIONS].
+    ///
+    /// Latest values are replaced only by newer ones. Older consumption values still fill in
+    /// hours of the trend that have no values yet, see [Self::missing_hours], but within an
+    /// hour only values newer than the ones recorded are counted. Points of other datasets
+    /// are ignored.
+    pub fn update(&mut self, point: &DataPoint) {
+        let latest = match point.dataset {
+            dataset::CONSUMPTION_EMISSIONS => {
+                self.record(point);
+                &mut self.consumption
+            }
+            dataset::PRODUCTION_EMISSIONS => &mut self.production,
+            _ => return,
+        };
+        if latest.map_or(true, |latest| latest.start < point.start) {
+            *latest = Some(*point);
+        }
+    }
+
+    fn record(&mut self, point: &DataPoint) {
+        let start = point.start - point.start.rem_euclid(SECONDS_PER_HOUR);
+        let newest = self.hours.last().map_or(start, |h| h.start.max(start));
+        let oldest = newest - (TREND_HOURS as Timestamp - 1) * SECONDS_PER_HOUR;
+        if start < oldest {
+            return;
+        }
+
+        match self.hours.binary_search_by_key(&start, |h| h.start) {
+            Ok(i) => {
+                let hour = &mut self.hours[i];
+                if point.start > hour.last {
+                    hour.sum += point.value as i64;
+                    hour.count += 1;
+                    hour.last = point.start;
+                }
+            }
+            Err(_) => {
+                self.hours.retain(|h| h.start >= oldest);
+                let i = self.hours.partition_point(|h| h.start < start);
+                // At most TREND_HOURS - 1 other hours are left in the window
+                let _ = self.hours.insert(
+                    i,
+                    Hour {
+                        start,
+                        sum: point.value as i64,
+                        count: 1,
+                        last: point.start,
+                    },
+                );
+            }
+        }
+    }
+
+    /// Start and average of each hour with consumption values, oldest first.
+    /// Values are in 1/[VALUE_SCALE] gCO2/kWh.
+    pub fn trend(&self) -> impl Iterator<Item = (Timestamp, i32)> + '_ {
+        self.hours
+            .iter()
+            .map(|h| (h.start, (h.sum / h.count as i64) as i32))
+    }
+
+    /// Average of the hourly averages in [Self::trend]
+    pub fn average(&self) -> Option<i32> {
+        let count = self.hours.len() as i64;
+        let sum: i64 = self.trend().map(|(_, average)| average as i64).sum();
+        (count > 0).then(|| (sum / count) as i32)
+    }
+
+    /// Latest consumption value relative to [Self::average] in percent, for example -20 when
+    /// electricity is 20 % cleaner than usual. [None] if either is unknown or the average is
+    /// not positive.
+    pub fn vs_average(&self) -> Option<i32> {
+        let latest = self.consumption?.value as i64;
+        let average = self.average().filter(|a| *a > 0)? as i64;
+        Some(((latest - average) * 100 / average) as i32)
+    }
+
+    /// Starts of the hours of the [TREND_HOURS] before the hour of `now` without any
+    /// consumption values, oldest first
+    pub fn missing_hours(&self, now: Timestamp) -> impl Iterator<Item = Timestamp> + '_ {
+        let current = now - now.rem_euclid(SECONDS_PER_HOUR);
+        (1..=TREND_HOURS as Timestamp)
+            .rev()
+            .map(move |i| current - i * SECONDS_PER_HOUR)
+            .filter(|start| self.hours.binary_search_by_key(start, |h| h.start).is_err())
+    }
+}
+
+/// Emission factor in whole gCO2/kWh, fractions are truncated
+pub fn grams_per_kwh(value: i32) -> i32 {
+    value / VALUE_SCALE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Emissions, TREND_HOURS};
+    use crate::{
+        fingrid::{dataset, DataPoint, DatasetId, VALUE_SCALE},
+        price::Timestamp,
+    };
+
+    /// 2025-01-15T00:00:00Z
+    const MIDNIGHT: Timestamp = 1_736_899_200;
+
+    fn point(dataset: DatasetId, start: Timestamp, grams: i32) -> DataPoint {
+        DataPoint {
+            dataset,
+            start,
+            end: start + 180,
+            value: grams * VALUE_SCALE,
+        }
+    }
+
+    fn consumption(start: Timestamp, grams: i32) -> DataPoint {
+        point(dataset::CONSUMPTION_EMISSIONS, start, grams)
+    }
+
+    #[test]
+    fn latest_values() {
+        let mut emissions = Emissions::new();
+        emissions.update(&consumption(MIDNIGHT + 180, 100));
+        emissions.update(&point(dataset::PRODUCTION_EMISSIONS, MIDNIGHT, 60));
+        // Older value does not replace the latest one or change an hour with newer values
+        emissions.update(&consumption(MIDNIGHT, 50));
+        emissions.update(&point(dataset::CONSUMPTION, MIDNIGHT + 360, 9000));
+
+        assert_eq!(emissions.consumption.unwrap().value, 100 * VALUE_SCALE);
+        assert_eq!(emissions.production.unwrap().value, 60 * VALUE_SCALE);
+        assert_eq!(
+            emissions.trend().collect::<std::vec::Vec<_>>(),
+            [(MIDNIGHT, 100 * VALUE_SCALE)]
+        );
+    }
+
+    #[test]
+    fn trend_of_last_day() {
+        let mut emissions = Emissions::new();
+        // 30 hours of values every 3 minutes, 100 g at night and 200 g at day
+        for i in 0..30 * 20 {
+            let start = MIDNIGHT + i * 180;
+            let hour = (i / 20) % 24;
+            let grams = if (6..18).contains(&hour) { 200 } else { 100 };
+            emissions.update(&consumption(start, grams));
+            // Same value fetched again is not counted twice
+            emissions.update(&consumption(start, 1000));
+        }
+
+        let trend: std::vec::Vec<_> = emissions.trend().collect();
+        assert_eq!(trend.len(), TREND_HOURS);
+        assert_eq!(trend[0].0, MIDNIGHT + 6 * 3600);
+        assert_eq!(trend[23].0, MIDNIGHT + 29 * 3600);
+        assert!(trend.iter().take(12).all(|(_, v)| *v == 200 * VALUE_SCALE));
+        assert_eq!(emissions.average(), Some(150 * VALUE_SCALE));
+        assert_eq!(emissions.vs_average(), Some(-33));
+
+        // Older than the trend
+        emissions.update(&consumption(MIDNIGHT + 3600, 0));
+        assert_eq!(emissions.trend().next().unwrap().0, MIDNIGHT + 6 * 3600);
+    }
+
+    #[test]
+    fn missing_hours_are_filled_in() {
+        let mut emissions = Emissions::new();
+        let now = MIDNIGHT + 24 * 3600 + 600;
+        assert_eq!(emissions.missing_hours(now).count(), TREND_HOURS);
+        assert_eq!(emissions.missing_hours(now).next(), Some(MIDNIGHT));
+
+        emissions.update(&consumption(now - 300, 300));
+        emissions.update(&consumption(MIDNIGHT + 5 * 3600, 100));
+        emissions.update(&consumption(MIDNIGHT + 2 * 3600, 100));
+        assert_eq!(emissions.missing_hours(now).count(), TREND_HOURS - 2);
+        assert!(emissions
+            .missing_hours(now)
+            .all(|start| start != MIDNIGHT + 2 * 3600 && start != MIDNIGHT + 5 * 3600));
+
+        let starts: std::vec::Vec<_> = emissions.trend().map(|(start, _)| start).collect();
+        assert_eq!(
+            starts,
+            [
+                MIDNIGHT + 2 * 3600,
+                MIDNIGHT + 5 * 3600,
+                MIDNIGHT + 24 * 3600
+            ]
+        );
+        assert_eq!(emissions.vs_average(), Some(80));
+    }
+}
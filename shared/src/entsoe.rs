@@ -0,0 +1,535 @@
+//! Streaming parser for ENTSO-E Transparency Platform day-ahead prices.
+//!
+//! Parses `Publication_MarketDocument` with documentType A44 as it arrives from the network,
+//! so the whole response never has to fit to memory. Only the elements needed for prices are
+//! looked at and everything else is skipped.
+//!
+//! ```xml
+//! <TimeSeries>
+//!   <currency_Unit.name>EUR</currency_Unit.name>
+//!   <curveType>A03</curveType>
+//!   <Period>
+//!     <timeInterval><start>2024-10-01T22:00Z</start><end>2024-10-02T22:00Z</end></timeInterval>
+//!     <resolution>PT60M</resolution>
+//!     <Point><position>1</position><price.amount>45.67</price.amount></Point>
+//!   </Period>
+//! </TimeSeries>
+//! ```
+//!
+//! With curve type A03 a position is omitted when its price is the same as the previous position,
+//! the parser fills those gaps so that [PriceSeries] always has every time unit.
+
+use heapless::String;
+
+use crate::fixed::parse_decimal;
+use crate::price::{
+    Currency, PricePoint, PriceSeries, PriceSeriesError, Resolution, Timestamp, PRICE_SCALE,
+};
+use crate::time::UtcDateTime;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EntsoeError {
+    /// Document is not well formed or a required element is missing
+    Malformed,
+    /// Response was an `Acknowledgement_MarketDocument` or it contained no points
+    NoData,
+    /// Period resolution is something else than PT15M or PT60M
+    UnsupportedResolution,
+    InvalidTime,
+    InvalidNumber,
+    /// Series ran out of capacity
+    Full,
+}
+
+impl core::fmt::Display for EntsoeError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            EntsoeError::Malformed => write!(f, "malformed document"),
+            EntsoeError::NoData => write!(f, "no data in response"),
+            EntsoeError::UnsupportedResolution => write!(f, "unsupported resolution"),
+            EntsoeError::InvalidTime => write!(f, "invalid time"),
+            EntsoeError::InvalidNumber => write!(f, "invalid number"),
+            EntsoeError::Full => write!(f, "too many price points"),
+        }
+    }
+}
+
+/// Longest element name or text content that is kept, longer ones are truncated
+const TOKEN_LEN: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Lexer {
+    /// Between tags
+    Text,
+    /// Right after `<`
+    TagOpen,
+    TagName,
+    /// Attributes after the tag name
+    InTag,
+    /// Processing instruction, comment or doctype, skipped until `>`
+    Skip,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Curve {
+    /// Every position is present
+    Sequential,
+    /// Positions with the same price as the previous one are omitted
+    VariableBlocks,
+}
+
+/// Push parser for A44 documents, see [module documentation](self)
+#[derive(Debug)]
+pub struct EntsoeParser {
+    lexer: Lexer,
+    closing: bool,
+    /// Previous byte inside a tag, used to detect self-closing tags
+    prev: u8,
+    name: String<TOKEN_LEN>,
+    text: String<TOKEN_LEN>,
+
+    acknowledgement: bool,
+    curve: Curve,
+    in_period: bool,
+    period_start: Option<Timestamp>,
+    period_end: Option<Timestamp>,
+    resolution: Option<Resolution>,
+    position: Option<u32>,
+    price: Option<i32>,
+    /// Position and price of the previous point in the current period
+    last: Option<(u32, i32)>,
+    points: usize,
+    error: Option<EntsoeError>,
+}
+
+impl Default for EntsoeParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EntsoeParser {
+    pub const fn new() -> Self {
+        Self {
+            lexer: Lexer::Text,
+            closing: false,
+            prev: 0,
+            name: String::new(),
+            text: String::new(),
+            acknowledgement: false,
+            curve: Curve::Sequential,
+            in_period: false,
+            period_start: None,
+            period_end: None,
+            resolution: None,
+            position: None,
+            price: None,
+            last: None,
+            points: 0,
+            error: None,
+        }
+    }
+
+    /// Parses next part of the document and appends completed points to `series`.
+    ///
+    /// Points that overlap with points already in `series` are skipped, ENTSO-E may return
+    /// the same day in several time series.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the document is invalid. After an error
+    /// the same error is returned for all further input.
+    pub fn feed<const N: usize>(
+        &mut self,
+        input: &[u8],
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), EntsoeError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        for byte in input {
+            if let Err(e) = self.feed_byte(*byte, series) {
+                self.error = Some(e);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the whole document was parsed and it contained prices
+    pub fn finish(&self) -> Result<(), EntsoeError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if self.acknowledgement {
+            return Err(EntsoeError::NoData);
+        }
+        if self.in_period || self.lexer != Lexer::Text {
+            return Err(EntsoeError::Malformed);
+        }
+        if self.points == 0 {
+            return Err(EntsoeError::NoData);
+        }
+        Ok(())
+    }
+
+    fn feed_byte<const N: usize>(
+        &mut self,
+        byte: u8,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), EntsoeError> {
+        match self.lexer {
+            Lexer::Text => {
+                if byte == b'<' {
+                    self.lexer = Lexer::TagOpen;
+                } else if !byte.is_ascii_whitespace() || !self.text.is_empty() {
+                    // Content is ASCII for all elements that are used, truncation is fine
+                    let _ = self.text.push(byte as char);
+                }
+            }
+            Lexer::TagOpen => {
+                self.name.clear();
+                self.closing = false;
+                self.prev = 0;
+                match byte {
+                    b'/' => {
+                        self.closing = true;
+                        self.lexer = Lexer::TagName;
+                    }
+                    b'?' | b'!' => self.lexer = Lexer::Skip,
+                    _ => {
+                        let _ = self.name.push(byte as char);
+                        self.lexer = Lexer::TagName;
+                    }
+                }
+            }
+            Lexer::TagName => match byte {
+                b'>' => {
+                    self.lexer = Lexer::Text;
+                    self.tag_complete(false, series)?;
+                }
+                b'/' => {
+                    self.prev = byte;
+                    self.lexer = Lexer::InTag;
+                }
+                b if b.is_ascii_whitespace() => self.lexer = Lexer::InTag,
+                _ => {
+                    let _ = self.name.push(byte as char);
+                }
+            },
+            Lexer::InTag => {
+                if byte == b'>' {
+                    self.lexer = Lexer::Text;
+                    let self_closing = self.prev == b'/';
+                    self.tag_complete(self_closing, series)?;
+                }
+                self.prev = byte;
+            }
+            Lexer::Skip => {
+                if byte == b'>' {
+                    self.lexer = Lexer::Text;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tag_complete<const N: usize>(
+        &mut self,
+        self_closing: bool,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), EntsoeError> {
+        if self.closing {
+            return self.end_element(series);
+        }
+        self.start_element();
+        self.text.clear();
+        if self_closing {
+            self.end_element(series)?;
+        }
+        Ok(())
+    }
+
+    fn start_element(&mut self) {
+        match self.name.as_str() {
+            "Acknowledgement_MarketDocument" => self.acknowledgement = true,
+            "TimeSeries" => self.curve = Curve::Sequential,
+            "Period" => {
+                self.in_period = true;
+                self.period_start = None;
+                self.period_end = None;
+                self.resolution = None;
+                self.last = None;
+            }
+            "Point" => {
+                self.position = None;
+                self.price = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn end_element<const N: usize>(
+        &mut self,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), EntsoeError> {
+        let text = self.text.trim_end();
+        match self.name.as_str() {
+            "curveType" => {
+                self.curve = match text {
+                    "A03" => Curve::VariableBlocks,
+                    _ => Curve::Sequential,
+                }
+            }
+            "currency_Unit.name" => {
+                series.currency = match text {
+                    "EUR" => Currency::Eur,
+                    "SEK" => Currency::Sek,
+                    "NOK" => Currency::Nok,
+                    "DKK" => Currency::Dkk,
+                    _ => return Err(EntsoeError::Malformed),
+                }
+            }
+            // Document level period.timeInterval has the same children, only the one
+            // inside Period matters
+            "start" if self.in_period => self.period_start = Some(parse_time(text)?),
+            "end" if self.in_period => self.period_end = Some(parse_time(text)?),
+            "resolution" if self.in_period => {
+                let resolution = match text {
+                    "PT15M" => Resolution::Minutes15,
+                    "PT60M" => Resolution::Minutes60,
+                    _ => return Err(EntsoeError::UnsupportedResolution),
+                };
+                self.resolution = Some(resolution);
+            }
+            "position" => {
+                let position = text.parse().map_err(|_| EntsoeError::InvalidNumber)?;
+                if position == 0 {
+                    return Err(EntsoeError::InvalidNumber);
+                }
+                self.position = Some(position);
+            }
+            "price.amount" => self.price = Some(parse_price(text)?),
+            "Point" => {
+                let (Some(position), Some(price)) = (self.position, self.price) else {
+                    return Err(EntsoeError::Malformed);
+                };
+                self.point(position, price, series)?;
+            }
+            "Period" => {
+                self.end_period(series)?;
+                self.in_period = false;
+            }
+            _ => {}
+        }
+        self.text.clear();
+        Ok(())
+    }
+
+    fn point<const N: usize>(
+        &mut self,
+        position: u32,
+        price: i32,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), EntsoeError> {
+        if let (Curve::VariableBlocks, Some((last_position, last_price))) = (self.curve, self.last)
+        {
+            for omitted in last_position + 1..position {
+                self.push(omitted, last_price, series)?;
+            }
+        }
+        self.push(position, price, series)?;
+        self.last = Some((position, price));
+        Ok(())
+    }
+
+    /// Repeats the last price until the end of the period for curve type A03
+    fn end_period<const N: usize>(
+        &mut self,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), EntsoeError> {
+        let (Curve::VariableBlocks, Some((last_position, last_price))) = (self.curve, self.last)
+        else {
+            return Ok(());
+        };
+        let (Some(start), Some(end), Some(resolution)) =
+            (self.period_start, self.period_end, self.resolution)
+        else {
+            return Err(EntsoeError::Malformed);
+        };
+        let positions = ((end - start) / resolution.seconds() as Timestamp) as u32;
+        for omitted in last_position + 1..=positions {
+            self.push(omitted, last_price, series)?;
+        }
+        Ok(())
+    }
+
+    fn push<const N: usize>(
+        &mut self,
+        position: u32,
+        price: i32,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), EntsoeError> {
+        let (Some(start), Some(resolution)) = (self.period_start, self.resolution) else {
+            return Err(EntsoeError::Malformed);
+        };
+        let duration = resolution.seconds();
+        let point = PricePoint {
+            start: start + (position - 1) as Timestamp * duration as Timestamp,
+            duration,
+            price,
+        };
+        match series.push(point) {
+            Ok(()) => {
+                self.points += 1;
+                Ok(())
+            }
+            Err(PriceSeriesError::NotSorted) => Ok(()),
+            Err(PriceSeriesError::Full) => Err(EntsoeError::Full),
+        }
+    }
+}
+
+fn parse_time(s: &str) -> Result<Timestamp, EntsoeError> {
+    UtcDateTime::parse_iso8601(s)
+        .map(|dt| dt.timestamp())
+        .ok_or(EntsoeError::InvalidTime)
+}
+
+/// Parses decimal number such as `-12.5` to fixed point with [PRICE_SCALE].
+///
+/// Decimals beyond the scale are truncated.
+pub fn parse_price(s: &str) -> Result<i32, EntsoeError> {
+    parse_decimal(s, PRICE_SCALE).ok_or(EntsoeError::InvalidNumber)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{parse_price, EntsoeError, EntsoeParser};
+    use crate::price::{BiddingZone, Currency, PriceSeries, Resolution};
+    use crate::time::UtcDateTime;
+
+    const HOURLY: &str = include_str!("../tests/fixtures/entsoe/a44_pt60m_fi.xml");
+    const QUARTER_HOURLY: &str = include_str!("../tests/fixtures/entsoe/a44_pt15m_fi.xml");
+    const TRANSITION: &str = include_str!("../tests/fixtures/entsoe/a44_transition_fi.xml");
+    const ACKNOWLEDGEMENT: &str = include_str!("../tests/fixtures/entsoe/acknowledgement.xml");
+
+    fn parse(document: &str, chunk_size: usize) -> (PriceSeries, Result<(), EntsoeError>) {
+        let mut series = PriceSeries::new(BiddingZone::Fi, Currency::Sek, Resolution::Minutes15);
+        let mut parser = EntsoeParser::new();
+        for chunk in document.as_bytes().chunks(chunk_size) {
+            if let Err(e) = parser.feed(chunk, &mut series) {
+                return (series, Err(e));
+            }
+        }
+        let result = parser.finish();
+        (series, result)
+    }
+
+    fn prices(series: &PriceSeries) -> Vec<i32> {
+        series.points().iter().map(|p| p.price).collect()
+    }
+
+    #[test]
+    fn hourly_with_omitted_positions() {
+        let (series, result) = parse(HOURLY, 4096);
+        result.unwrap();
+        assert_eq!(series.currency, Currency::Eur);
+        assert_eq!(series.resolution, Resolution::Minutes60);
+        assert_eq!(series.len(), 24);
+
+        let start = UtcDateTime::parse_iso8601("2024-10-01T22:00Z")
+            .unwrap()
+            .timestamp();
+        assert_eq!(series.points()[0].start, start);
+        assert_eq!(series.points()[23].end(), start + 24 * 3600);
+
+        // Positions 3, 4 and 22..=24 are omitted and repeat the previous price
+        let expected = [
+            1234, 1102, 1102, 1102, 998, 1500, 4567, 8901, 12000, 9950, 7010, 5005, 3000, 2999,
+            2500, 2400, 3100, 6000, 15012, 9999, 500, 500, 500, 500,
+        ];
+        assert_eq!(prices(&series), expected);
+    }
+
+    #[test]
+    fn quarter_hourly_with_omitted_negative_prices() {
+        let (series, result) = parse(QUARTER_HOURLY, 4096);
+        result.unwrap();
+        assert_eq!(series.resolution, Resolution::Minutes15);
+        assert_eq!(series.len(), 96);
+        assert_eq!(series.points()[1].start - series.points()[0].start, 900);
+        // Positions 54..=60 and 92..=96 are omitted
+        assert!(series.points()[52..60].iter().all(|p| p.price == -150));
+        assert!(series.points()[90..].iter().all(|p| p.price == 725));
+    }
+
+    #[test]
+    fn hourly_day_followed_by_quarter_hourly_day() {
+        let (series, result) = parse(TRANSITION, 4096);
+        result.unwrap();
+        // Finest resolution even though the hourly day comes first
+        assert_eq!(series.resolution, Resolution::Minutes15);
+        assert_eq!(series.len(), 24 + 96);
+        assert_eq!(series.points()[23].duration, 3600);
+        assert_eq!(series.points()[24].duration, 900);
+        assert_eq!(series.points()[24].start, series.points()[23].end());
+
+        let hourly: Vec<_> = series.resampled(Resolution::Minutes60).collect();
+        assert_eq!(hourly.len(), 48);
+        assert_eq!(&hourly[..24], &series.points()[..24]);
+        // Quarters 4.00, 3.20, 2.40 and 1.60 of the first hour of the second day
+        assert_eq!(hourly[24].price, 280);
+        assert_eq!(hourly[25].price, 440);
+
+        let quarters: Vec<_> = series.resampled(Resolution::Minutes15).collect();
+        assert_eq!(quarters.len(), 192);
+        assert!(quarters[..4]
+            .iter()
+            .all(|p| p.price == 351 && p.duration == 900));
+        assert_eq!(&quarters[96..], &series.points()[24..]);
+    }
+
+    #[test]
+    fn chunk_boundaries_do_not_matter() {
+        let (whole, _) = parse(HOURLY, 4096);
+        for chunk_size in [1, 2, 3, 7, 64] {
+            let (chunked, result) = parse(HOURLY, chunk_size);
+            result.unwrap();
+            assert_eq!(chunked, whole);
+        }
+    }
+
+    #[test]
+    fn acknowledgement_is_no_data() {
+        let (series, result) = parse(ACKNOWLEDGEMENT, 4096);
+        assert_eq!(result, Err(EntsoeError::NoData));
+        assert!(series.is_empty());
+    }
+
+    #[test]
+    fn truncated_document_is_malformed() {
+        let (_, result) = parse(&HOURLY[..HOURLY.len() / 2], 4096);
+        assert_eq!(result, Err(EntsoeError::Malformed));
+    }
+
+    #[test]
+    fn unsupported_resolution() {
+        let document = HOURLY.replace("PT60M", "P1D");
+        let (_, result) = parse(&document, 4096);
+        assert_eq!(result, Err(EntsoeError::UnsupportedResolution));
+    }
+
+    #[test]
+    fn decimal_prices() {
+        assert_eq!(parse_price("45.67"), Ok(4567));
+        assert_eq!(parse_price("45.6"), Ok(4560));
+        assert_eq!(parse_price("45"), Ok(4500));
+        assert_eq!(parse_price("-0.01"), Ok(-1));
+        assert_eq!(parse_price("1.239"), Ok(123));
+        assert_eq!(parse_price(""), Err(EntsoeError::InvalidNumber));
+        assert_eq!(parse_price("1.2e3"), Err(EntsoeError::InvalidNumber));
+    }
+}
@@ -0,0 +1,409 @@
+//! Streaming parser for Fingrid open data API v2 responses.
+//!
+//! Values of a dataset are requested with the `x-api-key` header from
+//! `https://data.fingrid.fi/api/datasets/{datasetId}/data`, which returns one page of values
+//!
+//! ```json
+//! {
+//!   "data": [
+//!     {"datasetId": 193, "startTime": "2024-10-01T00:00:00.000Z",
+//!      "endTime": "2024-10-01T00:03:00.000Z", "value": 8456.3}
+//!   ],
+//!   "pagination": {"total": 20, "lastPage": 2, "nextPage": 2, "currentPage": 1}
+//! }
+//! ```
+//!
+//! or from `.../data/latest`, which returns a single value object without the wrapper.
+//! Like [entsoe](crate::entsoe) the response is parsed as it arrives and only the keys
+//! needed for [DataPoint]s are looked at.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::fixed::parse_decimal;
+use crate::json::{Container, JsonError, JsonLexer, Level, Token};
+use crate::price::Timestamp;
+use crate::time::UtcDateTime;
+
+/// Identifies a Fingrid dataset, see <https://data.fingrid.fi/en/datasets>
+pub type DatasetId = u16;
+
+/// Commonly used datasets
+pub mod dataset {
+    use super::DatasetId;
+
+    /// Electricity consumption in Finland, MW
+    pub const CONSUMPTION: DatasetId = 193;
+    /// Electricity production in Finland, MW
+    pub const PRODUCTION: DatasetId = 192;
+    /// Wind power generation in Finland, MW
+    pub const WIND_GENERATION: DatasetId = 181;
+    /// Net import to Finland, MW. Negative when exporting.
+    pub const NET_IMPORT: DatasetId = 194;
+    /// Imbalance price, €/MWh. Published after each 15 minute time unit.
+    pub const IMBALANCE_PRICE: DatasetId = 319;
+    /// Power system state, 1 for normal (green) to 5 for restoration (blue),
+    /// see [SystemState](crate::grid::SystemState)
+    pub const POWER_SYSTEM_STATE: DatasetId = 209;
+    /// Electricity shortage status, 0 for normal to 3 for shortage,
+    /// see [ShortageStatus](crate::grid::ShortageStatus)
+    pub const SHORTAGE_STATUS: DatasetId = 336;
+    /// Emission factor of electricity consumed in Finland, gCO2/kWh. Updated every 3 minutes.
+    pub const CONSUMPTION_EMISSIONS: DatasetId = 265;
+    /// Emission factor of electricity produced in Finland, gCO2/kWh. Updated every 3 minutes.
+    pub const PRODUCTION_EMISSIONS: DatasetId = 266;
+}
+
+/// Most datasets that can be configured to be fetched
+pub const MAX_DATASETS: usize = 8;
+
+/// Real-time datasets updated every 3 minutes, fetched until the host configures others
+pub const DEFAULT_DATASETS: [DatasetId; 4] = [
+    dataset::CONSUMPTION,
+    dataset::PRODUCTION,
+    dataset::WIND_GENERATION,
+    dataset::NET_IMPORT,
+];
+
+/// [DataPoint::value] is in 1/`VALUE_SCALE` of the dataset unit
+pub const VALUE_SCALE: i32 = 1000;
+
+/// Value of a dataset for a time period
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct DataPoint {
+    pub dataset: DatasetId,
+    pub start: Timestamp,
+    pub end: Timestamp,
+    /// Value in 1/[VALUE_SCALE] of the dataset unit, for example 8456300 is 8456.3 MW
+    pub value: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FingridError {
+    /// Response is not valid JSON or a value object is missing a key
+    Malformed,
+    /// Response contained no values, for example it was an error message
+    NoData,
+    InvalidTime,
+    InvalidNumber,
+    /// Output ran out of capacity
+    Full,
+}
+
+impl core::fmt::Display for FingridError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            FingridError::Malformed => write!(f, "malformed response"),
+            FingridError::NoData => write!(f, "no data in response"),
+            FingridError::InvalidTime => write!(f, "invalid time"),
+            FingridError::InvalidNumber => write!(f, "invalid number"),
+            FingridError::Full => write!(f, "too many data points"),
+        }
+    }
+}
+
+impl From<JsonError> for FingridError {
+    fn from(_: JsonError) -> Self {
+        Self::Malformed
+    }
+}
+
+/// Push parser for dataset responses, see [module documentation](self)
+#[derive(Debug, Default)]
+pub struct FingridParser {
+    json: JsonLexer,
+    values: ValueState,
+    error: Option<FingridError>,
+}
+
+/// Everything except the lexer, so that it can be borrowed by the token callback
+#[derive(Debug, Default)]
+struct ValueState {
+    dataset: Option<DatasetId>,
+    start: Option<Timestamp>,
+    end: Option<Timestamp>,
+    /// `Some(None)` if the value was `null`
+    value: Option<Option<i32>>,
+    next_page: Option<u32>,
+    points: usize,
+}
+
+impl FingridParser {
+    pub const fn new() -> Self {
+        Self {
+            json: JsonLexer::new(),
+            values: ValueState {
+                dataset: None,
+                start: None,
+                end: None,
+                value: None,
+                next_page: None,
+                points: 0,
+            },
+            error: None,
+        }
+    }
+
+    /// Parses next part of the response and appends completed values to `points`.
+    ///
+    /// Values that are `null` are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the response is invalid. After an error
+    /// the same error is returned for all further input.
+    pub fn feed<const N: usize>(
+        &mut self,
+        input: &[u8],
+        points: &mut Vec<DataPoint, N>,
+    ) -> Result<(), FingridError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        for byte in input {
+            let result = self.json.feed(*byte, &mut |token, path| {
+                self.values.token(token, path, points)
+            });
+            if let Err(e) = result {
+                self.error = Some(e);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the whole response was parsed and it contained values
+    pub fn finish(&self) -> Result<(), FingridError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if !self.json.is_done() {
+            return Err(FingridError::Malformed);
+        }
+        if self.values.points == 0 {
+            return Err(FingridError::NoData);
+        }
+        Ok(())
+    }
+
+    /// Page to request next, [None] if this was the last page or a single value response
+    pub fn next_page(&self) -> Option<u32> {
+        self.values.next_page
+    }
+}
+
+impl ValueState {
+    fn token<const N: usize>(
+        &mut self,
+        token: Token<'_>,
+        path: &[Level],
+        points: &mut Vec<DataPoint, N>,
+    ) -> Result<(), FingridError> {
+        match token {
+            Token::Open(Container::Object) if is_value_object(path) => {
+                self.dataset = None;
+                self.start = None;
+                self.end = None;
+                self.value = None;
+                Ok(())
+            }
+            Token::Close(Container::Object) if is_value_object(path) => {
+                self.value_object_complete(path, points)
+            }
+            Token::String(s) | Token::Scalar(s) => self.value(s, path),
+            _ => Ok(()),
+        }
+    }
+
+    fn value(&mut self, token: &str, path: &[Level]) -> Result<(), FingridError> {
+        if let [pagination, next_page] = path {
+            if pagination.key() == "pagination" && next_page.key() == "nextPage" {
+                self.next_page = match token {
+                    "null" => None,
+                    page => Some(page.parse().map_err(|_| FingridError::InvalidNumber)?),
+                };
+                return Ok(());
+            }
+        }
+
+        let Some(level) = path.last() else {
+            return Ok(());
+        };
+        if !is_value_object(path) || level.container != Container::Object {
+            return Ok(());
+        }
+        match level.key() {
+            "datasetId" => {
+                self.dataset = Some(token.parse().map_err(|_| FingridError::InvalidNumber)?)
+            }
+            "startTime" => self.start = Some(parse_time(token)?),
+            "endTime" => self.end = Some(parse_time(token)?),
+            "value" => {
+                self.value = match token {
+                    "null" => Some(None),
+                    _ => Some(Some(
+                        parse_decimal(token, VALUE_SCALE).ok_or(FingridError::InvalidNumber)?,
+                    )),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn value_object_complete<const N: usize>(
+        &mut self,
+        path: &[Level],
+        points: &mut Vec<DataPoint, N>,
+    ) -> Result<(), FingridError> {
+        // Root object of a page or an error response
+        if path.len() == 1 && self.start.is_none() {
+            return Ok(());
+        }
+        let (Some(dataset), Some(start), Some(end), Some(value)) = (
+            self.dataset.take(),
+            self.start.take(),
+            self.end.take(),
+            self.value.take(),
+        ) else {
+            return Err(FingridError::Malformed);
+        };
+        let Some(value) = value else {
+            return Ok(());
+        };
+        points
+            .push(DataPoint {
+                dataset,
+                start,
+                end,
+                value,
+            })
+            .map_err(|_| FingridError::Full)?;
+        self.points += 1;
+        Ok(())
+    }
+}
+
+/// Objects in the `data` array of a page and a single object at the root are value objects
+fn is_value_object(path: &[Level]) -> bool {
+    match path {
+        [_] => true,
+        [root, data, _] => root.key() == "data" && data.container == Container::Array,
+        _ => false,
+    }
+}
+
+fn parse_time(s: &str) -> Result<Timestamp, FingridError> {
+    UtcDateTime::parse_iso8601(s)
+        .map(|dt| dt.timestamp())
+        .ok_or(FingridError::InvalidTime)
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::{dataset, DataPoint, FingridError, FingridParser};
+    use crate::time::UtcDateTime;
+
+    const PAGE: &str = include_str!("../tests/fixtures/fingrid/consumption_page1.json");
+    const LATEST: &str = include_str!("../tests/fixtures/fingrid/net_import_latest.json");
+    const UNAUTHORIZED: &str = include_str!("../tests/fixtures/fingrid/unauthorized.json");
+
+    fn parse(
+        response: &str,
+        chunk_size: usize,
+    ) -> (Vec<DataPoint, 16>, FingridParser, Result<(), FingridError>) {
+        let mut points = Vec::new();
+        let mut parser = FingridParser::new();
+        for chunk in response.as_bytes().chunks(chunk_size) {
+            if let Err(e) = parser.feed(chunk, &mut points) {
+                return (points, parser, Err(e));
+            }
+        }
+        let result = parser.finish();
+        (points, parser, result)
+    }
+
+    fn time(s: &str) -> i64 {
+        UtcDateTime::parse_iso8601(s).unwrap().timestamp()
+    }
+
+    #[test]
+    fn page_of_values() {
+        let (points, parser, result) = parse(PAGE, 4096);
+        result.unwrap();
+        assert_eq!(parser.next_page(), Some(2));
+
+        // Fifth value is null and skipped
+        assert_eq!(points.len(), 9);
+        assert_eq!(
+            points[0],
+            DataPoint {
+                dataset: dataset::CONSUMPTION,
+                start: time("2024-10-01T00:00Z"),
+                end: time("2024-10-01T00:03Z"),
+                value: 8_456_300,
+            }
+        );
+        assert_eq!(points[3].value, 8_502_000);
+        assert_eq!(points[4].start, time("2024-10-01T00:15Z"));
+        assert_eq!(points[8].value, 8_560_700);
+    }
+
+    #[test]
+    fn latest_value() {
+        let (points, parser, result) = parse(LATEST, 4096);
+        result.unwrap();
+        assert_eq!(parser.next_page(), None);
+        assert_eq!(
+            points.as_slice(),
+            [DataPoint {
+                dataset: dataset::NET_IMPORT,
+                start: time("2024-10-01T09:57Z"),
+                end: time("2024-10-01T10:00Z"),
+                value: -1_234_567,
+            }]
+        );
+    }
+
+    #[test]
+    fn chunk_boundaries_do_not_matter() {
+        let (whole, _, _) = parse(PAGE, 4096);
+        for chunk_size in [1, 2, 5, 13] {
+            let (chunked, _, result) = parse(PAGE, chunk_size);
+            result.unwrap();
+            assert_eq!(chunked, whole);
+        }
+    }
+
+    #[test]
+    fn error_response_is_no_data() {
+        let (points, _, result) = parse(UNAUTHORIZED, 4096);
+        assert_eq!(result, Err(FingridError::NoData));
+        assert!(points.is_empty());
+    }
+
+    #[test]
+    fn invalid_responses() {
+        let (_, _, result) = parse(&PAGE[..PAGE.len() / 2], 4096);
+        assert_eq!(result, Err(FingridError::Malformed));
+
+        let (_, _, result) = parse(&PAGE.replace("]", "}"), 4096);
+        assert_eq!(result, Err(FingridError::Malformed));
+
+        let (_, _, result) = parse(&LATEST.replace("-1234.567", "\"x\""), 4096);
+        assert_eq!(result, Err(FingridError::InvalidNumber));
+
+        let (_, _, result) = parse(&PAGE.replace("\"datasetId\":193,", ""), 4096);
+        assert_eq!(result, Err(FingridError::Malformed));
+
+        let mut points = Vec::<DataPoint, 4>::new();
+        let mut parser = FingridParser::new();
+        assert_eq!(
+            parser.feed(PAGE.as_bytes(), &mut points),
+            Err(FingridError::Full)
+        );
+    }
+}
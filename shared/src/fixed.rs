@@ -0,0 +1,87 @@
+//! Fixed point decimal numbers, used instead of floats for values parsed from api responses.
+
+/// Parses decimal number such as `-12.5` to an integer in 1/`scale` units.
+///
+/// `scale` must be a power of ten. Decimals beyond the scale are truncated.
+/// Returns [None] if `s` is not a plain decimal number or it does not fit to [i32].
+pub fn parse_decimal(s: &str, scale: i32) -> Option<i32> {
+    let (negative, s) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (whole, fraction) = s.split_once('.').unwrap_or((s, ""));
+    if whole.is_empty()
+        || !whole.bytes().all(|b| b.is_ascii_digit())
+        || !fraction.bytes().all(|b| b.is_ascii_digit())
+    {
+        return None;
+    }
+
+    let whole: i32 = whole.parse().ok()?;
+    let mut value = whole.checked_mul(scale)?;
+    let mut digit_scale = scale;
+    for digit in fraction.bytes() {
+        digit_scale /= 10;
+        if digit_scale == 0 {
+            break;
+        }
+        value = value.checked_add((digit - b'0') as i32 * digit_scale)?;
+    }
+    Some(if negative { -value } else { value })
+}
+
+/// Formats an integer in 1/`scale` units as a decimal number with every decimal of the scale,
+/// for example `Decimal::new(-1205, 100)` as `-12.05`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Decimal {
+    value: i32,
+    scale: i32,
+}
+
+impl Decimal {
+    /// `scale` must be a positive power of ten
+    pub const fn new(value: i32, scale: i32) -> Self {
+        Self { value, scale }
+    }
+}
+
+impl core::fmt::Display for Decimal {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let sign = if self.value < 0 { "-" } else { "" };
+        let value = self.value.unsigned_abs();
+        let scale = self.scale.unsigned_abs();
+        let whole = value / scale;
+        if scale == 1 {
+            return write!(f, "{sign}{whole}");
+        }
+        let decimals = scale.ilog10() as usize;
+        write!(f, "{sign}{whole}.{:0decimals$}", value % scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::{parse_decimal, Decimal};
+
+    #[test]
+    fn scales() {
+        assert_eq!(parse_decimal("8456.3", 1000), Some(8_456_300));
+        assert_eq!(parse_decimal("-0.125", 1000), Some(-125));
+        assert_eq!(parse_decimal("7", 1), Some(7));
+        assert_eq!(parse_decimal("7.9", 1), Some(7));
+        assert_eq!(parse_decimal("3000000", 1000), None);
+        assert_eq!(parse_decimal("+1", 100), None);
+        assert_eq!(parse_decimal("1e3", 100), None);
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(Decimal::new(1205, 100).to_string(), "12.05");
+        assert_eq!(Decimal::new(-1205, 100).to_string(), "-12.05");
+        assert_eq!(Decimal::new(-5, 1000).to_string(), "-0.005");
+        assert_eq!(Decimal::new(0, 10).to_string(), "0.0");
+        assert_eq!(Decimal::new(i32::MIN, 1).to_string(), "-2147483648");
+    }
+}
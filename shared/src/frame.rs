@@ -0,0 +1,203 @@
+/// Splits incoming byte stream to COBS frames terminated by [corncobs::ZERO].
+///
+/// Bytes can be fed in arbitrary sized slices, partial frames are kept in an internal buffer
+/// of `N` bytes between calls to [FrameDecoder::feed]. Frames that do not fit to the buffer
+/// are dropped and decoding continues from the next terminator,
+/// so garbage on the line never causes a panic.
+///
+/// ```
+/// # use shared::frame::{FeedResult, FrameDecoder};
+/// let mut decoder = FrameDecoder::<16>::new();
+/// let mut input: &[u8] = &[1, 2, 0, 3];
+///
+/// while !input.is_empty() {
+///     input = match decoder.feed(input) {
+///         FeedResult::Consumed => break,
+///         FeedResult::Frame { frame, remaining } => {
+///             assert_eq!(frame, &[1, 2, 0]);
+///             remaining
+///         }
+///         FeedResult::Overflow { remaining } => remaining,
+///     };
+/// }
+/// ```
+pub struct FrameDecoder<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    overflowed: bool,
+}
+
+/// Result of feeding bytes to [FrameDecoder]
+#[derive(Debug, PartialEq)]
+pub enum FeedResult<'a, 'b> {
+    /// All input was consumed without completing a frame
+    Consumed,
+    /// Frame was completed. Frame includes the terminating zero byte.
+    ///
+    /// `remaining` contains the input that was not consumed yet.
+    Frame {
+        frame: &'a [u8],
+        remaining: &'b [u8],
+    },
+    /// Frame was longer than the buffer and it was dropped.
+    ///
+    /// `remaining` contains the input that was not consumed yet.
+    Overflow { remaining: &'b [u8] },
+}
+
+impl<const N: usize> FrameDecoder<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            overflowed: false,
+        }
+    }
+
+    /// Feeds `input` to the decoder until a frame is completed or the input runs out.
+    ///
+    /// Empty frames (consecutive terminators) are skipped silently.
+    pub fn feed<'a, 'b>(&'a mut self, input: &'b [u8]) -> FeedResult<'a, 'b> {
+        for (i, byte) in input.iter().enumerate() {
+            let remaining = &input[i + 1..];
+
+            if self.overflowed {
+                // Skip rest of the over-long frame
+                if *byte == corncobs::ZERO {
+                    self.overflowed = false;
+                    return FeedResult::Overflow { remaining };
+                }
+                continue;
+            }
+
+            if *byte == corncobs::ZERO && self.len == 0 {
+                continue;
+            }
+
+            if self.len == N {
+                self.len = 0;
+                if *byte == corncobs::ZERO {
+                    return FeedResult::Overflow { remaining };
+                }
+                self.overflowed = true;
+                continue;
+            }
+
+            self.buf[self.len] = *byte;
+            self.len += 1;
+
+            if *byte == corncobs::ZERO {
+                let len = self.len;
+                self.len = 0;
+                return FeedResult::Frame {
+                    frame: &self.buf[..len],
+                    remaining,
+                };
+            }
+        }
+
+        FeedResult::Consumed
+    }
+
+    /// Drops partially received frame
+    pub fn reset(&mut self) {
+        self.len = 0;
+        self.overflowed = false;
+    }
+}
+
+impl<const N: usize> Default for FrameDecoder<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::{FeedResult, FrameDecoder};
+
+    /// Feeds all of `input` and collects frames, overflows are marked with empty vec
+    fn feed_all<const N: usize>(decoder: &mut FrameDecoder<N>, mut input: &[u8]) -> Vec<Vec<u8>> {
+        let mut frames = Vec::new();
+        while !input.is_empty() {
+            input = match decoder.feed(input) {
+                FeedResult::Consumed => break,
+                FeedResult::Frame { frame, remaining } => {
+                    frames.push(frame.to_vec());
+                    remaining
+                }
+                FeedResult::Overflow { remaining } => {
+                    frames.push(Vec::new());
+                    remaining
+                }
+            };
+        }
+        frames
+    }
+
+    #[test]
+    fn multiple_frames_in_one_slice() {
+        let mut decoder = FrameDecoder::<8>::new();
+        let frames = feed_all(&mut decoder, &[1, 2, 0, 3, 4, 5, 0]);
+        assert_eq!(frames, vec![vec![1, 2, 0], vec![3, 4, 5, 0]]);
+    }
+
+    #[test]
+    fn frame_split_over_slices() {
+        let mut decoder = FrameDecoder::<8>::new();
+        assert!(feed_all(&mut decoder, &[1, 2]).is_empty());
+        assert!(feed_all(&mut decoder, &[3]).is_empty());
+        let frames = feed_all(&mut decoder, &[4, 0, 5]);
+        assert_eq!(frames, vec![vec![1, 2, 3, 4, 0]]);
+        let frames = feed_all(&mut decoder, &[0]);
+        assert_eq!(frames, vec![vec![5, 0]]);
+    }
+
+    #[test]
+    fn empty_frames_are_skipped() {
+        let mut decoder = FrameDecoder::<8>::new();
+        let frames = feed_all(&mut decoder, &[0, 0, 1, 0, 0]);
+        assert_eq!(frames, vec![vec![1, 0]]);
+    }
+
+    #[test]
+    fn resynchronises_after_overlong_frame() {
+        let mut decoder = FrameDecoder::<4>::new();
+        let frames = feed_all(&mut decoder, &[9, 9, 9, 9, 9, 9, 9, 0, 1, 2, 0]);
+        assert_eq!(frames, vec![vec![], vec![1, 2, 0]]);
+    }
+
+    #[test]
+    fn frame_exactly_filling_buffer() {
+        let mut decoder = FrameDecoder::<4>::new();
+        let frames = feed_all(&mut decoder, &[1, 2, 3, 0, 1, 2, 3, 4, 0, 5, 0]);
+        assert_eq!(frames, vec![vec![1, 2, 3, 0], vec![], vec![5, 0]]);
+    }
+
+    #[test]
+    fn decodes_real_frames_after_garbage() {
+        use crate::{deserialize_crc_cobs, serialize_crc_cobs, Envelope, Response, RESPONSE_SIZE};
+
+        let mut buf = [0u8; RESPONSE_SIZE];
+        let encoded = serialize_crc_cobs::<Envelope<Response>, RESPONSE_SIZE>(
+            Envelope::new(3, Response::Ok),
+            &mut buf,
+        )
+        .unwrap();
+
+        let mut stream = b"ESP-ROM:esp32c3-api1-20210207\r\n".to_vec();
+        stream.push(0);
+        stream.extend_from_slice(encoded);
+
+        let mut decoder = FrameDecoder::<RESPONSE_SIZE>::new();
+        let frames = feed_all(&mut decoder, &stream);
+        assert_eq!(frames.len(), 2);
+        assert!(deserialize_crc_cobs::<Envelope<Response>, RESPONSE_SIZE>(&frames[0]).is_err());
+        let response =
+            deserialize_crc_cobs::<Envelope<Response>, RESPONSE_SIZE>(&frames[1]).unwrap();
+        assert_eq!(response.id, 3);
+        assert_eq!(response.payload, Response::Ok);
+    }
+}
@@ -0,0 +1,257 @@
+//! State of the Finnish power system published by Fingrid, used to warn the household
+//! when electricity should be saved, for example during winter consumption peaks.
+//!
+//! [SystemState] comes from [dataset::POWER_SYSTEM_STATE] and [ShortageStatus] from
+//! [dataset::SHORTAGE_STATUS]. Both are combined to a [GridAlert], which is shown on the
+//! display and reported to the host when it is not normal.
+
+use serde::{Deserialize, Serialize};
+
+use crate::fingrid::{dataset, DataPoint, VALUE_SCALE};
+
+/// Operating state of the power system, the traffic light of Fingrid
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub enum SystemState {
+    /// Green
+    #[default]
+    Normal,
+    /// Yellow, the power system is endangered and the adequacy of electricity is at risk
+    Endangered,
+    /// Red, serious disturbance such as rotating outages to keep the system in balance
+    Disturbance,
+    /// Black, blackout of a large area
+    Blackout,
+    /// Blue, power system is being restored after a blackout
+    Restoration,
+}
+
+impl SystemState {
+    /// State from a value of [dataset::POWER_SYSTEM_STATE], 1 for green to 5 for blue
+    pub fn from_value(value: i32) -> Option<Self> {
+        match value / VALUE_SCALE {
+            1 => Some(Self::Normal),
+            2 => Some(Self::Endangered),
+            3 => Some(Self::Disturbance),
+            4 => Some(Self::Blackout),
+            5 => Some(Self::Restoration),
+            _ => None,
+        }
+    }
+
+    pub fn level(self) -> AlertLevel {
+        match self {
+            SystemState::Normal => AlertLevel::Normal,
+            SystemState::Endangered | SystemState::Restoration => AlertLevel::Warning,
+            SystemState::Disturbance | SystemState::Blackout => AlertLevel::Critical,
+        }
+    }
+
+    /// Short description for the display, [None] if the state is normal
+    pub fn headline(self) -> Option<&'static str> {
+        match self {
+            SystemState::Normal => None,
+            SystemState::Endangered => Some("Power system endangered"),
+            SystemState::Disturbance => Some("Power system disturbance"),
+            SystemState::Blackout => Some("Blackout"),
+            SystemState::Restoration => Some("Restoring after blackout"),
+        }
+    }
+}
+
+/// Risk of electricity shortage, announced in advance when consumption is expected
+/// to exceed production and imports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub enum ShortageStatus {
+    #[default]
+    Normal,
+    /// Shortage is possible during the next days
+    Possible,
+    /// Risk of shortage is high during the next day
+    HighRisk,
+    /// Consumers are disconnected in turns
+    Shortage,
+}
+
+impl ShortageStatus {
+    /// Status from a value of [dataset::SHORTAGE_STATUS], 0 for normal to 3 for shortage
+    pub fn from_value(value: i32) -> Option<Self> {
+        match value / VALUE_SCALE {
+            0 => Some(Self::Normal),
+            1 => Some(Self::Possible),
+            2 => Some(Self::HighRisk),
+            3 => Some(Self::Shortage),
+            _ => None,
+        }
+    }
+
+    pub fn level(self) -> AlertLevel {
+        match self {
+            ShortageStatus::Normal => AlertLevel::Normal,
+            ShortageStatus::Possible | ShortageStatus::HighRisk => AlertLevel::Warning,
+            ShortageStatus::Shortage => AlertLevel::Critical,
+        }
+    }
+
+    /// Short description for the display, [None] if the status is normal
+    pub fn headline(self) -> Option<&'static str> {
+        match self {
+            ShortageStatus::Normal => None,
+            ShortageStatus::Possible => Some("Electricity shortage possible"),
+            ShortageStatus::HighRisk => Some("High risk of shortage"),
+            ShortageStatus::Shortage => Some("Electricity shortage"),
+        }
+    }
+}
+
+/// How urgently the household should save electricity
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AlertLevel {
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// Latest power system state and shortage status, reported with
+/// [Event::GridAlert](crate::Event::GridAlert)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct GridAlert {
+    pub state: SystemState,
+    pub shortage: ShortageStatus,
+}
+
+impl GridAlert {
+    /// Updates the state or status whose dataset `point` is from.
+    ///
+    /// Points of other datasets and unknown values are ignored.
+    pub fn update(&mut self, point: &DataPoint) {
+        match point.dataset {
+            dataset::POWER_SYSTEM_STATE => {
+                if let Some(state) = SystemState::from_value(point.value) {
+                    self.state = state;
+                }
+            }
+            dataset::SHORTAGE_STATUS => {
+                if let Some(shortage) = ShortageStatus::from_value(point.value) {
+                    self.shortage = shortage;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// More severe of the state and the shortage status
+    pub fn level(&self) -> AlertLevel {
+        self.state.level().max(self.shortage.level())
+    }
+
+    pub fn is_normal(&self) -> bool {
+        self.level() == AlertLevel::Normal
+    }
+
+    /// Descriptions of the state and the status that are not normal, more severe first
+    pub fn headlines(&self) -> impl Iterator<Item = &'static str> {
+        let (first, second) = if self.shortage.level() > self.state.level() {
+            (self.shortage.headline(), self.state.headline())
+        } else {
+            (self.state.headline(), self.shortage.headline())
+        };
+        first.into_iter().chain(second)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use heapless::Vec;
+
+    use super::{AlertLevel, GridAlert, ShortageStatus, SystemState};
+    use crate::fingrid::{dataset, DataPoint, DatasetId, FingridParser, VALUE_SCALE};
+
+    const STATE: &str = include_str!("../tests/fixtures/fingrid/power_system_state_latest.json");
+    const SHORTAGE: &str = include_str!("../tests/fixtures/fingrid/shortage_status_latest.json");
+
+    fn parse(response: &str) -> DataPoint {
+        let mut points = Vec::<DataPoint, 1>::new();
+        let mut parser = FingridParser::new();
+        parser.feed(response.as_bytes(), &mut points).unwrap();
+        parser.finish().unwrap();
+        points[0]
+    }
+
+    fn point(dataset: DatasetId, value: i32) -> DataPoint {
+        DataPoint {
+            dataset,
+            start: 1_733_000_000,
+            end: 1_733_000_180,
+            value: value * VALUE_SCALE,
+        }
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(
+            SystemState::from_value(VALUE_SCALE),
+            Some(SystemState::Normal)
+        );
+        assert_eq!(
+            SystemState::from_value(4 * VALUE_SCALE),
+            Some(SystemState::Blackout)
+        );
+        assert_eq!(SystemState::from_value(0), None);
+        assert_eq!(SystemState::from_value(6 * VALUE_SCALE), None);
+        assert_eq!(ShortageStatus::from_value(0), Some(ShortageStatus::Normal));
+        assert_eq!(
+            ShortageStatus::from_value(2 * VALUE_SCALE),
+            Some(ShortageStatus::HighRisk)
+        );
+        assert_eq!(ShortageStatus::from_value(-VALUE_SCALE), None);
+    }
+
+    #[test]
+    fn alert_from_datasets() {
+        let mut alert = GridAlert::default();
+        assert!(alert.is_normal());
+        assert_eq!(alert.headlines().count(), 0);
+
+        alert.update(&point(dataset::SHORTAGE_STATUS, 1));
+        assert_eq!(alert.level(), AlertLevel::Warning);
+        let headlines: std::vec::Vec<_> = alert.headlines().collect();
+        assert_eq!(headlines, ["Electricity shortage possible"]);
+
+        alert.update(&point(dataset::POWER_SYSTEM_STATE, 3));
+        assert_eq!(alert.level(), AlertLevel::Critical);
+        let headlines: std::vec::Vec<_> = alert.headlines().collect();
+        assert_eq!(
+            headlines,
+            ["Power system disturbance", "Electricity shortage possible"]
+        );
+
+        // Unknown values and other datasets do not change anything
+        alert.update(&point(dataset::POWER_SYSTEM_STATE, 9));
+        alert.update(&point(dataset::CONSUMPTION, 1));
+        assert_eq!(alert.state, SystemState::Disturbance);
+
+        alert.update(&point(dataset::POWER_SYSTEM_STATE, 1));
+        alert.update(&point(dataset::SHORTAGE_STATUS, 0));
+        assert!(alert.is_normal());
+    }
+
+    #[test]
+    fn alert_from_responses() {
+        let mut alert = GridAlert::default();
+
+        let state = parse(STATE);
+        assert_eq!(state.dataset, dataset::POWER_SYSTEM_STATE);
+        alert.update(&state);
+        assert_eq!(alert.state, SystemState::Endangered);
+        assert_eq!(alert.shortage, ShortageStatus::Normal);
+
+        let shortage = parse(SHORTAGE);
+        assert_eq!(shortage.dataset, dataset::SHORTAGE_STATUS);
+        alert.update(&shortage);
+        assert_eq!(alert.state, SystemState::Endangered);
+        assert_eq!(alert.shortage, ShortageStatus::Possible);
+    }
+}
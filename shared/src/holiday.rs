@@ -0,0 +1,142 @@
+//! Finnish public holidays, which are off-peak in time-of-use grid tariffs, see
+//! [time_of_use](crate::time_of_use).
+
+use serde::{Deserialize, Serialize};
+
+use crate::time::Date;
+
+/// Which days are holidays in addition to Sundays
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum HolidayCalendar {
+    /// No holidays
+    None,
+    /// See [is_finnish_holiday]
+    Finland,
+}
+
+impl HolidayCalendar {
+    pub fn is_holiday(self, date: Date) -> bool {
+        match self {
+            HolidayCalendar::None => false,
+            HolidayCalendar::Finland => is_finnish_holiday(date),
+        }
+    }
+}
+
+/// Returns true if `date` is a public holiday in Finland, or Midsummer Eve or Christmas Eve.
+///
+/// The eves are not official holidays but grid operators price them like holidays
+/// because almost everything is closed.
+pub fn is_finnish_holiday(date: Date) -> bool {
+    let Date { year, month, day } = date;
+    let fixed = matches!(
+        (month, day),
+        (1, 1) | (1, 6) | (5, 1) | (12, 6) | (12, 24) | (12, 25) | (12, 26)
+    );
+    let from_easter = date.days() - easter_sunday(year).days();
+    let moving = matches!(from_easter, -2 | 0 | 1 | 39 | 49);
+    // Midsummer Day is the Saturday between 20 and 26 June and All Saints' Day
+    // the Saturday between 31 October and 6 November
+    let saturday = date.weekday() == 5;
+    let midsummer = month == 6
+        && ((saturday && (20..=26).contains(&day))
+            || (date.weekday() == 4 && (19..=25).contains(&day)));
+    let all_saints =
+        saturday && ((month == 10 && day == 31) || (month == 11 && (1..=6).contains(&day)));
+    fixed || moving || midsummer || all_saints
+}
+
+/// Easter Sunday of the Gregorian calendar, from the anonymous Gregorian algorithm
+pub fn easter_sunday(year: i32) -> Date {
+    let a = year % 19;
+    let b = year / 100;
+    let c = year % 100;
+    let d = b / 4;
+    let e = b % 4;
+    let f = (b + 8) / 25;
+    let g = (b - f + 1) / 3;
+    let h = (19 * a + b - d - g + 15) % 30;
+    let i = c / 4;
+    let k = c % 4;
+    let l = (32 + 2 * e + 2 * i - h - k) % 7;
+    let m = (a + 11 * h + 22 * l) / 451;
+    let month = (h + l - 7 * m + 114) / 31;
+    let day = (h + l - 7 * m + 114) % 31 + 1;
+    Date {
+        year,
+        month: month as u8,
+        day: day as u8,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{easter_sunday, is_finnish_holiday, HolidayCalendar};
+    use crate::time::Date;
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn easter_dates() {
+        assert_eq!(easter_sunday(2019), date(2019, 4, 21));
+        assert_eq!(easter_sunday(2024), date(2024, 3, 31));
+        assert_eq!(easter_sunday(2025), date(2025, 4, 20));
+        assert_eq!(easter_sunday(2026), date(2026, 4, 5));
+        assert_eq!(easter_sunday(2038), date(2038, 4, 25));
+        for year in 1900..2100 {
+            let easter = easter_sunday(year);
+            assert_eq!(easter.weekday(), 6);
+            assert!(date(year, 3, 22) <= easter && easter <= date(year, 4, 25));
+        }
+    }
+
+    #[test]
+    fn holidays_of_2025() {
+        let holidays: std::vec::Vec<Date> = (date(2025, 1, 1).days()..date(2026, 1, 1).days())
+            .map(Date::from_days)
+            .filter(|d| is_finnish_holiday(*d))
+            .collect();
+        assert_eq!(
+            holidays,
+            [
+                date(2025, 1, 1),
+                date(2025, 1, 6),
+                // Good Friday, Easter Sunday and Monday
+                date(2025, 4, 18),
+                date(2025, 4, 20),
+                date(2025, 4, 21),
+                date(2025, 5, 1),
+                // Ascension Day and Whitsun
+                date(2025, 5, 29),
+                date(2025, 6, 8),
+                // Midsummer Eve and Day
+                date(2025, 6, 20),
+                date(2025, 6, 21),
+                date(2025, 11, 1),
+                date(2025, 12, 6),
+                date(2025, 12, 24),
+                date(2025, 12, 25),
+                date(2025, 12, 26),
+            ]
+        );
+    }
+
+    #[test]
+    fn midsummer_and_all_saints_edges() {
+        // Midsummer Eve as late as it gets
+        assert!(is_finnish_holiday(date(2027, 6, 25)));
+        assert!(is_finnish_holiday(date(2027, 6, 26)));
+        assert!(!is_finnish_holiday(date(2027, 6, 18)));
+        // and as early
+        assert!(is_finnish_holiday(date(2026, 6, 19)));
+        assert!(is_finnish_holiday(date(2026, 6, 20)));
+        assert!(!is_finnish_holiday(date(2026, 6, 26)));
+
+        assert!(is_finnish_holiday(date(2026, 10, 31)));
+        assert!(!is_finnish_holiday(date(2026, 11, 7)));
+        assert!(!HolidayCalendar::None.is_holiday(date(2025, 12, 25)));
+    }
+}
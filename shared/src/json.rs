@@ -0,0 +1,266 @@
+//! Streaming JSON tokenizer for api responses.
+//!
+//! Input is fed one byte at a time and [Token]s are passed to a callback together with the
+//! path to them, so responses can be parsed as they arrive without storing them.
+//! Strings and numbers longer than [TOKEN_LEN] are truncated, only short values are expected.
+
+use heapless::{String, Vec};
+
+/// Longest key or value that is kept, longer ones are truncated
+pub const TOKEN_LEN: usize = 32;
+
+/// Deepest nesting of objects and arrays
+pub const MAX_DEPTH: usize = 8;
+
+/// Input is not valid JSON or it is nested deeper than [MAX_DEPTH]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    Object,
+    Array,
+}
+
+/// Object or array that contains the current token
+#[derive(Debug)]
+pub struct Level {
+    pub container: Container,
+    key: String<TOKEN_LEN>,
+}
+
+impl Level {
+    /// Latest key in an object, empty for arrays
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Token<'a> {
+    /// Object or array was opened, it is the last level of the path
+    Open(Container),
+    /// Object or array is about to be closed, it is still the last level of the path
+    Close(Container),
+    /// String value without quotes. Escapes are kept as they are.
+    String(&'a str),
+    /// Number, `true`, `false` or `null`
+    Scalar(&'a str),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum State {
+    /// Between tokens
+    Structure,
+    String {
+        escaped: bool,
+    },
+    Scalar,
+}
+
+#[derive(Debug)]
+pub struct JsonLexer {
+    state: State,
+    token: String<TOKEN_LEN>,
+    /// Next string is a key
+    expect_key: bool,
+    stack: Vec<Level, MAX_DEPTH>,
+    /// Root value has been closed
+    done: bool,
+}
+
+impl Default for JsonLexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl JsonLexer {
+    pub const fn new() -> Self {
+        Self {
+            state: State::Structure,
+            token: String::new(),
+            expect_key: false,
+            stack: Vec::new(),
+            done: false,
+        }
+    }
+
+    /// True after the root object or array has been closed
+    pub fn is_done(&self) -> bool {
+        self.done
+    }
+
+    /// Feeds next byte of the input, `on_token` is called for each token it completes
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the input is not valid JSON
+    /// or `on_token` returns an error.
+    pub fn feed<E: From<JsonError>>(
+        &mut self,
+        byte: u8,
+        on_token: &mut impl FnMut(Token<'_>, &[Level]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        match self.state {
+            State::String { escaped } => {
+                if byte == b'"' && !escaped {
+                    self.state = State::Structure;
+                    return self.string_complete(on_token);
+                }
+                self.state = State::String {
+                    escaped: byte == b'\\' && !escaped,
+                };
+                let _ = self.token.push(byte as char);
+                return Ok(());
+            }
+            State::Scalar => {
+                if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'+' | b'.') {
+                    let _ = self.token.push(byte as char);
+                    return Ok(());
+                }
+                self.state = State::Structure;
+                on_token(Token::Scalar(&self.token), &self.stack)?;
+            }
+            State::Structure => {}
+        }
+
+        if byte.is_ascii_whitespace() {
+            return Ok(());
+        }
+        if self.done {
+            return Err(JsonError.into());
+        }
+
+        match byte {
+            b'{' => self.open(Container::Object, on_token),
+            b'[' => self.open(Container::Array, on_token),
+            b'}' => self.close(Container::Object, on_token),
+            b']' => self.close(Container::Array, on_token),
+            b',' => {
+                self.expect_key = self.top() == Some(Container::Object);
+                Ok(())
+            }
+            b':' => Ok(()),
+            b'"' => {
+                self.token.clear();
+                self.state = State::String { escaped: false };
+                Ok(())
+            }
+            b if b.is_ascii_alphanumeric() || b == b'-' => {
+                self.token.clear();
+                let _ = self.token.push(b as char);
+                self.state = State::Scalar;
+                Ok(())
+            }
+            _ => Err(JsonError.into()),
+        }
+    }
+
+    fn top(&self) -> Option<Container> {
+        self.stack.last().map(|level| level.container)
+    }
+
+    fn open<E: From<JsonError>>(
+        &mut self,
+        container: Container,
+        on_token: &mut impl FnMut(Token<'_>, &[Level]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        self.stack
+            .push(Level {
+                container,
+                key: String::new(),
+            })
+            .map_err(|_| JsonError)?;
+        self.expect_key = container == Container::Object;
+        on_token(Token::Open(container), &self.stack)
+    }
+
+    fn close<E: From<JsonError>>(
+        &mut self,
+        container: Container,
+        on_token: &mut impl FnMut(Token<'_>, &[Level]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if self.top() != Some(container) {
+            return Err(JsonError.into());
+        }
+        on_token(Token::Close(container), &self.stack)?;
+        self.stack.pop();
+        self.expect_key = false;
+        self.done = self.stack.is_empty();
+        Ok(())
+    }
+
+    fn string_complete<E: From<JsonError>>(
+        &mut self,
+        on_token: &mut impl FnMut(Token<'_>, &[Level]) -> Result<(), E>,
+    ) -> Result<(), E> {
+        if !self.expect_key {
+            return on_token(Token::String(&self.token), &self.stack);
+        }
+        self.expect_key = false;
+        if let Some(level) = self.stack.last_mut() {
+            level.key.clone_from(&self.token);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::{String, ToString};
+    use std::vec::Vec;
+
+    use super::{Container, JsonError, JsonLexer, Token};
+
+    /// Tokens formatted with the key of the enclosing object
+    fn tokens(input: &str) -> Result<Vec<String>, JsonError> {
+        let mut lexer = JsonLexer::new();
+        let mut tokens = Vec::new();
+        for byte in input.bytes() {
+            lexer.feed(byte, &mut |token, path| {
+                let key = path.last().map_or("", |level| level.key());
+                tokens.push(match token {
+                    Token::Open(Container::Object) => "{".to_string(),
+                    Token::Open(Container::Array) => "[".to_string(),
+                    Token::Close(Container::Object) => "}".to_string(),
+                    Token::Close(Container::Array) => "]".to_string(),
+                    Token::String(s) => std::format!("{key}=\"{s}\""),
+                    Token::Scalar(s) => std::format!("{key}={s}"),
+                });
+                Ok::<(), JsonError>(())
+            })?;
+        }
+        assert!(lexer.is_done());
+        Ok(tokens)
+    }
+
+    #[test]
+    fn nested_values() {
+        let input = r#"{"a": [1, -2.5e3, "x\"y"], "b": {"c": null, "d": true}}"#;
+        assert_eq!(
+            tokens(input).unwrap(),
+            [
+                "{",
+                "[",
+                "=1",
+                "=-2.5e3",
+                "=\"x\\\"y\"",
+                "]",
+                "{",
+                "c=null",
+                "d=true",
+                "}",
+                "}"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert_eq!(tokens(r#"{"a": 1]"#), Err(JsonError));
+        assert_eq!(tokens(r#"{"a": 1} 2"#), Err(JsonError));
+        assert_eq!(tokens(r#"{"a": @}"#), Err(JsonError));
+        assert_eq!(tokens(&"[".repeat(9)), Err(JsonError));
+    }
+}
@@ -1,5 +1,26 @@
 #![cfg_attr(not(test), no_std)]
-#![feature(type_alias_impl_trait)]
+
+pub mod emission;
+pub mod entsoe;
+pub mod fingrid;
+pub mod fixed;
+pub mod frame;
+pub mod grid;
+pub mod holiday;
+pub mod json;
+pub mod map_key;
+pub mod mirror;
+#[cfg(test)]
+mod mock_flash;
+pub mod planner;
+pub mod price;
+pub mod price_store;
+pub mod relay;
+pub mod statistics;
+pub mod tariff;
+pub mod time;
+pub mod time_of_use;
+pub mod transfer;
 
 use core::{mem::size_of, str::FromStr};
 
@@ -8,20 +29,103 @@ use embedded_graphics::pixelcolor::Rgb565;
 use heapless::String;
 use mipidsi::dcs::DcsCommand;
 use serde::{Deserialize, Serialize};
+use strum::EnumCount;
+use transfer::{TransferAck, TransferError, TransferFrame};
+
+pub const MESSAGE_SIZE: usize = max_encoded_len(size_of::<Envelope<Message>>() + size_of::<u32>());
+/// Size of the largest encoded [DeviceFrame], which wraps every [Response] sent by the device.
+///
+/// Encoded [Response::Statistics] is larger than in memory, because integers take up to one
+/// byte more when encoded. Frame and response variants and the request id add 5 bytes to it.
+pub const RESPONSE_SIZE: usize = max_encoded_len(
+    const_max(size_of::<DeviceFrame>(), 5 + statistics::MAX_ENCODED_SIZE) + size_of::<u32>(),
+);
+
+const fn const_max(a: usize, b: usize) -> usize {
+    if a > b {
+        a
+    } else {
+        b
+    }
+}
+
+/// Identifies a single request sent by the host
+pub type RequestId = u16;
+
+/// Every frame sent over serial is wrapped in an envelope.
+///
+/// Host picks `id` for each [Message] it sends and device echoes the same `id`
+/// in the [Response] to that message, so host can match replies to requests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Envelope<T> {
+    pub id: RequestId,
+    pub payload: T,
+}
 
-pub const MESSAGE_SIZE: usize = max_encoded_len(size_of::<Message>() + size_of::<u32>());
-pub const RESPONSE_SIZE: usize = max_encoded_len(size_of::<Response>() + size_of::<u32>());
+impl<T> Envelope<T> {
+    pub fn new(id: RequestId, payload: T) -> Self {
+        Self { id, payload }
+    }
+
+    /// Creates envelope for reply to this one, keeping the same id
+    pub fn reply<R>(&self, payload: R) -> Envelope<R> {
+        Envelope {
+            id: self.id,
+            payload,
+        }
+    }
+}
 
-#[derive(Debug, Serialize, Deserialize, strum_macros::VariantNames, strum_macros::EnumCount)]
+#[derive(
+    Debug,
+    Clone,
+    Serialize,
+    Deserialize,
+    strum_macros::VariantNames,
+    strum_macros::EnumCount,
+    strum_macros::IntoStaticStr,
+)]
 #[repr(C)]
 pub enum Message {
+    /// Must be the first message sent after opening the connection.
+    /// Carries protocol version of the host.
+    ///
+    /// Keep this as the first variant so that it is decoded the same way by every protocol version.
+    Hello(u16),
     Wifi(WifiInfo),
+    /// Sent to the Fingrid api by the device. The device cannot verify server certificates,
+    /// so the key can leak to anyone intercepting its traffic.
     FingridApiKey(String<64>),
+    /// Security token sent to the ENTSO-E api by the device. The device cannot verify
+    /// server certificates, so the token can leak to anyone intercepting its traffic.
     EntsoeApiKey(String<64>),
     Display(DisplayMessage),
+    /// Part of a payload too large for a single message, see [transfer]
+    Transfer(TransferFrame),
+    /// Fingrid datasets fetched by the device, see [fingrid::dataset] for common ones
+    FingridDatasets(heapless::Vec<fingrid::DatasetId, { fingrid::MAX_DATASETS }>),
+    /// Sources of day-ahead prices in the order they are tried, first one is the active source
+    /// and the rest are fallbacks
+    PriceSources(heapless::Vec<price::PriceSource, { price::PriceSource::COUNT }>),
+    /// Current time as seconds since the Unix epoch, device has no clock of its own.
+    /// See [validate_time] for the accepted range.
+    SetTime(i64),
+    /// Market area whose prices the device fetches and shows
+    SetBiddingZone(price::BiddingZone),
+    /// Fees and taxes used to calculate consumer prices, see [tariff]
+    SetTariff(tariff::Tariff),
+    /// Plans when to run each appliance with the latest day-ahead prices,
+    /// answered with [Response::Plans]
+    PlanWindow(heapless::Vec<planner::PlanRequest, { planner::MAX_APPLIANCES }>),
+    /// Replaces the rules that switch the relays, see [relay]
+    SetRelayRules(relay::RelayRules),
+    /// Statistics of the current day compared to the recent days, answered with
+    /// [Response::Statistics]
+    GetStatistics,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub struct WifiInfo {
     ssid: String<64>,
@@ -39,35 +143,412 @@ impl WifiInfo {
     pub fn get_password(&self) -> &str {
         &self.password
     }
+
+    /// Checks that the credentials can be used to connect to a network.
+    ///
+    /// Password can be empty for open networks, otherwise WPA2 requires at least 8 characters.
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.ssid.is_empty() {
+            return Err(ValidationError::Empty);
+        }
+        if !self.password.is_empty() && self.password.len() < Self::MIN_PASSWORD_LEN {
+            return Err(ValidationError::TooShort(Self::MIN_PASSWORD_LEN as u8));
+        }
+        Ok(())
+    }
+
+    const MIN_PASSWORD_LEN: usize = 8;
+}
+
+/// Latest time accepted by [validate_time], 2100-01-01T00:00Z
+pub const MAX_TIME: price::Timestamp = 4_102_444_800;
+
+/// Checks that `time` set with [Message::SetTime] is not before `earliest`, such as the
+/// build time of the firmware, or after [MAX_TIME]
+pub fn validate_time(
+    time: price::Timestamp,
+    earliest: price::Timestamp,
+) -> Result<(), ValidationError> {
+    if !(earliest..=MAX_TIME).contains(&time) {
+        return Err(ValidationError::OutOfRange);
+    }
+    Ok(())
+}
+
+/// Checks that api key is not empty and contains only printable ascii characters
+pub fn validate_api_key(key: &str) -> Result<(), ValidationError> {
+    if key.is_empty() {
+        return Err(ValidationError::Empty);
+    }
+    if !key.chars().all(|c| c.is_ascii_graphic()) {
+        return Err(ValidationError::InvalidCharacters);
+    }
+    Ok(())
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[repr(C)]
 pub enum Response {
+    /// Response to [Message::Hello]
+    ///
+    /// Keep this as the first variant so that it is decoded the same way by every protocol version.
+    Hello(DeviceInfo),
     Ok,
+    Error(ResponseError),
+    /// Response to [Message::Transfer]
+    Transfer(TransferAck),
+    /// Response to [Message::PlanWindow] in the same order as the requests,
+    /// [None] if there were not enough prices before the deadline
+    Plans(heapless::Vec<Option<planner::Plan>, { planner::MAX_APPLIANCES }>),
+    /// Response to [Message::GetStatistics]
+    Statistics(statistics::Statistics),
+}
+
+/// Version of the serial protocol, must be increased on every change to [Message] or [Response]
+pub const PROTOCOL_VERSION: u16 = 15;
+
+/// Everything device sends to the host is wrapped in this
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum DeviceFrame {
+    /// Response to a [Message] sent by the host.
+    ///
+    /// Keep this as the first variant so that [Response::Hello] is decoded the same way
+    /// by every protocol version.
+    Response(Envelope<Response>),
+    /// Sent by the device on its own when something happens
+    Event(Event),
+    /// Log record from the firmware
+    Log(LogRecord),
+}
+
+/// Log record forwarded from the firmware to the host
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct LogRecord {
+    pub level: LogLevel,
+    /// Milliseconds since the device booted
+    pub uptime_ms: u64,
+    /// Module that emitted the record, truncated to fit
+    pub target: String<32>,
+    /// Formatted message, truncated to fit
+    pub message: String<96>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[repr(C)]
+pub enum LogLevel {
     Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+/// Unsolicited notifications from the device
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum Event {
+    WifiStateChanged(WifiState),
+    /// Device got IPv4 address
+    IpAcquired([u8; 4]),
+    /// New price data was fetched, value is the number of price points
+    PriceDataFetched(u16),
+    Error(DeviceError),
+    StorageWarning(StorageFailure),
+    /// Summary of the prices fetched after [Event::PriceDataFetched]
+    PriceSummary(tariff::PriceSummary),
+    /// Relay was switched by the [relay] rules
+    RelaySwitched {
+        relay: u8,
+        on: bool,
+    },
+    /// Power system state or shortage status changed, see [grid]
+    GridAlert(grid::GridAlert),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum WifiState {
+    Disconnected,
+    Connecting,
+    Connected,
+}
+
+/// Errors device reports with [Event::Error]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum DeviceError {
+    /// Frame received from the host could not be decoded
+    DroppedFrame(ProtocolError),
+    /// Connecting to the wifi network failed
+    WifiConnectFailed,
+    /// Fetching data from a remote api failed
+    FetchFailed,
+}
+
+impl core::fmt::Display for DeviceError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            DeviceError::DroppedFrame(e) => write!(f, "dropped frame from host : {e:?}"),
+            DeviceError::WifiConnectFailed => write!(f, "wifi connection failed"),
+            DeviceError::FetchFailed => write!(f, "fetching data failed"),
+        }
+    }
+}
+
+/// Information device sends in response to [Message::Hello]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct DeviceInfo {
+    pub protocol_version: u16,
+    pub firmware_version: String<16>,
+    /// Short git commit hash the firmware was built from
+    pub build_hash: String<16>,
+    pub capabilities: Capabilities,
+}
+
+impl DeviceInfo {
+    /// Returns true if the device speaks the same protocol version as this build
+    pub fn is_compatible(&self) -> bool {
+        self.protocol_version == PROTOCOL_VERSION
+    }
+}
+
+/// Bitmap of features compiled in to the firmware
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Capabilities(pub u32);
+
+impl Capabilities {
+    /// Status text can be shown on the display
+    pub const DISPLAY_STATUS: Self = Self(1 << 0);
+    /// Day-ahead prices can be fetched from ENTSO-E
+    pub const ENTSOE_PRICES: Self = Self(1 << 1);
+    /// Data can be fetched from Fingrid open data
+    pub const FINGRID_DATA: Self = Self(1 << 2);
+    /// Prices can be drawn on the display
+    pub const DISPLAY_PRICES: Self = Self(1 << 3);
+    /// Payloads larger than a single message can be sent with [Message::Transfer]
+    pub const CHUNKED_TRANSFER: Self = Self(1 << 4);
+    /// Firmware logs are sent to the host with [DeviceFrame::Log]
+    pub const LOG_FORWARDING: Self = Self(1 << 5);
+    /// Sources of day-ahead prices, including a mirror, can be set with [Message::PriceSources]
+    pub const PRICE_SOURCES: Self = Self(1 << 6);
+    /// Consumer prices can be calculated with [Message::SetTariff]
+    pub const TARIFF: Self = Self(1 << 7);
+    /// Appliance runs can be planned with [Message::PlanWindow]
+    pub const PLANNER: Self = Self(1 << 8);
+    /// Relays can be switched by the rules set with [Message::SetRelayRules]
+    pub const RELAYS: Self = Self(1 << 9);
+    /// Price history is kept and answered to [Message::GetStatistics]
+    pub const STATISTICS: Self = Self(1 << 10);
+    /// Power system state is watched and reported with [Event::GridAlert]
+    pub const GRID_ALERTS: Self = Self(1 << 11);
+    /// Emission factors of electricity are fetched and drawn on [Page::Emissions]
+    pub const EMISSIONS: Self = Self(1 << 12);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+/// Reason why device could not handle a [Message]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum ResponseError {
+    /// Reading or writing non-volatile storage failed
+    Storage(StorageFailure),
+    /// Content of the message was not accepted
+    Validation(ValidationError),
+    /// Device does not support the message
+    NotSupported,
+    /// Chunked transfer frame was rejected
+    Transfer(TransferError),
+}
+
+/// Non-volatile storage errors reported by the device
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum StorageFailure {
+    /// An error in the storage (flash)
+    Storage,
+    /// Item can not be stored because storage is full
+    FullStorage,
+    /// Memory is likely corrupted, you may want to erase memory to recover
+    Corrupted,
+    /// Provided buffer was too big to be used
+    BufferTooBig,
+    /// Provided buffer was too small. Value is the size needed
+    BufferTooSmall(u32),
+    /// Either key or value serialization error
+    SerializationError,
+    /// Item is too big to fit even to empty flash
+    ItemTooBig,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum ValidationError {
+    /// Required value was empty
+    Empty,
+    /// Value was shorter than the minimum length
+    TooShort(u8),
+    /// Value contained characters that are not allowed
+    InvalidCharacters,
+    /// Number was outside the allowed range
+    OutOfRange,
+}
+
+impl core::fmt::Display for ResponseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ResponseError::Storage(e) => write!(f, "Storage error : {e}"),
+            ResponseError::Validation(e) => write!(f, "Invalid value : {e}"),
+            ResponseError::NotSupported => write!(f, "Not supported by the device"),
+            ResponseError::Transfer(e) => write!(f, "Transfer failed : {e}"),
+        }
+    }
+}
+
+impl core::fmt::Display for StorageFailure {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StorageFailure::Storage => write!(f, "flash access failed"),
+            StorageFailure::FullStorage => write!(f, "storage is full"),
+            StorageFailure::Corrupted => write!(f, "storage is corrupted"),
+            StorageFailure::BufferTooBig => write!(f, "buffer too big"),
+            StorageFailure::BufferTooSmall(size) => {
+                write!(f, "buffer too small, {size} bytes needed")
+            }
+            StorageFailure::SerializationError => write!(f, "serialization failed"),
+            StorageFailure::ItemTooBig => write!(f, "item too big"),
+        }
+    }
+}
+
+impl core::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            ValidationError::Empty => write!(f, "value can not be empty"),
+            ValidationError::TooShort(min) => write!(f, "must be at least {min} characters"),
+            ValidationError::InvalidCharacters => write!(f, "contains invalid characters"),
+            ValidationError::OutOfRange => write!(f, "out of range"),
+        }
+    }
+}
+
+impl From<StorageFailure> for ResponseError {
+    fn from(value: StorageFailure) -> Self {
+        Self::Storage(value)
+    }
+}
+
+impl From<TransferError> for ResponseError {
+    fn from(value: TransferError) -> Self {
+        Self::Transfer(value)
+    }
+}
+
+impl From<ValidationError> for ResponseError {
+    fn from(value: ValidationError) -> Self {
+        Self::Validation(value)
+    }
 }
 
 pub const CKSUM: crc::Crc<u32> = crc::Crc::<u32>::new(&crc::CRC_32_CKSUM);
 
-/// Panics on all errors? Should return result?
-pub fn serialize_crc_cobs<T: Serialize, const N: usize>(item: T, out_buf: &mut [u8]) -> &mut [u8] {
+/// Errors that can happen while encoding or decoding a frame sent over serial.
+///
+/// A frame failing to decode should never be fatal, receiver is expected to drop it and continue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProtocolError {
+    /// Item or frame does not fit to the provided buffer
+    BufferOverflow,
+    /// Frame is not valid COBS, likely truncated or corrupted on the line
+    CobsDecode,
+    /// Frame decoded fine but its checksum does not match the content
+    CrcMismatch,
+    /// Frame contained an unknown enum variant, most likely sent by incompatible version
+    UnknownVariant,
+    /// Frame content could not be deserialized for some other reason
+    Malformed,
+}
+
+impl From<postcard::Error> for ProtocolError {
+    fn from(value: postcard::Error) -> Self {
+        match value {
+            postcard::Error::SerializeBufferFull => Self::BufferOverflow,
+            postcard::Error::DeserializeBadCrc => Self::CrcMismatch,
+            // serde derived enums report out of range variant index as a custom error
+            postcard::Error::DeserializeBadEnum | postcard::Error::SerdeDeCustom => {
+                Self::UnknownVariant
+            }
+            _ => Self::Malformed,
+        }
+    }
+}
+
+impl From<corncobs::CobsError> for ProtocolError {
+    fn from(_: corncobs::CobsError) -> Self {
+        Self::CobsDecode
+    }
+}
+
+/// Serializes `item` with crc32 checksum and encodes it with COBS to `out_buf`.
+///
+/// `N` is the size of the intermediate buffer used for serialization.
+///
+/// # Errors
+///
+/// This function will return [ProtocolError::BufferOverflow] if `item` does not fit to
+/// the intermediate buffer or if the encoded frame does not fit to `out_buf`.
+pub fn serialize_crc_cobs<T: Serialize, const N: usize>(
+    item: T,
+    out_buf: &mut [u8],
+) -> Result<&mut [u8], ProtocolError> {
     let mut buf = [0u8; N];
 
-    let buf = postcard::to_slice_crc32(&item, &mut buf, CKSUM.digest()).unwrap();
+    let buf = postcard::to_slice_crc32(&item, &mut buf, CKSUM.digest())?;
+    if out_buf.len() < max_encoded_len(buf.len()) {
+        return Err(ProtocolError::BufferOverflow);
+    }
     let bytes_used = corncobs::encode_buf(buf, out_buf);
 
-    &mut out_buf[0..bytes_used]
+    Ok(&mut out_buf[0..bytes_used])
 }
 
-pub fn deserialize_crc_cobs<T: for<'a> Deserialize<'a>>(in_buf: &[u8]) -> T {
-    let mut decoded_buf = [0u8; MESSAGE_SIZE];
+/// Decodes COBS frame from `in_buf` and deserializes it while checking the crc32 checksum.
+///
+/// `N` is the size of the intermediate buffer used for decoding,
+/// usually [MESSAGE_SIZE] or [RESPONSE_SIZE].
+///
+/// # Errors
+///
+/// This function will return an error if the frame is too big, is not valid COBS,
+/// has wrong checksum or does not contain a valid `T`.
+pub fn deserialize_crc_cobs<T: for<'a> Deserialize<'a>, const N: usize>(
+    in_buf: &[u8],
+) -> Result<T, ProtocolError> {
+    let mut decoded_buf = [0u8; N];
 
-    let bytes_used = corncobs::decode_buf(in_buf, &mut decoded_buf).unwrap();
-    let decoded_buf =
-        postcard::from_bytes_crc32(&decoded_buf[0..bytes_used], CKSUM.digest()).unwrap();
+    // Decoded frame is never longer than the encoded one
+    if in_buf.len() > N {
+        return Err(ProtocolError::BufferOverflow);
+    }
 
-    decoded_buf
+    let bytes_used = corncobs::decode_buf(in_buf, &mut decoded_buf)?;
+    let item = postcard::from_bytes_crc32(&decoded_buf[0..bytes_used], CKSUM.digest())?;
+
+    Ok(item)
 }
 
 #[derive(Debug)]
@@ -77,6 +558,16 @@ pub enum DisplayUpdate {
     StatusUpdate(String<64>),
     Fill(Rgb565),
     SetBrightness(DisplayBrightness),
+    /// Draw the latest day-ahead prices with consumer prices calculated with the tariff
+    Prices(tariff::Tariff),
+    /// Draw prices in `Resolution` from now on
+    PriceResolution(price::Resolution),
+    /// Switch to the page and draw it
+    ShowPage(Page),
+    /// Cover the page with a banner while the power system is not normal
+    GridAlert(grid::GridAlert),
+    /// New emission values were fetched, redraw the page if it shows them
+    Emissions,
 }
 
 impl From<&str> for DisplayUpdate {
@@ -94,6 +585,8 @@ impl From<DisplayMessage> for DisplayUpdate {
             DisplayMessage::On => DisplayUpdate::On,
             DisplayMessage::Off => DisplayUpdate::Off,
             DisplayMessage::StatusUpdate(s) => DisplayUpdate::StatusUpdate(s),
+            DisplayMessage::PriceResolution(r) => DisplayUpdate::PriceResolution(r),
+            DisplayMessage::ShowPage(p) => DisplayUpdate::ShowPage(p),
         }
     }
 }
@@ -123,13 +616,260 @@ impl DcsCommand for DisplayBrightness {
 
 // trait DisplayBrightness {}
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[repr(C)]
 pub enum DisplayMessage {
     On,
     Off,
     StatusUpdate(String<64>),
+    /// Show prices as quarter-hours or hourly averages, see [price::PriceSeries::resampled]
+    PriceResolution(price::Resolution),
+    ShowPage(Page),
+}
+
+/// What the device display shows
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub enum Page {
+    /// Chart of the latest day-ahead prices
+    #[default]
+    Prices,
+    /// Today compared to the recent days, see [statistics]
+    Statistics,
+    /// Carbon dioxide emissions of electricity next to the current price, see [emission]
+    Emissions,
+}
+
+impl Page {
+    /// Page shown after this one when cycling through the pages
+    pub fn next(self) -> Self {
+        match self {
+            Page::Prices => Page::Statistics,
+            Page::Statistics => Page::Emissions,
+            Page::Emissions => Page::Prices,
+        }
+    }
 }
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use core::str::FromStr;
+
+    use heapless::String;
+
+    use super::*;
+
+    fn wifi_message() -> Message {
+        Message::Wifi(WifiInfo::new(
+            String::from_str("MyWifi").unwrap(),
+            String::from_str("1234").unwrap(),
+        ))
+    }
+
+    #[test]
+    fn roundtrip() {
+        let mut buf = [0u8; MESSAGE_SIZE];
+        let encoded =
+            serialize_crc_cobs::<Message, MESSAGE_SIZE>(wifi_message(), &mut buf).unwrap();
+        assert_eq!(*encoded.last().unwrap(), corncobs::ZERO);
+
+        let decoded = deserialize_crc_cobs::<Message, MESSAGE_SIZE>(encoded).unwrap();
+        let Message::Wifi(info) = decoded else {
+            panic!("Wrong variant decoded");
+        };
+        assert_eq!(info.get_ssid(), "MyWifi");
+        assert_eq!(info.get_password(), "1234");
+    }
+
+    #[test]
+    fn envelope_roundtrip() {
+        let request = Envelope::new(513, wifi_message());
+        let mut buf = [0u8; MESSAGE_SIZE];
+        let encoded =
+            serialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(request.clone(), &mut buf)
+                .unwrap();
+        let decoded = deserialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(encoded).unwrap();
+        assert_eq!(decoded.id, 513);
+
+        let reply = decoded.reply(Response::Ok);
+        let mut buf = [0u8; RESPONSE_SIZE];
+        let encoded =
+            serialize_crc_cobs::<Envelope<Response>, RESPONSE_SIZE>(reply, &mut buf).unwrap();
+        let decoded = deserialize_crc_cobs::<Envelope<Response>, RESPONSE_SIZE>(encoded).unwrap();
+        assert_eq!(decoded.id, request.id);
+        assert_eq!(decoded.payload, Response::Ok);
+    }
+
+    #[test]
+    fn error_response_roundtrip() {
+        let reply = Envelope::new(
+            7,
+            Response::Error(ResponseError::Storage(StorageFailure::BufferTooSmall(300))),
+        );
+        let mut buf = [0u8; RESPONSE_SIZE];
+        let encoded =
+            serialize_crc_cobs::<Envelope<Response>, RESPONSE_SIZE>(reply.clone(), &mut buf)
+                .unwrap();
+        let decoded = deserialize_crc_cobs::<Envelope<Response>, RESPONSE_SIZE>(encoded).unwrap();
+        assert_eq!(decoded.payload, reply.payload);
+    }
+
+    #[test]
+    fn largest_statistics_fit_response() {
+        let day = statistics::DailyStats {
+            date: time::Date::new(2025, 12, 31).unwrap(),
+            average: i32::MIN,
+            min: i32::MIN,
+            max: i32::MIN,
+            hourly: [i32::MIN; 24],
+        };
+        let statistics = statistics::Statistics {
+            date: day.date,
+            days: statistics::HISTORY_DAYS as u8,
+            today: Some(day),
+            week_average: Some(i32::MIN),
+            month_average: Some(i32::MIN),
+            history_average: Some(i32::MIN),
+            profile: Some([i32::MIN; 24]),
+        };
+        let frame = DeviceFrame::Response(Envelope::new(
+            RequestId::MAX,
+            Response::Statistics(statistics),
+        ));
+        let mut buf = [0u8; RESPONSE_SIZE];
+        let encoded =
+            serialize_crc_cobs::<DeviceFrame, RESPONSE_SIZE>(frame.clone(), &mut buf).unwrap();
+        let decoded = deserialize_crc_cobs::<DeviceFrame, RESPONSE_SIZE>(encoded).unwrap();
+        assert_eq!(decoded, frame);
+    }
+
+    #[test]
+    fn hello_keeps_variant_index() {
+        let mut buf = [0u8; MESSAGE_SIZE];
+        let encoded = postcard::to_slice(&Message::Hello(PROTOCOL_VERSION), &mut buf).unwrap();
+        assert_eq!(encoded[0], 0);
+
+        let info = DeviceInfo {
+            protocol_version: PROTOCOL_VERSION,
+            firmware_version: String::from_str("0.1.0").unwrap(),
+            build_hash: String::from_str("abc1234").unwrap(),
+            capabilities: Capabilities::DISPLAY_STATUS.union(Capabilities::ENTSOE_PRICES),
+        };
+        let mut buf = [0u8; RESPONSE_SIZE];
+        let encoded = postcard::to_slice(&Response::Hello(info.clone()), &mut buf).unwrap();
+        assert_eq!(encoded[0], 0);
+
+        assert!(info.is_compatible());
+        assert!(info.capabilities.contains(Capabilities::ENTSOE_PRICES));
+        assert!(!info.capabilities.contains(Capabilities::FINGRID_DATA));
+    }
+
+    #[test]
+    fn device_frames_roundtrip() {
+        let frames = [
+            DeviceFrame::Response(Envelope::new(1, Response::Ok)),
+            DeviceFrame::Event(Event::IpAcquired([192, 168, 1, 20])),
+            DeviceFrame::Event(Event::Error(DeviceError::DroppedFrame(
+                ProtocolError::CrcMismatch,
+            ))),
+            DeviceFrame::Log(LogRecord {
+                level: LogLevel::Warn,
+                uptime_ms: 123_456,
+                target: String::from_str("electricity_exhange::wifi").unwrap(),
+                message: String::from_str("Connecting failed, retrying").unwrap(),
+            }),
+        ];
+        for frame in frames {
+            let mut buf = [0u8; RESPONSE_SIZE];
+            let encoded =
+                serialize_crc_cobs::<DeviceFrame, RESPONSE_SIZE>(frame.clone(), &mut buf).unwrap();
+            let decoded = deserialize_crc_cobs::<DeviceFrame, RESPONSE_SIZE>(encoded).unwrap();
+            assert_eq!(decoded, frame);
+        }
+    }
+
+    #[test]
+    fn wifi_validation() {
+        assert!(
+            WifiInfo::new(String::from_str("open").unwrap(), String::new())
+                .validate()
+                .is_ok()
+        );
+        assert_eq!(
+            WifiInfo::new(String::new(), String::from_str("12345678").unwrap()).validate(),
+            Err(ValidationError::Empty)
+        );
+        assert_eq!(
+            WifiInfo::new(
+                String::from_str("MyWifi").unwrap(),
+                String::from_str("1234").unwrap()
+            )
+            .validate(),
+            Err(ValidationError::TooShort(8))
+        );
+        assert_eq!(
+            validate_api_key("abc def"),
+            Err(ValidationError::InvalidCharacters)
+        );
+        assert_eq!(validate_api_key(""), Err(ValidationError::Empty));
+
+        let built = 1_735_689_600;
+        assert_eq!(validate_time(built, built), Ok(()));
+        assert_eq!(validate_time(MAX_TIME, built), Ok(()));
+        assert_eq!(
+            validate_time(built - 1, built),
+            Err(ValidationError::OutOfRange)
+        );
+        assert_eq!(
+            validate_time(MAX_TIME + 1, built),
+            Err(ValidationError::OutOfRange)
+        );
+        assert_eq!(validate_time(-1, 0), Err(ValidationError::OutOfRange));
+    }
+
+    #[test]
+    fn too_small_output_buffer() {
+        let mut buf = [0u8; 4];
+        let res = serialize_crc_cobs::<Message, MESSAGE_SIZE>(wifi_message(), &mut buf);
+        assert_eq!(res.unwrap_err(), ProtocolError::BufferOverflow);
+    }
+
+    #[test]
+    fn corrupted_byte_is_crc_mismatch() {
+        let mut buf = [0u8; MESSAGE_SIZE];
+        let encoded =
+            serialize_crc_cobs::<Message, MESSAGE_SIZE>(wifi_message(), &mut buf).unwrap();
+        // Flip a byte inside the ssid, COBS structure stays intact
+        encoded[4] ^= 0x01;
+
+        let res = deserialize_crc_cobs::<Message, MESSAGE_SIZE>(encoded);
+        assert_eq!(res.unwrap_err(), ProtocolError::CrcMismatch);
+    }
+
+    #[test]
+    fn truncated_frame_is_cobs_error() {
+        let mut buf = [0u8; MESSAGE_SIZE];
+        let encoded =
+            serialize_crc_cobs::<Message, MESSAGE_SIZE>(wifi_message(), &mut buf).unwrap();
+        let len = encoded.len();
+
+        let res = deserialize_crc_cobs::<Message, MESSAGE_SIZE>(&encoded[..len - 3]);
+        assert_eq!(res.unwrap_err(), ProtocolError::CobsDecode);
+    }
+
+    #[test]
+    fn unknown_variant() {
+        let mut buf = [0u8; MESSAGE_SIZE];
+        let encoded = serialize_crc_cobs::<u32, MESSAGE_SIZE>(200, &mut buf).unwrap();
+
+        let res = deserialize_crc_cobs::<Message, MESSAGE_SIZE>(encoded);
+        assert_eq!(res.unwrap_err(), ProtocolError::UnknownVariant);
+    }
+
+    #[test]
+    fn oversized_frame() {
+        let frame = [1u8; MESSAGE_SIZE + 1];
+        let res = deserialize_crc_cobs::<Message, MESSAGE_SIZE>(&frame);
+        assert_eq!(res.unwrap_err(), ProtocolError::BufferOverflow);
+    }
+}
@@ -0,0 +1,129 @@
+//! Keys of a [sequential_storage::map] encoded with postcard.
+//!
+//! Keys of different lengths can share a map, because [deserialize_key] returns the length
+//! of the key that was read and the value is stored right after it.
+
+use sequential_storage::map::SerializationError;
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Encodes `key` to the start of `buffer`, returns the encoded length
+///
+/// # Errors
+///
+/// This function will return an error if `buffer` is too small for the key.
+pub fn serialize_key<K: Serialize>(
+    key: &K,
+    buffer: &mut [u8],
+) -> Result<usize, SerializationError> {
+    let encoded = postcard::to_slice(key, buffer).map_err(to_serialization_error)?;
+    Ok(encoded.len())
+}
+
+/// Decodes the key at the start of `buffer`, returns it with its encoded length
+///
+/// # Errors
+///
+/// This function will return an error if `buffer` does not start with a valid key.
+pub fn deserialize_key<K: DeserializeOwned>(
+    buffer: &[u8],
+) -> Result<(K, usize), SerializationError> {
+    let (key, rest) = postcard::take_from_bytes(buffer).map_err(to_serialization_error)?;
+    Ok((key, buffer.len() - rest.len()))
+}
+
+fn to_serialization_error(e: postcard::Error) -> SerializationError {
+    match e {
+        postcard::Error::SerializeBufferFull => SerializationError::BufferTooSmall,
+        postcard::Error::DeserializeBadVarint
+        | postcard::Error::DeserializeBadBool
+        | postcard::Error::DeserializeBadChar
+        | postcard::Error::DeserializeBadUtf8
+        | postcard::Error::DeserializeBadOption
+        | postcard::Error::DeserializeBadEnum
+        | postcard::Error::DeserializeBadEncoding => SerializationError::InvalidData,
+        _ => SerializationError::Custom(0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+    use sequential_storage::{
+        cache::NoCache,
+        map::{fetch_item, store_item, Key, SerializationError},
+    };
+    use serde::{Deserialize, Serialize};
+
+    use super::{deserialize_key, serialize_key};
+    use crate::mock_flash::{MockFlash, PAGE_SIZE};
+
+    #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+    enum TestKey {
+        Name,
+        Rule(u8),
+    }
+
+    impl Key for TestKey {
+        fn serialize_into(&self, buffer: &mut [u8]) -> Result<usize, SerializationError> {
+            serialize_key(self, buffer)
+        }
+
+        fn deserialize_from(buffer: &[u8]) -> Result<(Self, usize), SerializationError> {
+            deserialize_key(buffer)
+        }
+    }
+
+    #[test]
+    fn key_length_is_the_encoded_length() {
+        let mut buffer = [0u8; 2];
+        assert_eq!(serialize_key(&TestKey::Name, &mut buffer), Ok(1));
+        assert_eq!(
+            deserialize_key::<TestKey>(&[0, 0xAA, 0xBB]),
+            Ok((TestKey::Name, 1))
+        );
+        assert_eq!(serialize_key(&TestKey::Rule(3), &mut buffer), Ok(2));
+        assert_eq!(
+            deserialize_key::<TestKey>(&[1, 3, 0xAA]),
+            Ok((TestKey::Rule(3), 2))
+        );
+    }
+
+    #[test]
+    fn values_round_trip_with_keys_of_different_lengths() {
+        let mut flash = MockFlash::new(2);
+        let range = 0..2 * PAGE_SIZE as u32;
+        let mut buffer = [0u8; 64];
+        let items: [(TestKey, &[u8]); 3] = [
+            (TestKey::Name, b"living room"),
+            (TestKey::Rule(0), b"on below 5"),
+            (TestKey::Rule(1), b""),
+        ];
+
+        block_on(async {
+            for (key, value) in &items {
+                store_item(
+                    &mut flash,
+                    range.clone(),
+                    &mut NoCache::new(),
+                    &mut buffer,
+                    key.clone(),
+                    value,
+                )
+                .await
+                .unwrap();
+            }
+            for (key, value) in &items {
+                let fetched = fetch_item::<TestKey, &[u8], _>(
+                    &mut flash,
+                    range.clone(),
+                    &mut NoCache::new(),
+                    &mut buffer,
+                    key.clone(),
+                )
+                .await
+                .unwrap();
+                assert_eq!(fetched, Some(*value));
+            }
+        });
+    }
+}
@@ -0,0 +1,330 @@
+//! Streaming parser for community price apis that mirror day-ahead prices, such as spot-hinta.fi.
+//!
+//! These apis return a JSON array with an object for each time unit
+//!
+//! ```json
+//! [
+//!   {"Rank": 89, "DateTime": "2024-10-01T00:00:00+03:00", "PriceNoTax": 0.04, "PriceWithTax": 0.0502},
+//!   {"Rank": 94, "DateTime": "2024-10-01T00:15:00+03:00", "PriceNoTax": 0.04357, "PriceWithTax": 0.05468}
+//! ]
+//! ```
+//!
+//! Keys and the price unit differ between apis and are described with [MirrorFormat].
+//! Objects do not have the end time so the length of a time unit is taken from the start
+//! of the next one.
+
+use crate::fixed::parse_decimal;
+use crate::json::{Container, JsonError, JsonLexer, Level, Token};
+use crate::price::{PricePoint, PriceSeries, PriceSeriesError, Resolution, Timestamp, PRICE_SCALE};
+use crate::time::parse_timestamp;
+
+/// Describes the objects returned by a mirror api
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MirrorFormat {
+    /// Key of the start time, in ISO 8601 with an offset
+    pub time_key: &'static str,
+    /// Key of the price without taxes
+    pub price_key: &'static str,
+    /// Scale that converts prices to 1/[PRICE_SCALE] of currency unit per MWh,
+    /// for example `PRICE_SCALE * 1000` for prices in currency units per kWh
+    pub price_scale: i32,
+}
+
+/// <https://api.spot-hinta.fi/swagger/ui>, prices are in €/kWh
+pub const SPOT_HINTA: MirrorFormat = MirrorFormat {
+    time_key: "DateTime",
+    price_key: "PriceNoTax",
+    price_scale: PRICE_SCALE * 1000,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MirrorError {
+    /// Response is not a JSON array of price objects
+    Malformed,
+    /// Response had no prices for the requested period
+    NoData,
+    /// Time units are something else than 15 or 60 minutes long
+    UnsupportedResolution,
+    InvalidTime,
+    InvalidNumber,
+    /// Series ran out of capacity
+    Full,
+}
+
+impl core::fmt::Display for MirrorError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            MirrorError::Malformed => write!(f, "malformed response"),
+            MirrorError::NoData => write!(f, "no data in response"),
+            MirrorError::UnsupportedResolution => write!(f, "unsupported resolution"),
+            MirrorError::InvalidTime => write!(f, "invalid time"),
+            MirrorError::InvalidNumber => write!(f, "invalid number"),
+            MirrorError::Full => write!(f, "too many price points"),
+        }
+    }
+}
+
+impl From<JsonError> for MirrorError {
+    fn from(_: JsonError) -> Self {
+        Self::Malformed
+    }
+}
+
+/// Push parser for mirror responses, see [module documentation](self)
+#[derive(Debug)]
+pub struct MirrorParser {
+    json: JsonLexer,
+    prices: PriceState,
+    error: Option<MirrorError>,
+}
+
+/// Everything except the lexer, so that it can be borrowed by the token callback
+#[derive(Debug)]
+struct PriceState {
+    format: MirrorFormat,
+    period_start: Timestamp,
+    period_end: Timestamp,
+    start: Option<Timestamp>,
+    price: Option<i32>,
+    /// Previous object, it is pushed when the start of the next one is known
+    pending: Option<(Timestamp, i32)>,
+    duration: Option<u32>,
+    points: usize,
+}
+
+impl MirrorParser {
+    /// Creates parser that keeps only the prices that start from `period_start`
+    /// and before `period_end`
+    pub const fn new(format: MirrorFormat, period_start: Timestamp, period_end: Timestamp) -> Self {
+        Self {
+            json: JsonLexer::new(),
+            prices: PriceState {
+                format,
+                period_start,
+                period_end,
+                start: None,
+                price: None,
+                pending: None,
+                duration: None,
+                points: 0,
+            },
+            error: None,
+        }
+    }
+
+    /// Parses next part of the response and appends completed points to `series`.
+    ///
+    /// Points that overlap with points already in `series` are skipped.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the response is invalid. After an error
+    /// the same error is returned for all further input.
+    pub fn feed<const N: usize>(
+        &mut self,
+        input: &[u8],
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), MirrorError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        for byte in input {
+            let result = self.json.feed(*byte, &mut |token, path| {
+                self.prices.token(token, path, series)
+            });
+            if let Err(e) = result {
+                self.error = Some(e);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that the whole response was parsed and pushes the last point,
+    /// which gets the same length as the one before it.
+    pub fn finish<const N: usize>(
+        &mut self,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), MirrorError> {
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+        if !self.json.is_done() {
+            return Err(MirrorError::Malformed);
+        }
+        if let (Some((start, price)), Some(duration)) =
+            (self.prices.pending.take(), self.prices.duration)
+        {
+            self.prices.push(start, duration, price, series)?;
+        }
+        if self.prices.points == 0 {
+            return Err(MirrorError::NoData);
+        }
+        Ok(())
+    }
+}
+
+impl PriceState {
+    fn token<const N: usize>(
+        &mut self,
+        token: Token<'_>,
+        path: &[Level],
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), MirrorError> {
+        let is_price_object = matches!(path, [root, _] if root.container == Container::Array);
+        match token {
+            Token::Open(Container::Object) if is_price_object => {
+                self.start = None;
+                self.price = None;
+            }
+            Token::Close(Container::Object) if is_price_object => {
+                let (Some(start), Some(price)) = (self.start, self.price) else {
+                    return Err(MirrorError::Malformed);
+                };
+                self.object_complete(start, price, series)?;
+            }
+            Token::String(s) | Token::Scalar(s) if is_price_object => {
+                let key = path.last().map_or("", |level| level.key());
+                if key == self.format.time_key {
+                    self.start = Some(parse_timestamp(s).ok_or(MirrorError::InvalidTime)?);
+                } else if key == self.format.price_key {
+                    self.price = Some(
+                        parse_decimal(s, self.format.price_scale)
+                            .ok_or(MirrorError::InvalidNumber)?,
+                    );
+                }
+            }
+            // Root must be an array
+            Token::Open(Container::Object) if path.len() == 1 => {
+                return Err(MirrorError::Malformed)
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn object_complete<const N: usize>(
+        &mut self,
+        start: Timestamp,
+        price: i32,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), MirrorError> {
+        if let Some((pending_start, pending_price)) = self.pending {
+            let resolution = u32::try_from(start - pending_start)
+                .ok()
+                .and_then(Resolution::from_seconds)
+                .ok_or(MirrorError::UnsupportedResolution)?;
+            self.duration = Some(resolution.seconds());
+            self.push(pending_start, resolution.seconds(), pending_price, series)?;
+        }
+        self.pending = Some((start, price));
+        Ok(())
+    }
+
+    fn push<const N: usize>(
+        &mut self,
+        start: Timestamp,
+        duration: u32,
+        price: i32,
+        series: &mut PriceSeries<N>,
+    ) -> Result<(), MirrorError> {
+        if !(self.period_start..self.period_end).contains(&start) {
+            return Ok(());
+        }
+        match series.push(PricePoint {
+            start,
+            duration,
+            price,
+        }) {
+            Ok(()) => {
+                self.points += 1;
+                Ok(())
+            }
+            Err(PriceSeriesError::NotSorted) => Ok(()),
+            Err(PriceSeriesError::Full) => Err(MirrorError::Full),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MirrorError, MirrorParser, SPOT_HINTA};
+    use crate::price::{delivery_period, BiddingZone, Currency, PriceSeries, Resolution};
+    use crate::time::{parse_timestamp, Date};
+
+    const SPOT_HINTA_RESPONSE: &str =
+        include_str!("../tests/fixtures/mirror/spot_hinta_today_and_day_forward.json");
+
+    fn parse(
+        response: &str,
+        chunk_size: usize,
+        date: Date,
+    ) -> (PriceSeries, Result<(), MirrorError>) {
+        let (start, end) = delivery_period(date);
+        let mut series = PriceSeries::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        let mut parser = MirrorParser::new(SPOT_HINTA, start, end);
+        for chunk in response.as_bytes().chunks(chunk_size) {
+            if let Err(e) = parser.feed(chunk, &mut series) {
+                return (series, Err(e));
+            }
+        }
+        let result = parser.finish(&mut series);
+        (series, result)
+    }
+
+    #[test]
+    fn keeps_only_delivery_day() {
+        let date = Date::new(2024, 10, 2).unwrap();
+        let (series, result) = parse(SPOT_HINTA_RESPONSE, 4096, date);
+        result.unwrap();
+        assert_eq!(series.resolution, Resolution::Minutes15);
+
+        // Response ends at midnight in Finland, an hour before the CET day
+        assert_eq!(series.len(), 92);
+        let first = series.points()[0];
+        assert_eq!(first.start, delivery_period(date).0);
+        assert_eq!(
+            Some(first.start),
+            parse_timestamp("2024-10-02T01:00:00+03:00")
+        );
+        assert_eq!(first.price, 7788);
+        let last = series.points()[91];
+        assert_eq!(last.price, 8401);
+        assert_eq!(last.duration, 900);
+        assert!(series.min().unwrap().price < 0);
+    }
+
+    #[test]
+    fn chunk_boundaries_do_not_matter() {
+        let date = Date::new(2024, 10, 1).unwrap();
+        let (whole, _) = parse(SPOT_HINTA_RESPONSE, 4096, date);
+        for chunk_size in [1, 3, 17] {
+            let (chunked, result) = parse(SPOT_HINTA_RESPONSE, chunk_size, date);
+            result.unwrap();
+            assert_eq!(chunked, whole);
+        }
+    }
+
+    #[test]
+    fn day_outside_response_is_no_data() {
+        let date = Date::new(2024, 10, 5).unwrap();
+        let (series, result) = parse(SPOT_HINTA_RESPONSE, 4096, date);
+        assert_eq!(result, Err(MirrorError::NoData));
+        assert!(series.is_empty());
+    }
+
+    #[test]
+    fn invalid_responses() {
+        let date = Date::new(2024, 10, 2).unwrap();
+        let (_, result) = parse(r#"{"message": "Too many requests"}"#, 4096, date);
+        assert_eq!(result, Err(MirrorError::Malformed));
+
+        let hourly_gap = r#"[{"DateTime": "2024-10-02T01:00:00+03:00", "PriceNoTax": 0.1},
+            {"DateTime": "2024-10-02T03:00:00+03:00", "PriceNoTax": 0.1}]"#;
+        let (_, result) = parse(hourly_gap, 4096, date);
+        assert_eq!(result, Err(MirrorError::UnsupportedResolution));
+
+        let (_, result) = parse(&SPOT_HINTA_RESPONSE.replace("+03:00", ""), 4096, date);
+        assert_eq!(result, Err(MirrorError::InvalidTime));
+    }
+}
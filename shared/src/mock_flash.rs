@@ -0,0 +1,85 @@
+//! NOR flash in memory for testing code that uses [sequential_storage]
+
+use embedded_storage_async::nor_flash::{
+    ErrorType, NorFlash, NorFlashError, NorFlashErrorKind, ReadNorFlash,
+};
+
+/// Erase page of the flash
+pub const PAGE_SIZE: usize = 4096;
+
+/// NOR flash in memory that works like the flash of the ESP32-C3: erasing sets
+/// every bit of a page and writing can only clear bits
+pub struct MockFlash {
+    /// Contents of the flash
+    pub data: std::vec::Vec<u8>,
+    /// Erase operations done so far
+    pub erases: usize,
+}
+
+impl MockFlash {
+    pub fn new(pages: usize) -> Self {
+        Self {
+            data: std::vec![0xFF; pages * PAGE_SIZE],
+            erases: 0,
+        }
+    }
+
+    fn check(&self, from: u32, len: usize, align: usize) -> Result<(), MockFlashError> {
+        if from as usize % align != 0 || len % align != 0 {
+            return Err(MockFlashError(NorFlashErrorKind::NotAligned));
+        }
+        if from as usize + len > self.data.len() {
+            return Err(MockFlashError(NorFlashErrorKind::OutOfBounds));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct MockFlashError(NorFlashErrorKind);
+
+impl NorFlashError for MockFlashError {
+    fn kind(&self) -> NorFlashErrorKind {
+        self.0
+    }
+}
+
+impl ErrorType for MockFlash {
+    type Error = MockFlashError;
+}
+
+impl ReadNorFlash for MockFlash {
+    const READ_SIZE: usize = 4;
+
+    async fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check(offset, bytes.len(), Self::READ_SIZE)?;
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.data.len()
+    }
+}
+
+impl NorFlash for MockFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = PAGE_SIZE;
+
+    async fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        self.check(from, (to - from) as usize, Self::ERASE_SIZE)?;
+        self.data[from as usize..to as usize].fill(0xFF);
+        self.erases += 1;
+        Ok(())
+    }
+
+    async fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check(offset, bytes.len(), Self::WRITE_SIZE)?;
+        let offset = offset as usize;
+        for (old, new) in self.data[offset..].iter_mut().zip(bytes) {
+            *old &= new;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,353 @@
+//! Cheapest time to run an appliance within the known day-ahead prices.
+//!
+//! An appliance that must run without interruptions, like a dishwasher, gets the cheapest
+//! contiguous window with [cheapest_window]. One that can be paused, like a water heater or
+//! a car charger, gets the cheapest time units with [cheapest_slots]. Both only consider time
+//! units that fit between the earliest start and the deadline of the [PlanRequest].
+
+use heapless::{String, Vec};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    price::{PricePoint, PriceSeries, Resolution, Timestamp, MAX_PRICE_POINTS},
+    ValidationError,
+};
+
+/// Maximum number of appliances in a single [Message::PlanWindow](crate::Message::PlanWindow)
+pub const MAX_APPLIANCES: usize = 4;
+
+/// Maximum number of time units a [Plan] can span
+pub const MAX_PLAN_SLOTS: usize = MAX_PRICE_POINTS;
+
+/// When and how long an appliance needs to run
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct PlanRequest {
+    /// Shown on the device display, for example "dishwasher"
+    pub name: String<16>,
+    /// Run time in time units of [Self::resolution]
+    pub slots: u16,
+    pub resolution: Resolution,
+    /// Time units that start before this are not used
+    pub earliest: Timestamp,
+    /// Appliance must be done by this
+    pub deadline: Timestamp,
+    /// Appliance can not be paused once started
+    pub contiguous: bool,
+}
+
+impl PlanRequest {
+    /// Checks that the name is not empty, run time is between one and [MAX_PLAN_SLOTS]
+    /// time units and the deadline is after the earliest start
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.name.is_empty() {
+            return Err(ValidationError::Empty);
+        }
+        if self.slots == 0 || self.slots as usize > MAX_PLAN_SLOTS || self.deadline <= self.earliest
+        {
+            return Err(ValidationError::OutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Plans the run with prices of `series`.
+    ///
+    /// Returns [None] if there are not enough known prices before the deadline.
+    pub fn plan<const N: usize>(&self, series: &PriceSeries<N>) -> Option<Plan> {
+        let points = series.resampled(self.resolution);
+        let slots = self.slots as usize;
+        if self.contiguous {
+            cheapest_window(points, slots, self.earliest, self.deadline)
+        } else {
+            cheapest_slots(points, slots, self.earliest, self.deadline)
+        }
+    }
+}
+
+/// Time units selected for an appliance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Plan {
+    /// Start of the first selected time unit
+    pub start: Timestamp,
+    pub resolution: Resolution,
+    /// Average price of the selected time units
+    pub average: i32,
+    /// Bit `i` is set if the time unit `i` units after [Self::start] is selected
+    selected: [u8; MAX_PLAN_SLOTS / 8],
+}
+
+impl Plan {
+    /// Plan of `points`, which must be in order and within [MAX_PLAN_SLOTS] time units
+    /// of `resolution` from the first one. Returns [None] if `points` is empty.
+    fn new(resolution: Resolution, points: &[PricePoint]) -> Option<Self> {
+        let start = points.first()?.start;
+        let duration = resolution.seconds() as Timestamp;
+        let mut selected = [0u8; MAX_PLAN_SLOTS / 8];
+        let mut sum = 0i64;
+        for point in points {
+            let i = ((point.start - start) / duration) as usize;
+            selected[i / 8] |= 1 << (i % 8);
+            sum += point.price as i64;
+        }
+        Some(Self {
+            start,
+            resolution,
+            average: (sum / points.len() as i64) as i32,
+            selected,
+        })
+    }
+
+    /// Starts of the selected time units in order
+    pub fn slots(&self) -> impl Iterator<Item = Timestamp> + '_ {
+        let duration = self.resolution.seconds() as Timestamp;
+        (0..MAX_PLAN_SLOTS)
+            .filter(|i| self.selected[i / 8] & (1 << (i % 8)) != 0)
+            .map(move |i| self.start + i as Timestamp * duration)
+    }
+
+    /// Start and end of each uninterrupted run of selected time units
+    pub fn runs(&self) -> impl Iterator<Item = (Timestamp, Timestamp)> + '_ {
+        let duration = self.resolution.seconds() as Timestamp;
+        let mut slots = self.slots().peekable();
+        core::iter::from_fn(move || {
+            let start = slots.next()?;
+            let mut end = start + duration;
+            while slots.next_if_eq(&end).is_some() {
+                end += duration;
+            }
+            Some((start, end))
+        })
+    }
+
+    /// End of the last selected time unit
+    pub fn end(&self) -> Timestamp {
+        self.runs().last().map_or(self.start, |(_, end)| end)
+    }
+}
+
+/// Points of `points` that fit between `earliest` and `deadline`, limited to
+/// [MAX_PLAN_SLOTS] time units from the first one
+fn candidates(
+    points: impl Iterator<Item = PricePoint>,
+    earliest: Timestamp,
+    deadline: Timestamp,
+) -> Vec<PricePoint, MAX_PLAN_SLOTS> {
+    let mut candidates = Vec::new();
+    let mut horizon = None;
+    for point in points.filter(|p| p.start >= earliest && p.end() <= deadline) {
+        let horizon = *horizon
+            .get_or_insert(point.start + MAX_PLAN_SLOTS as Timestamp * point.duration as Timestamp);
+        if point.start >= horizon || candidates.push(point).is_err() {
+            break;
+        }
+    }
+    candidates
+}
+
+/// Cheapest `slots` consecutive time units of `points` that start at or after `earliest`
+/// and end by `deadline`. Earliest of equally cheap windows is returned.
+///
+/// `points` must be in order and in a single resolution, like [PriceSeries::resampled].
+/// Windows can not span gaps in `points`.
+pub fn cheapest_window(
+    points: impl Iterator<Item = PricePoint>,
+    slots: usize,
+    earliest: Timestamp,
+    deadline: Timestamp,
+) -> Option<Plan> {
+    let candidates = candidates(points, earliest, deadline);
+    if slots == 0 {
+        return None;
+    }
+    let mut best: Option<(i64, &[PricePoint])> = None;
+    for window in candidates.windows(slots) {
+        let (first, last) = (window[0], window[slots - 1]);
+        if last.start - first.start != (slots as Timestamp - 1) * first.duration as Timestamp {
+            continue;
+        }
+        let sum = window.iter().map(|p| p.price as i64).sum();
+        if best.map_or(true, |(best, _)| sum < best) {
+            best = Some((sum, window));
+        }
+    }
+    let (_, window) = best?;
+    Plan::new(Resolution::from_seconds(window[0].duration)?, window)
+}
+
+/// Cheapest `slots` time units of `points` that start at or after `earliest` and end by
+/// `deadline`, not necessarily consecutive. Earlier time units are preferred when prices
+/// are equal.
+///
+/// `points` must be in order and in a single resolution, like [PriceSeries::resampled].
+pub fn cheapest_slots(
+    points: impl Iterator<Item = PricePoint>,
+    slots: usize,
+    earliest: Timestamp,
+    deadline: Timestamp,
+) -> Option<Plan> {
+    let mut candidates = candidates(points, earliest, deadline);
+    if slots == 0 || candidates.len() < slots {
+        return None;
+    }
+    candidates.sort_unstable_by_key(|p| (p.price, p.start));
+    candidates.truncate(slots);
+    candidates.sort_unstable_by_key(|p| p.start);
+    Plan::new(
+        Resolution::from_seconds(candidates[0].duration)?,
+        &candidates,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use core::str::FromStr;
+
+    use heapless::String;
+
+    use super::{cheapest_slots, cheapest_window, PlanRequest, MAX_PLAN_SLOTS};
+    use crate::{
+        price::{BiddingZone, Currency, PricePoint, PriceSeries, Resolution, Timestamp},
+        ValidationError,
+    };
+
+    /// 2024-06-01 00:00 UTC
+    const DAY: Timestamp = 1_717_200_000;
+    const QUARTER: Timestamp = 900;
+
+    fn quarters(prices: &[i32]) -> impl Iterator<Item = PricePoint> + '_ {
+        prices.iter().enumerate().map(|(i, &price)| PricePoint {
+            start: DAY + i as Timestamp * QUARTER,
+            duration: QUARTER as u32,
+            price,
+        })
+    }
+
+    #[test]
+    fn contiguous_window() {
+        let prices = [50, 40, 10, 30, 20, 20, 25, 60];
+        let plan = cheapest_window(quarters(&prices), 2, DAY, DAY + 8 * QUARTER).unwrap();
+        // 10 + 30 and 20 + 20 cost the same, the earlier one wins
+        assert_eq!(plan.start, DAY + 2 * QUARTER);
+        assert_eq!(plan.end(), DAY + 4 * QUARTER);
+        assert_eq!(plan.average, 20);
+        assert_eq!(plan.resolution, Resolution::Minutes15);
+
+        // Window must start at or after the earliest start and end by the deadline
+        let plan = cheapest_window(quarters(&prices), 2, DAY + 3 * QUARTER, DAY + 6 * QUARTER);
+        assert_eq!(plan.unwrap().start, DAY + 4 * QUARTER);
+        let plan = cheapest_window(quarters(&prices), 3, DAY, DAY + 4 * QUARTER);
+        assert_eq!(plan.unwrap().start, DAY + QUARTER);
+        assert_eq!(
+            cheapest_window(quarters(&prices), 3, DAY + 6 * QUARTER, DAY + 8 * QUARTER),
+            None
+        );
+    }
+
+    #[test]
+    fn window_does_not_span_gaps() {
+        let points = quarters(&[10, 10, 50, 50, 50, 50])
+            .enumerate()
+            .filter(|(i, _)| *i != 1)
+            .map(|(_, p)| p);
+        let plan = cheapest_window(points, 2, DAY, DAY + 6 * QUARTER).unwrap();
+        assert_eq!(plan.start, DAY + 2 * QUARTER);
+        assert_eq!(plan.average, 50);
+    }
+
+    #[test]
+    fn separate_slots() {
+        let prices = [50, 10, 40, 10, 30, 5, 60, 10];
+        let plan = cheapest_slots(quarters(&prices), 4, DAY, DAY + 8 * QUARTER).unwrap();
+        // Three quarters at 10 and one at 5, the last 10 is as cheap as the earlier ones
+        let slots: std::vec::Vec<Timestamp> = plan.slots().collect();
+        assert_eq!(
+            slots,
+            [
+                DAY + QUARTER,
+                DAY + 3 * QUARTER,
+                DAY + 5 * QUARTER,
+                DAY + 7 * QUARTER
+            ]
+        );
+        assert_eq!(plan.average, 8);
+        assert_eq!(plan.end(), DAY + 8 * QUARTER);
+
+        let plan = cheapest_slots(quarters(&prices), 3, DAY, DAY + 6 * QUARTER).unwrap();
+        let runs: std::vec::Vec<(Timestamp, Timestamp)> = plan.runs().collect();
+        assert_eq!(
+            runs,
+            [
+                (DAY + QUARTER, DAY + 2 * QUARTER),
+                (DAY + 3 * QUARTER, DAY + 4 * QUARTER),
+                (DAY + 5 * QUARTER, DAY + 6 * QUARTER)
+            ]
+        );
+
+        assert_eq!(
+            cheapest_slots(quarters(&prices), 9, DAY, DAY + 8 * QUARTER),
+            None
+        );
+    }
+
+    #[test]
+    fn hourly_request() {
+        let mut series = PriceSeries::<{ MAX_PLAN_SLOTS }>::new(
+            BiddingZone::Fi,
+            Currency::Eur,
+            Resolution::Minutes15,
+        );
+        // Hour 02 is cheapest on average but its cheapest quarter is in hour 01
+        let prices = [
+            40, 40, 40, 40, 50, 0, 50, 50, 20, 20, 20, 20, 60, 60, 60, 60,
+        ];
+        for point in quarters(&prices) {
+            series.push(point).unwrap();
+        }
+        let mut request = PlanRequest {
+            name: String::from_str("dishwasher").unwrap(),
+            slots: 1,
+            resolution: Resolution::Minutes60,
+            earliest: DAY,
+            deadline: DAY + 4 * 3600,
+            contiguous: true,
+        };
+        assert_eq!(request.validate(), Ok(()));
+        let plan = request.plan(&series).unwrap();
+        assert_eq!(plan.start, DAY + 2 * 3600);
+        assert_eq!(plan.end(), DAY + 3 * 3600);
+        assert_eq!(plan.average, 20);
+
+        request.resolution = Resolution::Minutes15;
+        request.contiguous = false;
+        assert_eq!(request.plan(&series).unwrap().start, DAY + 5 * QUARTER);
+    }
+
+    #[test]
+    fn request_validation() {
+        let request = PlanRequest {
+            name: String::from_str("heater").unwrap(),
+            slots: 4,
+            resolution: Resolution::Minutes15,
+            earliest: DAY,
+            deadline: DAY + 3600,
+            contiguous: false,
+        };
+        assert_eq!(request.validate(), Ok(()));
+        let empty = PlanRequest {
+            name: String::new(),
+            ..request.clone()
+        };
+        assert_eq!(empty.validate(), Err(ValidationError::Empty));
+        let zero = PlanRequest {
+            slots: 0,
+            ..request.clone()
+        };
+        assert_eq!(zero.validate(), Err(ValidationError::OutOfRange));
+        let late = PlanRequest {
+            earliest: DAY + 3600,
+            ..request
+        };
+        assert_eq!(late.validate(), Err(ValidationError::OutOfRange));
+    }
+}
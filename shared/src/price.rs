@@ -0,0 +1,914 @@
+//! Electricity price data shared by the firmware fetchers, the display and the host.
+//!
+//! Prices are fixed point integers, see [PricePoint::price], so that they can be compared and
+//! summed on the device without floats and encoded compactly with postcard.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+use strum::{EnumCount, VariantArray};
+
+use crate::time::{Date, TimeZone};
+
+/// Seconds since unix epoch in UTC
+pub type Timestamp = i64;
+
+/// [PricePoint::price] is in 1/`PRICE_SCALE` of currency unit per MWh
+pub const PRICE_SCALE: i32 = 100;
+
+/// Enough points for two days with [Resolution::Minutes15], also when one of them is
+/// 25 hours long because summer time ends
+pub const MAX_PRICE_POINTS: usize = 2 * 100;
+
+/// Price of electricity for a single market time unit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct PricePoint {
+    pub start: Timestamp,
+    /// Length of the time unit in seconds
+    pub duration: u32,
+    /// Price in 1/[PRICE_SCALE] of currency unit per MWh, for example 4567 is 45.67 €/MWh.
+    ///
+    /// Can be negative.
+    pub price: i32,
+}
+
+impl PricePoint {
+    /// First second after this time unit
+    pub fn end(&self) -> Timestamp {
+        self.start + self.duration as Timestamp
+    }
+
+    /// Returns true if `instant` is within this time unit
+    pub fn contains(&self, instant: Timestamp) -> bool {
+        self.start <= instant && instant < self.end()
+    }
+}
+
+/// Market time unit of a [PriceSeries], ordered from the finest
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[repr(C)]
+pub enum Resolution {
+    Minutes15,
+    Minutes60,
+}
+
+impl Resolution {
+    pub const fn seconds(self) -> u32 {
+        match self {
+            Resolution::Minutes15 => 15 * 60,
+            Resolution::Minutes60 => 60 * 60,
+        }
+    }
+
+    /// Resolution whose time unit is `seconds` long
+    pub const fn from_seconds(seconds: u32) -> Option<Self> {
+        match seconds {
+            900 => Some(Resolution::Minutes15),
+            3600 => Some(Resolution::Minutes60),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum Currency {
+    Eur,
+    Sek,
+    Nok,
+    Dkk,
+}
+
+/// Day-ahead market area
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum_macros::VariantArray,
+    strum_macros::EnumCount,
+)]
+#[repr(C)]
+pub enum BiddingZone {
+    Fi,
+    Ee,
+    Se1,
+    Se2,
+    Se3,
+    Se4,
+    No1,
+    No2,
+    No3,
+    No4,
+    No5,
+    Dk1,
+    Dk2,
+    Lt,
+    Lv,
+}
+
+impl BiddingZone {
+    /// Short name used by Nord Pool and most price apis
+    pub const fn code(self) -> &'static str {
+        match self {
+            BiddingZone::Fi => "FI",
+            BiddingZone::Ee => "EE",
+            BiddingZone::Se1 => "SE1",
+            BiddingZone::Se2 => "SE2",
+            BiddingZone::Se3 => "SE3",
+            BiddingZone::Se4 => "SE4",
+            BiddingZone::No1 => "NO1",
+            BiddingZone::No2 => "NO2",
+            BiddingZone::No3 => "NO3",
+            BiddingZone::No4 => "NO4",
+            BiddingZone::No5 => "NO5",
+            BiddingZone::Dk1 => "DK1",
+            BiddingZone::Dk2 => "DK2",
+            BiddingZone::Lt => "LT",
+            BiddingZone::Lv => "LV",
+        }
+    }
+
+    /// Zone with [Self::code] `code`
+    pub fn from_code(code: &str) -> Option<Self> {
+        Self::VARIANTS
+            .iter()
+            .copied()
+            .find(|zone| zone.code() == code)
+    }
+
+    /// Local time of the zone
+    pub const fn time_zone(self) -> TimeZone {
+        match self {
+            BiddingZone::Fi | BiddingZone::Ee | BiddingZone::Lt | BiddingZone::Lv => TimeZone::Eet,
+            _ => TimeZone::Cet,
+        }
+    }
+
+    /// Energy Identification Code used by ENTSO-E to identify the zone
+    pub const fn eic(self) -> &'static str {
+        match self {
+            BiddingZone::Fi => "10YFI-1--------U",
+            BiddingZone::Ee => "10Y1001A1001A39I",
+            BiddingZone::Se1 => "10Y1001A1001A44P",
+            BiddingZone::Se2 => "10Y1001A1001A45N",
+            BiddingZone::Se3 => "10Y1001A1001A46L",
+            BiddingZone::Se4 => "10Y1001A1001A47J",
+            BiddingZone::No1 => "10YNO-1--------2",
+            BiddingZone::No2 => "10YNO-2--------T",
+            BiddingZone::No3 => "10YNO-3--------J",
+            BiddingZone::No4 => "10YNO-4--------9",
+            BiddingZone::No5 => "10Y1001A1001A48H",
+            BiddingZone::Dk1 => "10YDK-1--------W",
+            BiddingZone::Dk2 => "10YDK-2--------M",
+            BiddingZone::Lt => "10YLT-1001A0008Q",
+            BiddingZone::Lv => "10YLV-1001A00074",
+        }
+    }
+}
+
+/// Zone used until the host selects one
+pub const DEFAULT_BIDDING_ZONE: BiddingZone = BiddingZone::Fi;
+
+/// Start and end of the delivery day `date` of the day-ahead market.
+///
+/// Delivery days are from midnight to midnight Central European Time,
+/// so they are 23 or 25 hours long when summer time starts or ends.
+pub fn delivery_period(date: Date) -> (Timestamp, Timestamp) {
+    TimeZone::Cet.day(date)
+}
+
+/// Results of the day-ahead auction are published around 12:45 CET, prices of the next
+/// delivery day are fetched from this hour on
+pub const PUBLICATION_HOUR: Timestamp = 13;
+
+/// Time between attempts to fetch prices that are still missing
+pub const RETRY_INTERVAL: Timestamp = 15 * 60;
+
+/// When prices of the delivery day after `date` are fetched, [PUBLICATION_HOUR] CET on `date`
+pub fn publication_time(date: Date) -> Timestamp {
+    let (midnight, _) = delivery_period(date);
+    let time = midnight + PUBLICATION_HOUR * 3600;
+    // Summer time starts or ends between midnight and the publication
+    time - (TimeZone::Cet.offset(time) - TimeZone::Cet.offset(midnight))
+}
+
+/// Delivery days whose prices should be fetched and when to check again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DayAheadSchedule {
+    /// Today and, after the prices have been published, tomorrow if their prices are missing
+    pub dates: Vec<Date, 2>,
+    /// Next publication, or [RETRY_INTERVAL] from now if prices are missing
+    pub next_check: Timestamp,
+}
+
+impl DayAheadSchedule {
+    /// Schedule at `now` when `series` has the latest prices and `zone` is selected.
+    /// Prices of another zone are fetched again.
+    pub fn new<const N: usize>(series: &PriceSeries<N>, zone: BiddingZone, now: Timestamp) -> Self {
+        let today = TimeZone::Cet.date(now);
+        let tomorrow = today.next();
+        let published = publication_time(today);
+
+        let mut dates = Vec::new();
+        if !series.has_delivery_day(zone, today) {
+            let _ = dates.push(today);
+        }
+        if now >= published && !series.has_delivery_day(zone, tomorrow) {
+            let _ = dates.push(tomorrow);
+        }
+        let next_check = if !dates.is_empty() {
+            now + RETRY_INTERVAL
+        } else if now < published {
+            published
+        } else {
+            publication_time(tomorrow)
+        };
+        Self { dates, next_check }
+    }
+}
+
+/// Source of day-ahead prices.
+///
+/// Device tries sources in the order configured with
+/// [Message::PriceSources](crate::Message::PriceSources) until one of them succeeds.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::IntoStaticStr,
+    strum_macros::EnumCount,
+)]
+#[repr(C)]
+pub enum PriceSource {
+    /// ENTSO-E Transparency Platform, needs an api key
+    Entsoe,
+    /// Fingrid open data, needs an api key. Only has prices for [BiddingZone::Fi].
+    Fingrid,
+    /// spot-hinta.fi, free community api
+    SpotHinta,
+}
+
+/// Order used until the host configures one
+pub const DEFAULT_PRICE_SOURCES: [PriceSource; PriceSource::COUNT] = [
+    PriceSource::Entsoe,
+    PriceSource::SpotHinta,
+    PriceSource::Fingrid,
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum PriceSeriesError {
+    /// Series already has `N` points
+    Full,
+    /// Point does not start after the end of the previous point
+    NotSorted,
+}
+
+/// Consecutive [PricePoint]s of one bidding zone, ordered by start time.
+///
+/// Points keep the resolution they were published in, so a series can have both hourly and
+/// quarter-hourly points, for example around the day the market moved to 15 minute time units.
+/// Use [Self::resampled] to view them with a single resolution.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct PriceSeries<const N: usize = MAX_PRICE_POINTS> {
+    pub zone: BiddingZone,
+    pub currency: Currency,
+    /// Finest resolution of the points, updated by [Self::push]
+    pub resolution: Resolution,
+    points: Vec<PricePoint, N>,
+}
+
+impl<const N: usize> PriceSeries<N> {
+    pub const fn new(zone: BiddingZone, currency: Currency, resolution: Resolution) -> Self {
+        Self {
+            zone,
+            currency,
+            resolution,
+            points: Vec::new(),
+        }
+    }
+
+    /// Appends `point` to the end of the series.
+    ///
+    /// [Self::resolution] is set from the first point and lowered if `point` is finer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the series is full or `point` overlaps
+    /// or is before the last point.
+    pub fn push(&mut self, point: PricePoint) -> Result<(), PriceSeriesError> {
+        if self
+            .points
+            .last()
+            .is_some_and(|last| point.start < last.end())
+        {
+            return Err(PriceSeriesError::NotSorted);
+        }
+        self.points
+            .push(point)
+            .map_err(|_| PriceSeriesError::Full)?;
+        if let Some(resolution) = Resolution::from_seconds(point.duration) {
+            if self.points.len() == 1 || resolution < self.resolution {
+                self.resolution = resolution;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.points.clear();
+    }
+
+    /// Adds the points of `previous` that end before the first point of this series,
+    /// so that today's prices are kept when tomorrow's are fetched.
+    ///
+    /// Nothing is added if `previous` is of another zone or currency.
+    /// Oldest points are dropped if all of them do not fit.
+    pub fn keep_previous(&mut self, previous: &Self) {
+        if previous.zone != self.zone || previous.currency != self.currency {
+            return;
+        }
+        let first = self.points.first().map_or(Timestamp::MAX, |p| p.start);
+        let earlier = &previous.points[..previous.points.partition_point(|p| p.end() <= first)];
+        let room = N - self.points.len();
+        let earlier = &earlier[earlier.len().saturating_sub(room)..];
+
+        let mut merged = Self::new(self.zone, self.currency, self.resolution);
+        for point in earlier.iter().chain(&self.points) {
+            // Both are sorted and fit in the capacity
+            let _ = merged.push(*point);
+        }
+        *self = merged;
+    }
+
+    pub fn points(&self) -> &[PricePoint] {
+        &self.points
+    }
+
+    /// True if the series has prices of `zone` for the whole delivery day `date`, without
+    /// gaps. Sources such as Fingrid return only the past time units of today.
+    pub fn has_delivery_day(&self, zone: BiddingZone, date: Date) -> bool {
+        let (start, end) = delivery_period(date);
+        let first = self.points.partition_point(|p| p.start < start);
+        let last = self.points.partition_point(|p| p.start < end);
+        let day = &self.points[first..last];
+        self.zone == zone
+            && day.first().is_some_and(|p| p.start == start)
+            && day.last().is_some_and(|p| p.end() >= end)
+            && day.windows(2).all(|pair| pair[0].end() == pair[1].start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    /// Cheapest point, the earliest one if there are several
+    pub fn min(&self) -> Option<&PricePoint> {
+        self.points
+            .iter()
+            .reduce(|min, p| if p.price < min.price { p } else { min })
+    }
+
+    /// Most expensive point, the earliest one if there are several
+    pub fn max(&self) -> Option<&PricePoint> {
+        self.points
+            .iter()
+            .reduce(|max, p| if p.price > max.price { p } else { max })
+    }
+
+    /// Time weighted average price, rounded towards zero
+    pub fn average(&self) -> Option<i32> {
+        let (sum, duration) = self.points.iter().fold((0i64, 0i64), |(sum, duration), p| {
+            (
+                sum + p.price as i64 * p.duration as i64,
+                duration + p.duration as i64,
+            )
+        });
+        if duration == 0 {
+            return None;
+        }
+        Some((sum / duration) as i32)
+    }
+
+    /// Point whose time unit contains `instant`
+    pub fn at(&self, instant: Timestamp) -> Option<&PricePoint> {
+        let idx = self.points.partition_point(|p| p.end() <= instant);
+        self.points.get(idx).filter(|p| p.contains(instant))
+    }
+
+    /// Points split to local calendar days of `time_zone`, see [LocalDays]
+    pub fn local_days(&self, time_zone: TimeZone) -> LocalDays<'_> {
+        LocalDays {
+            points: &self.points,
+            time_zone,
+        }
+    }
+
+    /// Points converted to `resolution`, see [Resampled]
+    pub fn resampled(&self, resolution: Resolution) -> Resampled<'_> {
+        Resampled {
+            points: &self.points,
+            resolution,
+            next_start: None,
+        }
+    }
+}
+
+/// Iterator over the local calendar days of a [PriceSeries] and the points that start on them.
+///
+/// Days without points are skipped. A day can have 92, 96 or 100 quarter-hours,
+/// see [TimeZone::day].
+#[derive(Debug, Clone)]
+pub struct LocalDays<'a> {
+    points: &'a [PricePoint],
+    time_zone: TimeZone,
+}
+
+impl<'a> Iterator for LocalDays<'a> {
+    type Item = (Date, &'a [PricePoint]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let date = self.time_zone.date(self.points.first()?.start);
+        let (_, end) = self.time_zone.day(date);
+        let (day, rest) = self
+            .points
+            .split_at(self.points.partition_point(|p| p.start < end));
+        self.points = rest;
+        Some((date, day))
+    }
+}
+
+/// Iterator over the points of a [PriceSeries] in a single [Resolution].
+///
+/// Time units are aligned to whole hours or quarters in UTC, which are also whole in the
+/// local time of every [BiddingZone]. Price of each time unit is the time weighted average
+/// of the points it overlaps, so hourly points are repeated for each quarter and quarters
+/// are averaged to hours. Time units without any points are skipped, and a time unit
+/// that is only partially covered gets the average of the part that has points.
+#[derive(Debug, Clone)]
+pub struct Resampled<'a> {
+    /// Points that end after [Self::next_start]
+    points: &'a [PricePoint],
+    resolution: Resolution,
+    next_start: Option<Timestamp>,
+}
+
+impl Iterator for Resampled<'_> {
+    type Item = PricePoint;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let duration = self.resolution.seconds() as Timestamp;
+        let first = self.points.first()?;
+        // Jump over gaps to the time unit of the next point
+        let start = match self.next_start {
+            Some(next) if next > first.start - duration => next,
+            _ => first.start - first.start.rem_euclid(duration),
+        };
+        let end = start + duration;
+
+        let (mut sum, mut covered) = (0i64, 0i64);
+        for point in self.points.iter().take_while(|p| p.start < end) {
+            let overlap = point.end().min(end) - point.start.max(start);
+            sum += point.price as i64 * overlap;
+            covered += overlap;
+        }
+
+        let consumed = self.points.partition_point(|p| p.end() <= end);
+        self.points = &self.points[consumed..];
+        self.next_start = Some(end);
+
+        Some(PricePoint {
+            start,
+            duration: duration as u32,
+            // Points without length do not cover anything
+            price: (sum / covered.max(1)) as i32,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        delivery_period, publication_time, BiddingZone, Currency, DayAheadSchedule, PricePoint,
+        PriceSeries, PriceSeriesError, Resolution, Timestamp, MAX_PRICE_POINTS, RETRY_INTERVAL,
+    };
+    use strum::VariantArray;
+
+    use crate::time::{Date, TimeZone, UtcDateTime};
+
+    /// 2024-10-01T00:00:00Z
+    const START: Timestamp = 1_727_740_800;
+
+    fn hourly(prices: &[i32]) -> PriceSeries<24> {
+        let mut series = PriceSeries::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        for (i, price) in prices.iter().enumerate() {
+            series
+                .push(PricePoint {
+                    start: START + i as Timestamp * 3600,
+                    duration: 3600,
+                    price: *price,
+                })
+                .unwrap();
+        }
+        series
+    }
+
+    #[test]
+    fn min_max_average() {
+        let series = hourly(&[500, -120, 3000, -120, 4567]);
+        assert_eq!(series.min().unwrap().start, START + 3600);
+        assert_eq!(series.max().unwrap().price, 4567);
+        assert_eq!(series.average(), Some(1565));
+
+        let empty = hourly(&[]);
+        assert_eq!(empty.min(), None);
+        assert_eq!(empty.average(), None);
+    }
+
+    #[test]
+    fn lookup_by_instant() {
+        let series = hourly(&[10, 20, 30]);
+        assert_eq!(series.at(START - 1), None);
+        assert_eq!(series.at(START).unwrap().price, 10);
+        assert_eq!(series.at(START + 3599).unwrap().price, 10);
+        assert_eq!(series.at(START + 3600).unwrap().price, 20);
+        assert_eq!(series.at(START + 3 * 3600), None);
+    }
+
+    #[test]
+    fn lookup_with_gap() {
+        let mut series = hourly(&[10]);
+        series
+            .push(PricePoint {
+                start: START + 2 * 3600,
+                duration: 900,
+                price: 30,
+            })
+            .unwrap();
+        assert_eq!(series.at(START + 3600), None);
+        assert_eq!(series.at(START + 2 * 3600 + 899).unwrap().price, 30);
+    }
+
+    #[test]
+    fn push_rejects_overlapping_and_overflow() {
+        let mut series = hourly(&[10]);
+        let overlapping = PricePoint {
+            start: START + 1800,
+            duration: 3600,
+            price: 0,
+        };
+        assert_eq!(series.push(overlapping), Err(PriceSeriesError::NotSorted));
+
+        let mut series =
+            PriceSeries::<1>::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        let point = PricePoint {
+            start: START,
+            duration: 3600,
+            price: 0,
+        };
+        series.push(point).unwrap();
+        let next = PricePoint {
+            start: START + 3600,
+            ..point
+        };
+        assert_eq!(series.push(next), Err(PriceSeriesError::Full));
+    }
+
+    #[test]
+    fn keep_previous_day() {
+        let today = hourly(&[1, 2, 3]);
+        let mut tomorrow =
+            PriceSeries::<4>::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        for (i, price) in [4, 5].into_iter().enumerate() {
+            tomorrow
+                .push(PricePoint {
+                    start: START + (3 + i as Timestamp) * 3600,
+                    duration: 3600,
+                    price,
+                })
+                .unwrap();
+        }
+        let mut previous = PriceSeries::<4>::new(BiddingZone::Fi, Currency::Eur, today.resolution);
+        for point in today.points() {
+            previous.push(*point).unwrap();
+        }
+
+        // Oldest point does not fit
+        let mut merged = tomorrow.clone();
+        merged.keep_previous(&previous);
+        assert_eq!(prices(merged.points().iter().copied()), [2, 3, 4, 5]);
+
+        // Refetching the same day does not duplicate it
+        let mut refetched = tomorrow.clone();
+        refetched.keep_previous(&merged);
+        assert_eq!(prices(refetched.points().iter().copied()), [2, 3, 4, 5]);
+
+        let mut other_zone = tomorrow.clone();
+        other_zone.zone = BiddingZone::Se3;
+        other_zone.keep_previous(&previous);
+        assert_eq!(other_zone.len(), 2);
+    }
+
+    #[test]
+    fn keep_previous_day_when_summer_time_ends() {
+        let quarters = |date: Date| {
+            let (start, end) = delivery_period(date);
+            let mut series = PriceSeries::<MAX_PRICE_POINTS>::new(
+                BiddingZone::Fi,
+                Currency::Eur,
+                Resolution::Minutes15,
+            );
+            for t in (start..end).step_by(900) {
+                series
+                    .push(PricePoint {
+                        start: t,
+                        duration: 900,
+                        price: 0,
+                    })
+                    .unwrap();
+            }
+            series
+        };
+        // 25 hours in CET
+        let today = quarters(Date::new(2025, 10, 26).unwrap());
+        assert_eq!(today.len(), 100);
+        let mut tomorrow = quarters(Date::new(2025, 10, 27).unwrap());
+        assert_eq!(tomorrow.len(), 96);
+
+        tomorrow.keep_previous(&today);
+        assert_eq!(tomorrow.len(), 196);
+        assert_eq!(tomorrow.points()[0], today.points()[0]);
+    }
+
+    #[test]
+    fn series_roundtrip() {
+        let series = hourly(&[1, -2, 3]);
+        let mut buf = [0u8; 128];
+        let encoded = postcard::to_slice(&series, &mut buf).unwrap();
+        let decoded: PriceSeries<24> = postcard::from_bytes(encoded).unwrap();
+        assert_eq!(decoded, series);
+    }
+
+    #[test]
+    fn series_split_to_local_days() {
+        // Quarter-hours from 2025-03-29T00:00 to 2025-03-31T00:00 in Helsinki
+        let start = point("2025-03-28T22:00Z", 15, 0).start;
+        let end = point("2025-03-30T21:00Z", 15, 0).start;
+        let mut series =
+            PriceSeries::<192>::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes15);
+        for t in (start..end).step_by(900) {
+            let price = (t / 900 % 100) as i32;
+            series
+                .push(PricePoint {
+                    start: t,
+                    duration: 900,
+                    price,
+                })
+                .unwrap();
+        }
+
+        let days: std::vec::Vec<_> = series.local_days(TimeZone::Eet).collect();
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0].0, Date::new(2025, 3, 29).unwrap());
+        assert_eq!(days[0].1.len(), 96);
+        // Summer time starts
+        assert_eq!(days[1].0, Date::new(2025, 3, 30).unwrap());
+        assert_eq!(days[1].1.len(), 92);
+
+        // Same points are split differently in Stockholm, an hour later
+        let days: std::vec::Vec<_> = series.local_days(TimeZone::Cet).collect();
+        let lengths: std::vec::Vec<_> = days.iter().map(|(_, points)| points.len()).collect();
+        assert_eq!(lengths, [4, 96, 88]);
+        assert_eq!(days[0].0, Date::new(2025, 3, 28).unwrap());
+
+        assert_eq!(series_of(&[]).local_days(TimeZone::Eet).count(), 0);
+        assert_eq!(BiddingZone::Fi.time_zone(), TimeZone::Eet);
+        assert_eq!(BiddingZone::Se3.time_zone(), TimeZone::Cet);
+    }
+
+    #[test]
+    fn delivery_days_in_cet() {
+        let time = |s| UtcDateTime::parse_iso8601(s).unwrap().timestamp();
+        let period = |y, m, d| delivery_period(Date::new(y, m, d).unwrap());
+
+        assert_eq!(
+            period(2024, 10, 2),
+            (time("2024-10-01T22:00Z"), time("2024-10-02T22:00Z"))
+        );
+        assert_eq!(
+            period(2024, 12, 24),
+            (time("2024-12-23T23:00Z"), time("2024-12-24T23:00Z"))
+        );
+        let (start, end) = period(2024, 3, 31);
+        assert_eq!(end - start, 23 * 3600);
+        let (start, end) = period(2024, 10, 27);
+        assert_eq!(end - start, 25 * 3600);
+    }
+
+    #[test]
+    fn day_ahead_schedule() {
+        let time = |s| UtcDateTime::parse_iso8601(s).unwrap().timestamp();
+        let today = Date::new(2025, 1, 15).unwrap();
+        let tomorrow = today.next();
+        let published = time("2025-01-15T12:00Z");
+        assert_eq!(publication_time(today), published);
+        // 13:00 CEST
+        assert_eq!(
+            publication_time(Date::new(2025, 3, 30).unwrap()),
+            time("2025-03-30T11:00Z")
+        );
+
+        let mut series =
+            PriceSeries::<48>::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        let morning = time("2025-01-15T08:00Z");
+        let schedule = DayAheadSchedule::new(&series, BiddingZone::Fi, morning);
+        assert_eq!(schedule.dates, [today]);
+        assert_eq!(schedule.next_check, morning + RETRY_INTERVAL);
+
+        let (start, _) = delivery_period(today);
+        for hour in 0..24 {
+            series
+                .push(PricePoint {
+                    start: start + hour * 3600,
+                    duration: 3600,
+                    price: 0,
+                })
+                .unwrap();
+        }
+        let schedule = DayAheadSchedule::new(&series, BiddingZone::Fi, morning);
+        assert!(schedule.dates.is_empty());
+        assert_eq!(schedule.next_check, published);
+
+        // Tomorrow's prices after they are published
+        let schedule = DayAheadSchedule::new(&series, BiddingZone::Fi, published);
+        assert_eq!(schedule.dates, [tomorrow]);
+        assert_eq!(schedule.next_check, published + RETRY_INTERVAL);
+
+        // Only the first hours of tomorrow
+        let (start, _) = delivery_period(tomorrow);
+        for hour in 0..12 {
+            series
+                .push(PricePoint {
+                    start: start + hour * 3600,
+                    duration: 3600,
+                    price: 0,
+                })
+                .unwrap();
+        }
+        let schedule = DayAheadSchedule::new(&series, BiddingZone::Fi, published);
+        assert_eq!(schedule.dates, [tomorrow]);
+
+        for hour in 12..24 {
+            series
+                .push(PricePoint {
+                    start: start + hour * 3600,
+                    duration: 3600,
+                    price: 0,
+                })
+                .unwrap();
+        }
+        let schedule = DayAheadSchedule::new(&series, BiddingZone::Fi, published);
+        assert!(schedule.dates.is_empty());
+        assert_eq!(schedule.next_check, publication_time(tomorrow));
+
+        // Prices of another zone are fetched again
+        let schedule = DayAheadSchedule::new(&series, BiddingZone::Se3, published);
+        assert_eq!(schedule.dates, [today, tomorrow]);
+    }
+
+    #[test]
+    fn delivery_day_coverage() {
+        let date = Date::new(2025, 1, 15).unwrap();
+        let hours = |hours: &[i64]| {
+            let (start, _) = delivery_period(date);
+            let mut series =
+                PriceSeries::<24>::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+            for hour in hours {
+                series
+                    .push(PricePoint {
+                        start: start + hour * 3600,
+                        duration: 3600,
+                        price: 0,
+                    })
+                    .unwrap();
+            }
+            series
+        };
+        let whole_day: std::vec::Vec<i64> = (0..24).collect();
+        assert!(hours(&whole_day).has_delivery_day(BiddingZone::Fi, date));
+        assert!(!hours(&whole_day).has_delivery_day(BiddingZone::Se3, date));
+        assert!(!hours(&whole_day).has_delivery_day(BiddingZone::Fi, date.next()));
+        // Past hours of today
+        assert!(!hours(&whole_day[..10]).has_delivery_day(BiddingZone::Fi, date));
+        assert!(!hours(&whole_day[1..]).has_delivery_day(BiddingZone::Fi, date));
+        let gap: std::vec::Vec<i64> = (0..24).filter(|hour| *hour != 12).collect();
+        assert!(!hours(&gap).has_delivery_day(BiddingZone::Fi, date));
+    }
+
+    #[test]
+    fn zone_codes() {
+        for zone in BiddingZone::VARIANTS {
+            assert_eq!(BiddingZone::from_code(zone.code()), Some(*zone));
+            assert_eq!(zone.eic().len(), 16);
+        }
+        assert_eq!(BiddingZone::from_code("SE3"), Some(BiddingZone::Se3));
+        assert_eq!(BiddingZone::from_code("se3"), None);
+        assert_eq!(BiddingZone::from_code("DE-LU"), None);
+    }
+
+    fn point(start: &str, minutes: u32, price: i32) -> PricePoint {
+        PricePoint {
+            start: UtcDateTime::parse_iso8601(start).unwrap().timestamp(),
+            duration: minutes * 60,
+            price,
+        }
+    }
+
+    fn series_of(points: &[PricePoint]) -> PriceSeries<24> {
+        let mut series = PriceSeries::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        for p in points {
+            series.push(*p).unwrap();
+        }
+        series
+    }
+
+    fn prices(points: impl Iterator<Item = PricePoint>) -> std::vec::Vec<i32> {
+        points.map(|p| p.price).collect()
+    }
+
+    #[test]
+    fn push_keeps_finest_resolution() {
+        let mut series =
+            PriceSeries::<8>::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes15);
+        series.push(point("2025-09-30T20:00Z", 60, 0)).unwrap();
+        assert_eq!(series.resolution, Resolution::Minutes60);
+        series.push(point("2025-09-30T21:00Z", 15, 0)).unwrap();
+        assert_eq!(series.resolution, Resolution::Minutes15);
+        series.push(point("2025-09-30T21:15Z", 60, 0)).unwrap();
+        assert_eq!(series.resolution, Resolution::Minutes15);
+    }
+
+    #[test]
+    fn mixed_resolutions_in_both_views() {
+        let series = series_of(&[
+            point("2025-09-30T20:00Z", 60, 1000),
+            point("2025-09-30T21:00Z", 60, -2000),
+            point("2025-09-30T22:00Z", 15, 100),
+            point("2025-09-30T22:15Z", 15, 200),
+            point("2025-09-30T22:30Z", 15, 300),
+            point("2025-09-30T22:45Z", 15, 401),
+        ]);
+
+        let hourly: std::vec::Vec<_> = series.resampled(Resolution::Minutes60).collect();
+        assert_eq!(prices(hourly.iter().copied()), [1000, -2000, 250]);
+        assert!(hourly.iter().all(|p| p.duration == 3600));
+        assert_eq!(hourly[2].start, series.points()[2].start);
+
+        let quarters: std::vec::Vec<_> = series.resampled(Resolution::Minutes15).collect();
+        assert_eq!(
+            prices(quarters.iter().copied()),
+            [1000, 1000, 1000, 1000, -2000, -2000, -2000, -2000, 100, 200, 300, 401]
+        );
+        assert_eq!(quarters[1].start - quarters[0].start, 900);
+        assert_eq!(&quarters[8..], &series.points()[2..]);
+    }
+
+    #[test]
+    fn resampling_partial_hours_and_gaps() {
+        let series = series_of(&[
+            point("2025-10-01T10:15Z", 15, 100),
+            point("2025-10-01T10:30Z", 15, 200),
+            point("2025-10-01T13:45Z", 15, -5),
+            point("2025-10-01T14:00Z", 15, -2),
+        ]);
+        let hourly: std::vec::Vec<_> = series.resampled(Resolution::Minutes60).collect();
+        // Average of the covered half hour, empty hours are skipped
+        assert_eq!(prices(hourly.iter().copied()), [150, -5, -2]);
+        assert_eq!(hourly[0].start, point("2025-10-01T10:00Z", 0, 0).start);
+        assert_eq!(hourly[1].start, point("2025-10-01T13:00Z", 0, 0).start);
+
+        // Hour that overlaps half of a point that started before it
+        let series = series_of(&[
+            point("2025-10-01T10:30Z", 60, 400),
+            point("2025-10-01T11:30Z", 30, 100),
+        ]);
+        let hourly: std::vec::Vec<_> = series.resampled(Resolution::Minutes60).collect();
+        assert_eq!(prices(hourly.iter().copied()), [400, 250]);
+
+        assert_eq!(series_of(&[]).resampled(Resolution::Minutes15).count(), 0);
+    }
+}
@@ -0,0 +1,276 @@
+//! Latest [PriceSeries] kept in flash, so that prices can be shown after a reboot before
+//! the network is up.
+//!
+//! Series are pushed to a [sequential_storage] queue that overwrites the oldest ones when it
+//! is full, and the newest one that can be decoded is loaded. The flash range must be
+//! reserved for this queue only, at least two erase pages long.
+//!
+//! The [History] of daily statistics is kept the same way in a queue of its own, one
+//! [DailyStats] per entry, and loaded by replaying every entry that can be decoded.
+
+use core::ops::Range;
+
+use embedded_storage_async::nor_flash::NorFlash;
+use sequential_storage::{cache::NoCache, queue};
+
+use serde::Serialize;
+
+use crate::{
+    price::{PriceSeries, MAX_PRICE_POINTS},
+    statistics::{DailyStats, History, MAX_DAY_ENCODED_SIZE},
+};
+
+/// Size of the buffer needed by [save_series] and [load_series]. Encoded [PricePoint]s
+/// take at most 20 bytes, rounded up to whole flash words.
+///
+/// [PricePoint]: crate::price::PricePoint
+pub const SERIES_BUFFER_SIZE: usize = (16 + MAX_PRICE_POINTS * 20).next_multiple_of(32);
+
+/// Size of the buffer needed by [save_day] and [load_history], rounded up to whole flash words
+pub const DAY_BUFFER_SIZE: usize = MAX_DAY_ENCODED_SIZE.next_multiple_of(32);
+
+#[derive(Debug, PartialEq)]
+pub enum PriceStoreError<E> {
+    Storage(sequential_storage::Error<E>),
+    /// Series did not fit the buffer
+    Serialization,
+}
+
+impl<E> From<sequential_storage::Error<E>> for PriceStoreError<E> {
+    fn from(value: sequential_storage::Error<E>) -> Self {
+        Self::Storage(value)
+    }
+}
+
+/// Stores `series` as the newest entry of the queue in `range` of `flash`.
+///
+/// `buffer` must be at least [SERIES_BUFFER_SIZE] long.
+pub async fn save_series<S: NorFlash>(
+    flash: &mut S,
+    range: Range<u32>,
+    series: &PriceSeries,
+    buffer: &mut [u8],
+) -> Result<(), PriceStoreError<S::Error>> {
+    push(flash, range, series, buffer).await
+}
+
+/// Returns the newest series stored with [save_series], [None] if there is none.
+///
+/// Entries that can not be decoded, for example ones written by an older firmware,
+/// are skipped. `buffer` must be at least [SERIES_BUFFER_SIZE] long.
+pub async fn load_series<S: NorFlash>(
+    flash: &mut S,
+    range: Range<u32>,
+    buffer: &mut [u8],
+) -> Result<Option<PriceSeries>, PriceStoreError<S::Error>> {
+    let mut cache = NoCache::new();
+    let mut entries = queue::iter(flash, range, &mut cache).await?;
+    let mut newest = None;
+    while let Some(entry) = entries.next(buffer).await? {
+        if let Ok(series) = postcard::from_bytes(&entry) {
+            newest = Some(series);
+        }
+    }
+    Ok(newest)
+}
+
+/// Stores `day` as the newest entry of the history queue in `range` of `flash`.
+/// A day that is saved again replaces the older entry when the history is loaded.
+///
+/// `buffer` must be at least [DAY_BUFFER_SIZE] long.
+pub async fn save_day<S: NorFlash>(
+    flash: &mut S,
+    range: Range<u32>,
+    day: &DailyStats,
+    buffer: &mut [u8],
+) -> Result<(), PriceStoreError<S::Error>> {
+    push(flash, range, day, buffer).await
+}
+
+/// Returns the [History] of the days stored with [save_day], entries that can not be
+/// decoded are skipped. `buffer` must be at least [DAY_BUFFER_SIZE] long.
+pub async fn load_history<S: NorFlash>(
+    flash: &mut S,
+    range: Range<u32>,
+    buffer: &mut [u8],
+) -> Result<History, PriceStoreError<S::Error>> {
+    let mut cache = NoCache::new();
+    let mut entries = queue::iter(flash, range, &mut cache).await?;
+    let mut history = History::new();
+    while let Some(entry) = entries.next(buffer).await? {
+        if let Ok(day) = postcard::from_bytes(&entry) {
+            history.insert(day);
+        }
+    }
+    Ok(history)
+}
+
+async fn push<S: NorFlash, T: Serialize>(
+    flash: &mut S,
+    range: Range<u32>,
+    value: &T,
+    buffer: &mut [u8],
+) -> Result<(), PriceStoreError<S::Error>> {
+    let data = postcard::to_slice(value, buffer).map_err(|_| PriceStoreError::Serialization)?;
+    queue::push(flash, range, &mut NoCache::new(), data, true).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use embassy_futures::block_on;
+
+    use super::{
+        load_history, load_series, save_day, save_series, DAY_BUFFER_SIZE, SERIES_BUFFER_SIZE,
+    };
+    use crate::{
+        mock_flash::{MockFlash, PAGE_SIZE},
+        price::{
+            BiddingZone, Currency, PricePoint, PriceSeries, Resolution, Timestamp, MAX_PRICE_POINTS,
+        },
+        statistics::{DailyStats, HISTORY_DAYS},
+        time::Date,
+    };
+
+    /// 2025-03-01T00:00:00Z
+    const START: Timestamp = 1_740_787_200;
+
+    fn quarters(day: Timestamp, count: usize, price: i32) -> PriceSeries {
+        let mut series = PriceSeries::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes15);
+        for i in 0..count {
+            series
+                .push(PricePoint {
+                    start: START + day * 86_400 + i as Timestamp * 900,
+                    duration: 900,
+                    // Largest encoded prices
+                    price: price - i as i32,
+                })
+                .unwrap();
+        }
+        series
+    }
+
+    #[test]
+    fn empty_flash_has_no_series() {
+        let mut flash = MockFlash::new(4);
+        let mut buffer = [0u8; SERIES_BUFFER_SIZE];
+        let loaded = block_on(load_series(
+            &mut flash,
+            0..4 * PAGE_SIZE as u32,
+            &mut buffer,
+        ));
+        assert_eq!(loaded, Ok(None));
+    }
+
+    #[test]
+    fn newest_series_is_loaded() {
+        let mut flash = MockFlash::new(4);
+        let range = PAGE_SIZE as u32..3 * PAGE_SIZE as u32;
+        let mut buffer = [0u8; SERIES_BUFFER_SIZE];
+
+        let today = quarters(0, 96, 10_000);
+        let full = quarters(1, MAX_PRICE_POINTS, i32::MIN / 2);
+        block_on(async {
+            save_series(&mut flash, range.clone(), &today, &mut buffer)
+                .await
+                .unwrap();
+            let loaded = load_series(&mut flash, range.clone(), &mut buffer).await;
+            assert_eq!(loaded, Ok(Some(today)));
+
+            save_series(&mut flash, range.clone(), &full, &mut buffer)
+                .await
+                .unwrap();
+            let loaded = load_series(&mut flash, range.clone(), &mut buffer).await;
+            assert_eq!(loaded, Ok(Some(full)));
+        });
+
+        // Nothing is written outside the range
+        assert!(flash.data[..PAGE_SIZE].iter().all(|b| *b == 0xFF));
+        assert!(flash.data[3 * PAGE_SIZE..].iter().all(|b| *b == 0xFF));
+    }
+
+    #[test]
+    fn oldest_series_are_overwritten() {
+        let mut flash = MockFlash::new(3);
+        let range = 0..3 * PAGE_SIZE as u32;
+        let mut buffer = [0u8; SERIES_BUFFER_SIZE];
+
+        // Full series takes a page, so the queue wraps around many times
+        for day in 0..10 {
+            let series = quarters(day, MAX_PRICE_POINTS, -(day as i32));
+            block_on(save_series(&mut flash, range.clone(), &series, &mut buffer)).unwrap();
+            let loaded = block_on(load_series(&mut flash, range.clone(), &mut buffer));
+            assert_eq!(loaded, Ok(Some(series)));
+        }
+        assert!(flash.erases > 0);
+    }
+
+    #[test]
+    fn undecodable_entries_are_skipped() {
+        let mut flash = MockFlash::new(2);
+        let range = 0..2 * PAGE_SIZE as u32;
+        let mut buffer = [0u8; SERIES_BUFFER_SIZE];
+
+        let series = quarters(0, 4, 100);
+        block_on(async {
+            save_series(&mut flash, range.clone(), &series, &mut buffer)
+                .await
+                .unwrap();
+            sequential_storage::queue::push(
+                &mut flash,
+                range.clone(),
+                &mut sequential_storage::cache::NoCache::new(),
+                &[0xFF; 3],
+                true,
+            )
+            .await
+            .unwrap();
+            let loaded = load_series(&mut flash, range.clone(), &mut buffer).await;
+            assert_eq!(loaded, Ok(Some(series)));
+        });
+    }
+
+    fn day(days: Timestamp, price: i32) -> DailyStats {
+        DailyStats {
+            date: Date::from_days(days),
+            average: price,
+            min: i32::MIN,
+            max: i32::MAX,
+            hourly: [price; 24],
+        }
+    }
+
+    #[test]
+    fn history_is_replayed() {
+        let mut flash = MockFlash::new(2);
+        let range = 0..2 * PAGE_SIZE as u32;
+        let mut buffer = [0u8; DAY_BUFFER_SIZE];
+        let first = 20_000;
+
+        block_on(async {
+            let history = load_history(&mut flash, range.clone(), &mut buffer).await;
+            assert_eq!(history.unwrap().days(), &[]);
+
+            // More days than fit the history and the queue, largest encoded prices
+            for i in 0..100 {
+                let day = day(first + i, i32::MIN + i as i32);
+                save_day(&mut flash, range.clone(), &day, &mut buffer)
+                    .await
+                    .unwrap();
+            }
+            // Day saved again replaces the older entry
+            let today = day(first + 99, 5);
+            save_day(&mut flash, range.clone(), &today, &mut buffer)
+                .await
+                .unwrap();
+
+            let history = load_history(&mut flash, range.clone(), &mut buffer)
+                .await
+                .unwrap();
+            assert_eq!(history.days().len(), HISTORY_DAYS);
+            assert_eq!(history.days()[0].date, Date::from_days(first + 69));
+            assert_eq!(history.latest(), Some(&today));
+        });
+        assert!(flash.erases > 0);
+    }
+}
@@ -0,0 +1,317 @@
+//! Rules that switch relays, for example of a water heater, based on prices and time of day.
+//!
+//! Rules are checked in order for each time unit and the first one whose [Condition] holds
+//! sets the state of its relay. A relay without any matching rule is off, so a rule list like
+//!
+//! 1. on between 06:00 and 07:00
+//! 2. off above 20 c/kWh
+//! 3. on during the cheapest 4 hours
+//!
+//! keeps the heater on every morning and during the cheapest hours of the day, unless they
+//! are too expensive.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    planner::cheapest_slots,
+    price::{PricePoint, PriceSeries, Resolution, Timestamp},
+    tariff::Tariff,
+    time_of_use::MINUTES_PER_DAY,
+    ValidationError,
+};
+
+/// Maximum number of relays rules can switch
+pub const MAX_RELAYS: usize = 4;
+
+/// Maximum number of [RelayRule]s
+pub const MAX_RELAY_RULES: usize = 8;
+
+/// When a [RelayRule] applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum Condition {
+    /// Time unit is one of the `count` cheapest of its local day in `resolution`.
+    /// If fewer prices of the day are known, all of them are the cheapest.
+    Cheapest { count: u16, resolution: Resolution },
+    /// Consumer price is above the limit, in the unit of [Tariff::retail_price_of]
+    PriceAbove(i32),
+    /// Consumer price is below the limit, in the unit of [Tariff::retail_price_of]
+    PriceBelow(i32),
+    /// Local time is between `start` and `end` minutes after midnight, end excluded.
+    /// Window continues over midnight if it ends before it starts.
+    Between { start: u16, end: u16 },
+}
+
+/// Sets the relay `relay` on or off when `condition` holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct RelayRule {
+    /// Index of the relay, less than [MAX_RELAYS]
+    pub relay: u8,
+    pub condition: Condition,
+    pub on: bool,
+}
+
+impl RelayRule {
+    /// Checks that the relay exists, cheapest count is within a day and the window is
+    /// within a day
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let valid = match self.condition {
+            Condition::Cheapest { count, .. } => count > 0 && count <= 100,
+            Condition::PriceAbove(_) | Condition::PriceBelow(_) => true,
+            Condition::Between { start, end } => start < MINUTES_PER_DAY && end <= MINUTES_PER_DAY,
+        };
+        if !valid || self.relay as usize >= MAX_RELAYS {
+            return Err(ValidationError::OutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Returns true if the condition holds at `now`.
+    ///
+    /// Price conditions do not hold if the price at `now` is not known.
+    pub fn applies<const N: usize>(
+        &self,
+        now: Timestamp,
+        series: &PriceSeries<N>,
+        tariff: &Tariff,
+    ) -> bool {
+        let time_zone = series.zone.time_zone();
+        let price = || series.at(now).map(|p| tariff.retail_price_of(p));
+        match self.condition {
+            Condition::Cheapest { count, resolution } => {
+                let (start, end) = time_zone.day(time_zone.date(now));
+                let in_day = |p: &PricePoint| p.start >= start && p.end() <= end;
+                let known = series.resampled(resolution).filter(in_day).count();
+                let count = (count as usize).min(known);
+                let duration = resolution.seconds() as Timestamp;
+                cheapest_slots(series.resampled(resolution), count, start, end).is_some_and(
+                    |plan| {
+                        plan.slots()
+                            .any(|slot| slot <= now && now < slot + duration)
+                    },
+                )
+            }
+            Condition::PriceAbove(limit) => price().is_some_and(|p| p > limit),
+            Condition::PriceBelow(limit) => price().is_some_and(|p| p < limit),
+            Condition::Between { start, end } => {
+                let local = time_zone.to_local(now);
+                let minute = local.hour as u16 * 60 + local.minute as u16;
+                if start <= end {
+                    start <= minute && minute < end
+                } else {
+                    minute >= start || minute < end
+                }
+            }
+        }
+    }
+}
+
+/// States of all relays at `now`, see the [module documentation](self)
+pub fn relay_states<const N: usize>(
+    rules: &[RelayRule],
+    now: Timestamp,
+    series: &PriceSeries<N>,
+    tariff: &Tariff,
+) -> [bool; MAX_RELAYS] {
+    let mut states = [false; MAX_RELAYS];
+    let mut decided = [false; MAX_RELAYS];
+    for rule in rules {
+        let relay = rule.relay as usize;
+        if relay < MAX_RELAYS && !decided[relay] && rule.applies(now, series, tariff) {
+            states[relay] = rule.on;
+            decided[relay] = true;
+        }
+    }
+    states
+}
+
+/// Rules of every relay in the order they are checked
+pub type RelayRules = Vec<RelayRule, MAX_RELAY_RULES>;
+
+#[cfg(test)]
+mod tests {
+    use super::{relay_states, Condition, RelayRule};
+    use crate::{
+        price::{BiddingZone, Currency, PricePoint, PriceSeries, Resolution, Timestamp},
+        tariff::{Tariff, TaxClass, CENT_PER_KWH},
+        time_of_use::TransferSchedule,
+        ValidationError,
+    };
+
+    /// 2025-01-15 00:00 in Finland
+    const MIDNIGHT: Timestamp = 1_736_892_000;
+    const HOUR: Timestamp = 3600;
+
+    /// Transfer fee cancels the tax so that the consumer price is the spot price
+    const SPOT: Tariff = Tariff {
+        vat: 0,
+        margin: 0,
+        tax_class: TaxClass::II,
+        transfer_fee: -63,
+        transfer_schedule: TransferSchedule::Flat,
+    };
+
+    /// Hourly prices of a day in c/kWh, cheapest at night and most expensive in the evening
+    fn series() -> PriceSeries {
+        let cents = [
+            3, 2, 1, 1, 2, 4, 8, 12, 15, 14, 12, 10, 9, 9, 10, 12, 18, 25, 30, 24, 16, 10, 6, 4,
+        ];
+        let mut series = PriceSeries::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        for (hour, c) in cents.iter().enumerate() {
+            series
+                .push(PricePoint {
+                    start: MIDNIGHT + hour as Timestamp * HOUR,
+                    duration: HOUR as u32,
+                    price: c * CENT_PER_KWH,
+                })
+                .unwrap();
+        }
+        series
+    }
+
+    /// Hours of the day when relay 0 is on
+    fn hours_on(rules: &[RelayRule]) -> std::vec::Vec<Timestamp> {
+        let series = series();
+        (0..24)
+            .filter(|h| relay_states(rules, MIDNIGHT + h * HOUR + 1800, &series, &SPOT)[0])
+            .collect()
+    }
+
+    const CHEAPEST_4_HOURS: RelayRule = RelayRule {
+        relay: 0,
+        condition: Condition::Cheapest {
+            count: 4,
+            resolution: Resolution::Minutes60,
+        },
+        on: true,
+    };
+
+    #[test]
+    fn cheapest_hours() {
+        assert_eq!(hours_on(&[CHEAPEST_4_HOURS]), [1, 2, 3, 4]);
+
+        // Quarters of the same day
+        let quarters = RelayRule {
+            condition: Condition::Cheapest {
+                count: 6,
+                resolution: Resolution::Minutes15,
+            },
+            ..CHEAPEST_4_HOURS
+        };
+        // Hours 02 and 03 are equally cheap, so the quarters are 02:00 - 03:30
+        assert_eq!(hours_on(&[quarters]), [2]);
+        let series = series();
+        assert!(relay_states(&[quarters], MIDNIGHT + 3 * HOUR + 900, &series, &SPOT)[0]);
+        assert!(!relay_states(&[quarters], MIDNIGHT + 3 * HOUR + 1800, &series, &SPOT)[0]);
+
+        // Nothing is known of the next day
+        let tomorrow = MIDNIGHT + 24 * HOUR + 1800;
+        assert_eq!(
+            relay_states(&[CHEAPEST_4_HOURS], tomorrow, &series, &SPOT),
+            [false; 4]
+        );
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let morning = RelayRule {
+            relay: 0,
+            condition: Condition::Between {
+                start: 6 * 60,
+                end: 7 * 60,
+            },
+            on: true,
+        };
+        let expensive = RelayRule {
+            relay: 0,
+            condition: Condition::PriceAbove(20 * CENT_PER_KWH),
+            on: false,
+        };
+        let cheap = RelayRule {
+            relay: 0,
+            condition: Condition::PriceBelow(5 * CENT_PER_KWH),
+            on: true,
+        };
+        assert_eq!(
+            hours_on(&[morning, expensive, CHEAPEST_4_HOURS]),
+            [1, 2, 3, 4, 6]
+        );
+        assert_eq!(hours_on(&[expensive, cheap]), [0, 1, 2, 3, 4, 5, 23]);
+
+        // Rules of other relays do not affect relay 0
+        let other = RelayRule { relay: 1, ..cheap };
+        let series = series();
+        let night = MIDNIGHT + 2 * HOUR;
+        assert_eq!(
+            relay_states(&[other, morning], night, &series, &SPOT),
+            [false, true, false, false]
+        );
+    }
+
+    #[test]
+    fn price_with_night_transfer_fee() {
+        let cheap = RelayRule {
+            relay: 0,
+            condition: Condition::PriceBelow(10 * CENT_PER_KWH),
+            on: true,
+        };
+        // 5 c/kWh more during the day
+        let flat = Tariff {
+            transfer_fee: SPOT.transfer_fee + 5 * CENT_PER_KWH,
+            ..SPOT
+        };
+        let night = Tariff {
+            transfer_schedule: TransferSchedule::Night(SPOT.transfer_fee),
+            ..flat
+        };
+        let series = series();
+        let hours_on = |tariff: &Tariff| {
+            (0..24)
+                .filter(|h| relay_states(&[cheap], MIDNIGHT + h * HOUR + 1800, &series, tariff)[0])
+                .collect::<std::vec::Vec<Timestamp>>()
+        };
+        assert_eq!(hours_on(&flat), [0, 1, 2, 3, 4, 5, 23]);
+        assert_eq!(hours_on(&night), [0, 1, 2, 3, 4, 5, 6, 22, 23]);
+    }
+
+    #[test]
+    fn window_over_midnight() {
+        let night = RelayRule {
+            relay: 0,
+            condition: Condition::Between {
+                start: 22 * 60,
+                end: 6 * 60,
+            },
+            on: true,
+        };
+        assert_eq!(hours_on(&[night]), [0, 1, 2, 3, 4, 5, 22, 23]);
+    }
+
+    #[test]
+    fn rule_validation() {
+        assert_eq!(CHEAPEST_4_HOURS.validate(), Ok(()));
+        let relay = RelayRule {
+            relay: 4,
+            ..CHEAPEST_4_HOURS
+        };
+        assert_eq!(relay.validate(), Err(ValidationError::OutOfRange));
+        let window = RelayRule {
+            condition: Condition::Between {
+                start: 24 * 60,
+                end: 0,
+            },
+            ..CHEAPEST_4_HOURS
+        };
+        assert_eq!(window.validate(), Err(ValidationError::OutOfRange));
+        let none = RelayRule {
+            condition: Condition::Cheapest {
+                count: 0,
+                resolution: Resolution::Minutes60,
+            },
+            ..CHEAPEST_4_HOURS
+        };
+        assert_eq!(none.validate(), Err(ValidationError::OutOfRange));
+    }
+}
@@ -0,0 +1,346 @@
+//! Rolling history of daily prices, used to judge whether a day is cheap relative to
+//! recent days rather than in absolute terms.
+//!
+//! Each complete local day of fetched prices is summarized to a [DailyStats] and kept in
+//! a [History] of the last [HISTORY_DAYS] days, which gives [Statistics] for any day.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    price::{PricePoint, Timestamp},
+    time::{Date, TimeZone},
+};
+
+/// Number of days kept in a [History]
+pub const HISTORY_DAYS: usize = 31;
+
+/// Size of the largest encoded [Statistics]. Encoded [Date] takes at most 7 bytes, `i32`
+/// 5 bytes and [Option] one byte more than its value.
+pub const MAX_ENCODED_SIZE: usize = 7 + 1 + (1 + MAX_DAY_ENCODED_SIZE) + 3 * (1 + 5) + (1 + 24 * 5);
+
+/// Size of the largest encoded [DailyStats]
+pub const MAX_DAY_ENCODED_SIZE: usize = 7 + 3 * 5 + 24 * 5;
+
+/// Days before the current one averaged for [Statistics::week_average]
+const WEEK_DAYS: Timestamp = 7;
+
+/// Prices of one local day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct DailyStats {
+    pub date: Date,
+    /// Time weighted average of the day
+    pub average: i32,
+    pub min: i32,
+    pub max: i32,
+    /// Average of each local hour. The hour skipped when summer time starts gets
+    /// the average of the day and the hour repeated when it ends is averaged.
+    pub hourly: [i32; 24],
+}
+
+impl DailyStats {
+    /// Summary of the local day `date` in `time_zone` from `points` that start on it,
+    /// like the days of [PriceSeries::local_days](crate::price::PriceSeries::local_days).
+    ///
+    /// Returns [None] unless `points` cover the whole day without gaps.
+    pub fn new(date: Date, points: &[PricePoint], time_zone: TimeZone) -> Option<Self> {
+        let (start, end) = time_zone.day(date);
+        let mut next = start;
+        for point in points {
+            if point.start != next {
+                return None;
+            }
+            next = point.end();
+        }
+        if next != end {
+            return None;
+        }
+
+        let mut sums = [(0i64, 0i64); 24];
+        for point in points {
+            let hour = time_zone.to_local(point.start).hour as usize;
+            sums[hour].0 += point.price as i64 * point.duration as i64;
+            sums[hour].1 += point.duration as i64;
+        }
+        let (sum, duration) = sums
+            .iter()
+            .fold((0, 0), |(sum, duration), (s, d)| (sum + s, duration + d));
+        let average = (sum / duration) as i32;
+
+        Some(Self {
+            date,
+            average,
+            min: points.iter().map(|p| p.price).min()?,
+            max: points.iter().map(|p| p.price).max()?,
+            hourly: sums.map(|(sum, duration)| match duration {
+                0 => average,
+                _ => (sum / duration) as i32,
+            }),
+        })
+    }
+}
+
+/// [DailyStats] of the latest [HISTORY_DAYS] days in date order
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub struct History {
+    days: Vec<DailyStats, HISTORY_DAYS>,
+}
+
+impl History {
+    pub const fn new() -> Self {
+        Self { days: Vec::new() }
+    }
+
+    pub fn days(&self) -> &[DailyStats] {
+        &self.days
+    }
+
+    /// Newest day in the history
+    pub fn latest(&self) -> Option<&DailyStats> {
+        self.days.last()
+    }
+
+    /// Adds `day` or replaces the day with the same date, dropping the oldest day if the
+    /// history is full. Days older than all the others are ignored when it is full.
+    ///
+    /// Returns true if the history changed.
+    pub fn insert(&mut self, day: DailyStats) -> bool {
+        match self.days.binary_search_by_key(&day.date, |d| d.date) {
+            Ok(i) => {
+                let changed = self.days[i] != day;
+                self.days[i] = day;
+                changed
+            }
+            Err(0) if self.days.is_full() => false,
+            Err(i) => {
+                let i = if self.days.is_full() {
+                    self.days.remove(0);
+                    i - 1
+                } else {
+                    i
+                };
+                // There is room after removing the oldest day
+                let _ = self.days.insert(i, day);
+                true
+            }
+        }
+    }
+
+    /// Statistics of `date` compared to the days before it
+    pub fn statistics(&self, date: Date) -> Statistics {
+        let average_of = |from: Date| {
+            let days = self
+                .days
+                .iter()
+                .filter(|d| from <= d.date && d.date <= date);
+            let (sum, count) = days.fold((0i64, 0i64), |(sum, count), d| {
+                (sum + d.average as i64, count + 1)
+            });
+            (count > 0).then(|| (sum / count) as i32)
+        };
+        let today = self.days.iter().find(|d| d.date == date).copied();
+        let week_before = Date::from_days(date.days() - WEEK_DAYS);
+        let week = self
+            .days
+            .iter()
+            .filter(|d| week_before <= d.date && d.date < date);
+        let (sum, count) = week.fold((0i64, 0i64), |(sum, count), d| {
+            (sum + d.average as i64, count + 1)
+        });
+
+        let mut profile = [(0i64, 0i64); 24];
+        for day in &self.days {
+            for (hour, price) in day.hourly.iter().enumerate() {
+                profile[hour].0 += *price as i64;
+                profile[hour].1 += 1;
+            }
+        }
+
+        Statistics {
+            date,
+            days: self.days.len() as u8,
+            today,
+            week_average: (count > 0).then(|| (sum / count) as i32),
+            month_average: Date::new(date.year, date.month, 1).and_then(average_of),
+            history_average: self.days.first().and_then(|d| average_of(d.date)),
+            profile: (!self.days.is_empty())
+                .then(|| profile.map(|(sum, count)| (sum / count.max(1)) as i32)),
+        }
+    }
+}
+
+/// Prices of a day compared to the [History] before it, answer to
+/// [Message::GetStatistics](crate::Message::GetStatistics)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Statistics {
+    pub date: Date,
+    /// Number of days in the history
+    pub days: u8,
+    /// Day `date` if its prices are known
+    pub today: Option<DailyStats>,
+    /// Average of the daily averages of the 7 days before `date`
+    pub week_average: Option<i32>,
+    /// Average of the daily averages of the calendar month of `date` up to and including it
+    pub month_average: Option<i32>,
+    /// Average of the daily averages of the whole history up to and including `date`
+    pub history_average: Option<i32>,
+    /// Average of each local hour over the whole history
+    pub profile: Option<[i32; 24]>,
+}
+
+impl Statistics {
+    /// Average of `date` relative to [Self::week_average] in percent, for example -20 when
+    /// the day is 20 % cheaper. [None] if either is unknown or the week average is not positive.
+    pub fn today_vs_week(&self) -> Option<i32> {
+        let today = self.today?.average as i64;
+        let week = self.week_average.filter(|w| *w > 0)? as i64;
+        Some(((today - week) * 100 / week) as i32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DailyStats, History, HISTORY_DAYS};
+    use crate::{
+        price::{PricePoint, Timestamp},
+        time::{Date, TimeZone},
+    };
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    /// Quarter-hours of the Finnish day `date`, priced `price(local hour)`
+    fn day(date: Date, price: impl Fn(u8) -> i32) -> std::vec::Vec<PricePoint> {
+        let (start, end) = TimeZone::Eet.day(date);
+        (start..end)
+            .step_by(900)
+            .map(|start| PricePoint {
+                start,
+                duration: 900,
+                price: price(TimeZone::Eet.to_local(start).hour),
+            })
+            .collect()
+    }
+
+    /// Day with the same price all day
+    fn flat(date: Date, price: i32) -> DailyStats {
+        DailyStats::new(date, &day(date, |_| price), TimeZone::Eet).unwrap()
+    }
+
+    #[test]
+    fn daily_stats() {
+        let date = date(2025, 1, 15);
+        let stats = DailyStats::new(date, &day(date, |h| h as i32 * 100), TimeZone::Eet).unwrap();
+        assert_eq!(stats.min, 0);
+        assert_eq!(stats.max, 2300);
+        assert_eq!(stats.average, 1150);
+        assert_eq!(stats.hourly[7], 700);
+
+        // Missing quarter
+        let mut points = day(date, |_| 0);
+        points.remove(40);
+        assert_eq!(DailyStats::new(date, &points, TimeZone::Eet), None);
+        // Day in another time zone
+        assert_eq!(
+            DailyStats::new(date, &day(date, |_| 0), TimeZone::Cet),
+            None
+        );
+    }
+
+    #[test]
+    fn daylight_saving_days() {
+        // 03:00 - 04:00 does not exist in Finland when summer time starts
+        let spring = date(2025, 3, 30);
+        let points = day(spring, |h| h as i32 * 100);
+        assert_eq!(points.len(), 92);
+        let stats = DailyStats::new(spring, &points, TimeZone::Eet).unwrap();
+        assert_eq!(stats.hourly[3], stats.average);
+        assert_eq!(stats.hourly[4], 400);
+
+        // and 03:00 - 04:00 happens twice when it ends
+        let autumn = date(2025, 10, 26);
+        let (start, _) = TimeZone::Eet.day(autumn);
+        let mut points = day(autumn, |_| 0);
+        assert_eq!(points.len(), 100);
+        for (i, point) in points.iter_mut().enumerate() {
+            point.price = i as i32;
+        }
+        let stats = DailyStats::new(autumn, &points, TimeZone::Eet).unwrap();
+        // Quarters 12 - 15 and 16 - 19 are both 03:xx
+        assert_eq!(TimeZone::Eet.to_local(start + 16 * 900).hour, 3);
+        assert_eq!(stats.hourly[3], (12..20).sum::<i32>() / 8);
+    }
+
+    #[test]
+    fn history_keeps_latest_days() {
+        let mut history = History::new();
+        let first = date(2025, 1, 1);
+        for i in 0..40 {
+            let date = Date::from_days(first.days() + i);
+            assert!(history.insert(flat(date, i as i32)));
+        }
+        assert_eq!(history.days().len(), HISTORY_DAYS);
+        assert_eq!(history.days()[0].date, date(2025, 1, 10));
+        assert_eq!(history.latest().unwrap().date, date(2025, 2, 9));
+
+        // Replacing a day with the same prices does not change anything
+        assert!(!history.insert(flat(date(2025, 1, 20), 19)));
+        assert!(history.insert(flat(date(2025, 1, 20), 1000)));
+        assert_eq!(history.days()[10].average, 1000);
+        // Older than everything else
+        assert!(!history.insert(flat(date(2025, 1, 9), 0)));
+        assert_eq!(history.days()[0].date, date(2025, 1, 10));
+
+        // Days can be inserted out of order
+        let mut history = History::new();
+        history.insert(flat(date(2025, 1, 3), 3));
+        history.insert(flat(date(2025, 1, 1), 1));
+        history.insert(flat(date(2025, 1, 2), 2));
+        let dates: std::vec::Vec<Date> = history.days().iter().map(|d| d.date).collect();
+        assert_eq!(
+            dates,
+            [date(2025, 1, 1), date(2025, 1, 2), date(2025, 1, 3)]
+        );
+    }
+
+    #[test]
+    fn today_compared_to_history() {
+        let mut history = History::new();
+        // From 25 January to 10 February, 10 c/kWh in January and 5 c/kWh in February
+        let first = date(2025, 1, 25);
+        for i in 0..17 {
+            let date = Date::from_days(first.days() + i as Timestamp);
+            let price = if date.month == 1 { 10_000 } else { 5000 };
+            history.insert(flat(date, price));
+        }
+        let today = date(2025, 2, 4);
+        history.insert(flat(today, 2000));
+
+        let stats = history.statistics(today);
+        assert_eq!(stats.days, 17);
+        assert_eq!(stats.today.unwrap().average, 2000);
+        // 28 - 31 January and 1 - 3 February
+        assert_eq!(stats.week_average, Some((4 * 10_000 + 3 * 5000) / 7));
+        assert_eq!(stats.month_average, Some((3 * 5000 + 2000) / 4));
+        assert_eq!(
+            stats.history_average,
+            Some((7 * 10_000 + 3 * 5000 + 2000) / 11)
+        );
+        assert_eq!(stats.today_vs_week(), Some(-74));
+        assert_eq!(stats.profile.unwrap()[0], stats.profile.unwrap()[23]);
+
+        // Day without prices
+        let stats = history.statistics(date(2025, 3, 1));
+        assert_eq!(stats.today, None);
+        assert_eq!(stats.week_average, None);
+        assert_eq!(stats.month_average, None);
+        assert_eq!(stats.today_vs_week(), None);
+
+        let stats = History::new().statistics(today);
+        assert_eq!(stats.profile, None);
+        assert_eq!(stats.history_average, None);
+    }
+}
@@ -0,0 +1,304 @@
+//! Consumer price of electricity on a spot contract.
+//!
+//! The seller charges the day-ahead price plus a margin, and the electricity tax and the
+//! transfer fee of the grid operator are added on top. VAT is charged on all of them.
+//! The transfer fee can depend on the time of day and season, see [TransferSchedule].
+//! All amounts are in the unit of [PricePoint::price], 1/[PRICE_SCALE] currency units
+//! per MWh, which is 1/1000 cents per kWh.
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fixed::Decimal,
+    price::{BiddingZone, PricePoint, PriceSeries, Timestamp, PRICE_SCALE},
+    time_of_use::TransferSchedule,
+    ValidationError,
+};
+
+/// One cent per kWh in 1/[PRICE_SCALE] currency units per MWh
+pub const CENT_PER_KWH: i32 = 10 * PRICE_SCALE;
+
+/// VAT in Finland since September 2024, 25.5 %
+pub const FINNISH_VAT: u16 = 2550;
+
+/// Largest accepted [Tariff::vat], 100 %
+pub const MAX_VAT: u16 = 10_000;
+
+/// Used until the host configures a tariff, VAT and electricity tax without any fees
+pub const DEFAULT_TARIFF: Tariff = Tariff {
+    vat: FINNISH_VAT,
+    margin: 0,
+    tax_class: TaxClass::I,
+    transfer_fee: 0,
+    transfer_schedule: TransferSchedule::Flat,
+};
+
+/// Finnish electricity tax class
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum_macros::EnumString,
+    strum_macros::IntoStaticStr,
+)]
+#[repr(C)]
+pub enum TaxClass {
+    /// Households and services
+    I,
+    /// Industry, data centers and greenhouses
+    II,
+}
+
+impl TaxClass {
+    /// Electricity tax and the security of supply fee without VAT
+    pub const fn tax(self) -> i32 {
+        match self {
+            TaxClass::I => 2253,
+            TaxClass::II => 63,
+        }
+    }
+}
+
+/// Everything that is added to the day-ahead price
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Tariff {
+    /// VAT in 1/100 of a percent, for example [FINNISH_VAT]
+    pub vat: u16,
+    /// Margin of the spot contract without VAT. Can be negative.
+    pub margin: i32,
+    pub tax_class: TaxClass,
+    /// Transfer fee of the grid operator per kWh without VAT
+    pub transfer_fee: i32,
+    /// When another transfer fee applies
+    pub transfer_schedule: TransferSchedule,
+}
+
+impl Default for Tariff {
+    fn default() -> Self {
+        DEFAULT_TARIFF
+    }
+}
+
+impl Tariff {
+    /// Checks that VAT is at most [MAX_VAT] and the transfer fees are not negative
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.vat > MAX_VAT || self.transfer_fee < 0 {
+            return Err(ValidationError::OutOfRange);
+        }
+        self.transfer_schedule.validate()
+    }
+
+    /// Transfer fee at `timestamp` according to [Self::transfer_schedule]
+    pub fn transfer_fee_at(&self, timestamp: Timestamp) -> i32 {
+        self.transfer_schedule
+            .time_of_use(self.transfer_fee)
+            .fee_at(timestamp)
+    }
+
+    /// Day-ahead price `spot` with the margin, tax and transfer fee, without VAT
+    pub fn price_without_vat(&self, spot: i32) -> i32 {
+        spot.saturating_add(self.margin)
+            .saturating_add(self.tax_class.tax())
+            .saturating_add(self.transfer_fee)
+    }
+
+    /// All-in consumer price for the day-ahead price `spot` with [Self::transfer_fee],
+    /// rounded half away from zero.
+    ///
+    /// Saturates at the bounds of [i32].
+    pub fn retail_price(&self, spot: i32) -> i32 {
+        let total = self.price_without_vat(spot) as i64 * (MAX_VAT + self.vat) as i64;
+        let half = MAX_VAT as i64 / 2 * total.signum();
+        ((total + half) / MAX_VAT as i64).clamp(i32::MIN as i64, i32::MAX as i64) as i32
+    }
+
+    /// All-in consumer price of `point` with the transfer fee of its time unit
+    pub fn retail_price_of(&self, point: &PricePoint) -> i32 {
+        self.transfer_schedule
+            .time_of_use(self.transfer_fee)
+            .retail_price(self, point)
+    }
+
+    /// Time weighted average of the consumer prices of `series`, rounded towards zero
+    pub fn average_retail_price<const N: usize>(&self, series: &PriceSeries<N>) -> Option<i32> {
+        let time_of_use = self.transfer_schedule.time_of_use(self.transfer_fee);
+        let (sum, duration) = series
+            .points()
+            .iter()
+            .fold((0i64, 0i64), |(sum, duration), p| {
+                (
+                    sum + time_of_use.retail_price(self, p) as i64 * p.duration as i64,
+                    duration + p.duration as i64,
+                )
+            });
+        if duration == 0 {
+            return None;
+        }
+        Some((sum / duration) as i32)
+    }
+}
+
+/// Formats `price` as cents per kWh with two decimals, for example 4567 (45.67 €/MWh) as `4.56`
+pub fn cents_per_kwh(price: i32) -> Decimal {
+    Decimal::new(price / (CENT_PER_KWH / 100), 100)
+}
+
+/// Overview of the latest day-ahead prices, sent to the host with
+/// [Event::PriceSummary](crate::Event::PriceSummary)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct PriceSummary {
+    pub zone: BiddingZone,
+    pub min: i32,
+    pub max: i32,
+    pub average: i32,
+    /// Cheapest consumer price with the tariff configured on the device
+    pub retail_min: i32,
+    /// Most expensive consumer price with the tariff configured on the device
+    pub retail_max: i32,
+    /// Average consumer price with the tariff configured on the device
+    pub retail_average: i32,
+}
+
+impl PriceSummary {
+    /// Returns [None] if `series` is empty
+    pub fn new<const N: usize>(series: &PriceSeries<N>, tariff: &Tariff) -> Option<Self> {
+        let retail = || series.points().iter().map(|p| tariff.retail_price_of(p));
+        Some(Self {
+            zone: series.zone,
+            min: series.min()?.price,
+            max: series.max()?.price,
+            average: series.average()?,
+            retail_min: retail().min()?,
+            retail_max: retail().max()?,
+            retail_average: tariff.average_retail_price(series)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::string::ToString;
+
+    use super::{
+        cents_per_kwh, PriceSummary, Tariff, TaxClass, CENT_PER_KWH, DEFAULT_TARIFF, FINNISH_VAT,
+    };
+    use crate::{
+        price::{BiddingZone, Currency, PricePoint, PriceSeries, Resolution, Timestamp},
+        time_of_use::TransferSchedule,
+        ValidationError,
+    };
+
+    /// Typical Finnish household contract
+    const HOUSEHOLD: Tariff = Tariff {
+        vat: FINNISH_VAT,
+        margin: 490,
+        tax_class: TaxClass::I,
+        transfer_fee: 3500,
+        transfer_schedule: TransferSchedule::Flat,
+    };
+
+    #[test]
+    fn finnish_household() {
+        // 50 €/MWh is 5 c/kWh
+        let spot = 5 * CENT_PER_KWH;
+        assert_eq!(HOUSEHOLD.price_without_vat(spot), 11_243);
+        // 11.243 c/kWh * 1.255 = 14.109965 c/kWh
+        assert_eq!(HOUSEHOLD.retail_price(spot), 14_110);
+        assert_eq!(
+            cents_per_kwh(HOUSEHOLD.retail_price(spot)).to_string(),
+            "14.11"
+        );
+
+        // Tax and VAT only
+        assert_eq!(DEFAULT_TARIFF.retail_price(0), 2828);
+    }
+
+    #[test]
+    fn negative_prices() {
+        let spot = -10 * CENT_PER_KWH;
+        // -3.757 c/kWh * 1.255 = -4.715035 c/kWh
+        assert_eq!(HOUSEHOLD.retail_price(spot), -4715);
+        assert_eq!(cents_per_kwh(-4715).to_string(), "-4.71");
+    }
+
+    #[test]
+    fn without_vat_and_industrial_tax() {
+        let tariff = Tariff {
+            vat: 0,
+            margin: -100,
+            tax_class: TaxClass::II,
+            transfer_fee: 0,
+            transfer_schedule: TransferSchedule::Flat,
+        };
+        assert_eq!(tariff.retail_price(4567), 4567 - 100 + 63);
+        assert_eq!(HOUSEHOLD.retail_price(i32::MAX), i32::MAX);
+        assert_eq!(HOUSEHOLD.retail_price(i32::MIN), i32::MIN);
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(HOUSEHOLD.validate(), Ok(()));
+        let vat = Tariff {
+            vat: 10_001,
+            ..HOUSEHOLD
+        };
+        assert_eq!(vat.validate(), Err(ValidationError::OutOfRange));
+        let fee = Tariff {
+            transfer_fee: -1,
+            ..HOUSEHOLD
+        };
+        assert_eq!(fee.validate(), Err(ValidationError::OutOfRange));
+        let night_fee = Tariff {
+            transfer_schedule: TransferSchedule::Night(-1),
+            ..HOUSEHOLD
+        };
+        assert_eq!(night_fee.validate(), Err(ValidationError::OutOfRange));
+    }
+
+    #[test]
+    fn night_transfer_fee() {
+        // 2025-01-15 00:00 in Finland
+        const MIDNIGHT: Timestamp = 1_736_892_000;
+        let tariff = Tariff {
+            vat: 0,
+            transfer_schedule: TransferSchedule::Night(1500),
+            ..HOUSEHOLD
+        };
+        let mut series =
+            PriceSeries::<24>::new(BiddingZone::Fi, Currency::Eur, Resolution::Minutes60);
+        for hour in 0..24 {
+            series
+                .push(PricePoint {
+                    start: MIDNIGHT + hour * 3600,
+                    duration: 3600,
+                    price: 5 * CENT_PER_KWH,
+                })
+                .unwrap();
+        }
+        let day = tariff.retail_price(5 * CENT_PER_KWH);
+        let night = day - 3500 + 1500;
+        assert_eq!(tariff.transfer_fee_at(MIDNIGHT + 6 * 3600), 1500);
+        assert_eq!(tariff.transfer_fee_at(MIDNIGHT + 7 * 3600), 3500);
+        assert_eq!(tariff.retail_price_of(&series.points()[6]), night);
+        assert_eq!(tariff.retail_price_of(&series.points()[7]), day);
+
+        // 9 night hours and 15 day hours
+        let summary = PriceSummary::new(&series, &tariff).unwrap();
+        assert_eq!(summary.average, 5 * CENT_PER_KWH);
+        assert_eq!(summary.retail_min, night);
+        assert_eq!(summary.retail_max, day);
+        assert_eq!(summary.retail_average, (9 * night + 15 * day) / 24);
+        assert_eq!(
+            PriceSummary::new(&series, &HOUSEHOLD)
+                .unwrap()
+                .retail_average,
+            HOUSEHOLD.retail_price(5 * CENT_PER_KWH)
+        );
+    }
+}
@@ -0,0 +1,530 @@
+//! Calendar conversions for [Timestamp]s without an allocator or system clock.
+//!
+//! Prices are published in UTC but shown in the local time of the bidding zone, see
+//! [TimeZone]. Summer time rules are built in, so no time zone database is needed.
+
+use serde::{Deserialize, Serialize};
+
+use crate::price::{Resolution, Timestamp};
+
+pub const SECONDS_PER_DAY: Timestamp = 24 * 60 * 60;
+
+/// Calendar date and time of day in UTC
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UtcDateTime {
+    pub year: i32,
+    /// 1..=12
+    pub month: u8,
+    /// 1..=31
+    pub day: u8,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+}
+
+impl UtcDateTime {
+    pub fn from_timestamp(timestamp: Timestamp) -> Self {
+        let days = timestamp.div_euclid(SECONDS_PER_DAY);
+        let seconds = timestamp.rem_euclid(SECONDS_PER_DAY);
+        let (year, month, day) = civil_from_days(days);
+        Self {
+            year,
+            month,
+            day,
+            hour: (seconds / 3600) as u8,
+            minute: (seconds % 3600 / 60) as u8,
+            second: (seconds % 60) as u8,
+        }
+    }
+
+    pub fn timestamp(&self) -> Timestamp {
+        days_from_civil(self.year, self.month, self.day) * SECONDS_PER_DAY
+            + self.hour as Timestamp * 3600
+            + self.minute as Timestamp * 60
+            + self.second as Timestamp
+    }
+
+    /// Parses `YYYY-MM-DDTHH:MMZ`, `YYYY-MM-DDTHH:MM:SSZ` or `YYYY-MM-DDTHH:MM:SS.sssZ`.
+    ///
+    /// Fractions of a second are truncated.
+    pub fn parse_iso8601(s: &str) -> Option<Self> {
+        Self::parse_without_zone(s.strip_suffix('Z')?)
+    }
+
+    fn parse_without_zone(s: &str) -> Option<Self> {
+        let (date, time) = s.split_once('T')?;
+
+        let mut date = date.splitn(3, '-');
+        let year = date.next()?.parse().ok()?;
+        let month = date.next()?.parse().ok()?;
+        let day = date.next()?.parse().ok()?;
+
+        let mut time = time.splitn(3, ':');
+        let hour = time.next()?.parse().ok()?;
+        let minute = time.next()?.parse().ok()?;
+        let second = match time.next() {
+            Some(s) => {
+                let (whole, fraction) = s.split_once('.').unwrap_or((s, "0"));
+                if fraction.is_empty() || !fraction.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                whole.parse().ok()?
+            }
+            None => 0,
+        };
+
+        let valid = (1..=12).contains(&month)
+            && (1..=days_in_month(year, month)).contains(&day)
+            && hour < 24
+            && minute < 60
+            && second < 60;
+        valid.then_some(Self {
+            year,
+            month,
+            day,
+            hour,
+            minute,
+            second,
+        })
+    }
+}
+
+/// Parses ISO 8601 date and time in UTC or with an offset such as `+03:00`
+pub fn parse_timestamp(s: &str) -> Option<Timestamp> {
+    if s.ends_with('Z') {
+        return UtcDateTime::parse_iso8601(s).map(|dt| dt.timestamp());
+    }
+    let split = s.len().checked_sub(6)?;
+    let (local, offset) = (s.get(..split)?, s.get(split..)?);
+    let sign = match offset.as_bytes()[0] {
+        b'+' => 1,
+        b'-' => -1,
+        _ => return None,
+    };
+    let (hours, minutes) = offset[1..].split_once(':')?;
+    let hours: Timestamp = hours.parse().ok()?;
+    let minutes: Timestamp = minutes.parse().ok()?;
+    if hours > 23 || minutes > 59 {
+        return None;
+    }
+    let local = UtcDateTime::parse_without_zone(local)?.timestamp();
+    Some(local - sign * (hours * 3600 + minutes * 60))
+}
+
+/// Calendar date without a time zone
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Date {
+    pub year: i32,
+    /// 1..=12
+    pub month: u8,
+    /// 1..=31
+    pub day: u8,
+}
+
+impl Date {
+    /// Returns [None] if the date does not exist
+    pub fn new(year: i32, month: u8, day: u8) -> Option<Self> {
+        let valid = (1..=12).contains(&month) && (1..=days_in_month(year, month)).contains(&day);
+        valid.then_some(Self { year, month, day })
+    }
+
+    /// Date at `days` since 1970-01-01
+    pub fn from_days(days: Timestamp) -> Self {
+        let (year, month, day) = civil_from_days(days);
+        Self { year, month, day }
+    }
+
+    /// Days since 1970-01-01
+    pub fn days(self) -> Timestamp {
+        days_from_civil(self.year, self.month, self.day)
+    }
+
+    pub fn next(self) -> Self {
+        Self::from_days(self.days() + 1)
+    }
+
+    /// Days since Monday, 0 for Monday and 6 for Sunday
+    pub fn weekday(self) -> u8 {
+        // 1970-01-01 was a Thursday
+        (self.days() + 3).rem_euclid(7) as u8
+    }
+}
+
+/// Time zone of a bidding zone, all of them follow the EU summer time rules
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum TimeZone {
+    /// Central European Time, UTC+1 and UTC+2 in summer. Europe/Stockholm, Europe/Oslo
+    /// and Europe/Copenhagen, also the time zone of the day-ahead market.
+    Cet,
+    /// Eastern European Time, UTC+2 and UTC+3 in summer. Europe/Helsinki, Europe/Tallinn,
+    /// Europe/Riga and Europe/Vilnius.
+    Eet,
+}
+
+impl TimeZone {
+    /// Offset from UTC in seconds outside summer time
+    pub const fn standard_offset(self) -> Timestamp {
+        match self {
+            TimeZone::Cet => 3600,
+            TimeZone::Eet => 2 * 3600,
+        }
+    }
+
+    /// Offset from UTC in seconds at `timestamp`
+    pub fn offset(self, timestamp: Timestamp) -> Timestamp {
+        if is_eu_summer_time(timestamp) {
+            self.standard_offset() + 3600
+        } else {
+            self.standard_offset()
+        }
+    }
+
+    /// Local date and time at `timestamp`
+    pub fn to_local(self, timestamp: Timestamp) -> LocalDateTime {
+        let offset = self.offset(timestamp);
+        let local = UtcDateTime::from_timestamp(timestamp + offset);
+        LocalDateTime {
+            date: Date {
+                year: local.year,
+                month: local.month,
+                day: local.day,
+            },
+            hour: local.hour,
+            minute: local.minute,
+            second: local.second,
+            offset: offset as i32,
+        }
+    }
+
+    /// Local date at `timestamp`
+    pub fn date(self, timestamp: Timestamp) -> Date {
+        self.to_local(timestamp).date
+    }
+
+    /// Start and end of the local day `date`.
+    ///
+    /// Days are 23 hours long when summer time starts and 25 hours when it ends.
+    pub fn day(self, date: Date) -> (Timestamp, Timestamp) {
+        (self.midnight(date), self.midnight(date.next()))
+    }
+
+    /// Summer time changes at 01:00 UTC, which is never midnight in Europe,
+    /// so every day has exactly one midnight
+    fn midnight(self, date: Date) -> Timestamp {
+        let utc_midnight = date.days() * SECONDS_PER_DAY;
+        utc_midnight - self.offset(utc_midnight - self.standard_offset())
+    }
+
+    /// Time unit of `resolution` that contains `timestamp`, counted from the local midnight.
+    ///
+    /// Unlike the local time, the slot is unique on the day summer time ends,
+    /// when the hour from 03:00 to 04:00 EET happens twice.
+    pub fn slot(self, timestamp: Timestamp, resolution: Resolution) -> Slot {
+        let date = self.date(timestamp);
+        let (start, _) = self.day(date);
+        Slot {
+            date,
+            index: ((timestamp - start) / resolution.seconds() as Timestamp) as u16,
+        }
+    }
+
+    /// Number of time units of `resolution` in the local day `date`,
+    /// for example 92, 96 or 100 quarter-hours
+    pub fn slots_in_day(self, date: Date, resolution: Resolution) -> u16 {
+        let (start, end) = self.day(date);
+        ((end - start) / resolution.seconds() as Timestamp) as u16
+    }
+}
+
+/// Date and time of day in a [TimeZone]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocalDateTime {
+    pub date: Date,
+    pub hour: u8,
+    pub minute: u8,
+    pub second: u8,
+    /// Offset from UTC in seconds
+    pub offset: i32,
+}
+
+impl LocalDateTime {
+    /// Instant in UTC
+    pub fn timestamp(&self) -> Timestamp {
+        self.date.days() * SECONDS_PER_DAY
+            + self.hour as Timestamp * 3600
+            + self.minute as Timestamp * 60
+            + self.second as Timestamp
+            - self.offset as Timestamp
+    }
+}
+
+/// Time unit of a local day, see [TimeZone::slot]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Slot {
+    pub date: Date,
+    /// Time units since the local midnight
+    pub index: u16,
+}
+
+/// Returns true if European summer time is in effect at `timestamp`.
+///
+/// Summer time starts on the last Sunday of March and ends on the last Sunday of October
+/// at 01:00 UTC in every EU time zone.
+pub fn is_eu_summer_time(timestamp: Timestamp) -> bool {
+    let year = UtcDateTime::from_timestamp(timestamp).year;
+    let start = last_sunday(year, 3).days() * SECONDS_PER_DAY + 3600;
+    let end = last_sunday(year, 10).days() * SECONDS_PER_DAY + 3600;
+    (start..end).contains(&timestamp)
+}
+
+fn last_sunday(year: i32, month: u8) -> Date {
+    let last = Date {
+        year,
+        month,
+        day: days_in_month(year, month),
+    };
+    Date::from_days(last.days() - ((last.weekday() + 1) % 7) as Timestamp)
+}
+
+pub fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+pub fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01, from <http://howardhinnant.github.io/date_algorithms.html>
+pub fn days_from_civil(year: i32, month: u8, day: u8) -> Timestamp {
+    let year = year as Timestamp - (month <= 2) as Timestamp;
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as Timestamp;
+    let day_of_year =
+        (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as Timestamp - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Inverse of [days_from_civil]
+pub fn civil_from_days(days: Timestamp) -> (i32, u8, u8) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = (year_of_era + era * 400 + (month <= 2) as Timestamp) as i32;
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        civil_from_days, days_from_civil, is_eu_summer_time, last_sunday, parse_timestamp, Date,
+        LocalDateTime, Slot, TimeZone, UtcDateTime, SECONDS_PER_DAY,
+    };
+    use crate::price::Resolution;
+
+    const ZONES: [TimeZone; 2] = [TimeZone::Cet, TimeZone::Eet];
+
+    fn time(s: &str) -> i64 {
+        UtcDateTime::parse_iso8601(s).unwrap().timestamp()
+    }
+
+    fn date(year: i32, month: u8, day: u8) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn known_timestamps() {
+        let dt = UtcDateTime::parse_iso8601("2024-10-01T22:00Z").unwrap();
+        assert_eq!(dt.timestamp(), 1_727_820_000);
+        assert_eq!(UtcDateTime::from_timestamp(1_727_820_000), dt);
+
+        let leap = UtcDateTime::parse_iso8601("2024-02-29T23:59:59Z").unwrap();
+        assert_eq!(leap.timestamp(), 1_709_251_199);
+        assert_eq!(UtcDateTime::from_timestamp(0).year, 1970);
+        assert_eq!(UtcDateTime::from_timestamp(-1).year, 1969);
+
+        let fraction = UtcDateTime::parse_iso8601("2024-02-29T23:59:59.999Z").unwrap();
+        assert_eq!(fraction, leap);
+    }
+
+    #[test]
+    fn rejects_invalid_dates() {
+        assert_eq!(UtcDateTime::parse_iso8601("2023-02-29T00:00Z"), None);
+        assert_eq!(UtcDateTime::parse_iso8601("2024-10-01T24:00Z"), None);
+        assert_eq!(UtcDateTime::parse_iso8601("2024-10-01T22:00"), None);
+        assert_eq!(UtcDateTime::parse_iso8601("2024-13-01T00:00Z"), None);
+        assert_eq!(UtcDateTime::parse_iso8601("2024-10-01T22:00:00.Z"), None);
+    }
+
+    #[test]
+    fn civil_days_roundtrip() {
+        for days in -800_000..800_000 {
+            let (y, m, d) = civil_from_days(days);
+            assert_eq!(days_from_civil(y, m, d), days);
+        }
+    }
+
+    #[test]
+    fn timestamps_with_offset() {
+        let utc = UtcDateTime::parse_iso8601("2024-10-01T21:00Z")
+            .unwrap()
+            .timestamp();
+        assert_eq!(parse_timestamp("2024-10-02T00:00:00+03:00"), Some(utc));
+        assert_eq!(parse_timestamp("2024-10-01T19:30-01:30"), Some(utc));
+        assert_eq!(parse_timestamp("2024-10-01T21:00Z"), Some(utc));
+        assert_eq!(parse_timestamp("2024-10-01T21:00+3:00"), None);
+        assert_eq!(parse_timestamp("2024-10-01T21:00"), None);
+    }
+
+    #[test]
+    fn dates() {
+        let date = Date::new(2024, 2, 28).unwrap();
+        assert_eq!(date.next(), Date::new(2024, 2, 29).unwrap());
+        assert_eq!(Date::new(2024, 12, 31).unwrap().next().year, 2025);
+        assert_eq!(Date::new(2023, 2, 29), None);
+        // 2024-10-01 was a Tuesday
+        assert_eq!(Date::new(2024, 10, 1).unwrap().weekday(), 1);
+    }
+
+    #[test]
+    fn eu_summer_time_changes() {
+        assert!(!is_eu_summer_time(time("2024-03-31T00:59:59Z")));
+        assert!(is_eu_summer_time(time("2024-03-31T01:00Z")));
+        assert!(is_eu_summer_time(time("2024-10-27T00:59:59Z")));
+        assert!(!is_eu_summer_time(time("2024-10-27T01:00Z")));
+        assert!(is_eu_summer_time(time("2025-03-30T01:00Z")));
+        assert!(!is_eu_summer_time(time("2025-03-29T01:00Z")));
+    }
+
+    #[test]
+    fn helsinki_local_times() {
+        let local = |s| {
+            let l = TimeZone::Eet.to_local(time(s));
+            (l.date, l.hour, l.minute, l.offset / 3600)
+        };
+        assert_eq!(local("2024-01-15T10:00Z"), (date(2024, 1, 15), 12, 0, 2));
+        assert_eq!(local("2024-07-15T21:30Z"), (date(2024, 7, 16), 0, 30, 3));
+        // Spring forward from 03:00 to 04:00
+        assert_eq!(local("2024-03-31T00:59Z"), (date(2024, 3, 31), 2, 59, 2));
+        assert_eq!(local("2024-03-31T01:00Z"), (date(2024, 3, 31), 4, 0, 3));
+        // Fall back from 04:00 to 03:00, the hour after 03:00 happens twice
+        assert_eq!(local("2024-10-27T00:30Z"), (date(2024, 10, 27), 3, 30, 3));
+        assert_eq!(local("2024-10-27T01:30Z"), (date(2024, 10, 27), 3, 30, 2));
+        assert_eq!(
+            TimeZone::Cet.to_local(time("2024-10-27T01:30Z")).hour,
+            2,
+            "Stockholm falls back from 03:00 to 02:00"
+        );
+    }
+
+    #[test]
+    fn every_minute_around_transitions() {
+        for year in 2000..=2040 {
+            for month in [3, 10] {
+                let change = last_sunday(year, month).days() * SECONDS_PER_DAY + 3600;
+                for zone in ZONES {
+                    let mut previous: Option<LocalDateTime> = None;
+                    for t in
+                        (change - 2 * SECONDS_PER_DAY..change + 2 * SECONDS_PER_DAY).step_by(60)
+                    {
+                        let local = zone.to_local(t);
+                        assert_eq!(local.timestamp(), t);
+                        let summer = (month == 3) == (t >= change);
+                        assert_eq!(
+                            local.offset as i64,
+                            zone.standard_offset() + if summer { 3600 } else { 0 },
+                            "{zone:?} {t}"
+                        );
+
+                        let wall = |l: &LocalDateTime| {
+                            l.date.days() * SECONDS_PER_DAY
+                                + l.hour as i64 * 3600
+                                + l.minute as i64 * 60
+                        };
+                        if let Some(previous) = previous {
+                            // Wall clock jumps only at the change
+                            let step = wall(&local) - wall(&previous);
+                            let expected = match (t == change, month) {
+                                (false, _) => 60,
+                                (true, 3) => 60 + 3600,
+                                (true, _) => 60 - 3600,
+                            };
+                            assert_eq!(step, expected, "{zone:?} {t}");
+                        }
+                        previous = Some(local);
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn length_of_every_day() {
+        for zone in ZONES {
+            let mut day = date(2000, 1, 1);
+            while day.year <= 2040 {
+                let (start, end) = zone.day(day);
+                let expected = if day == last_sunday(day.year, 3) {
+                    23
+                } else if day == last_sunday(day.year, 10) {
+                    25
+                } else {
+                    24
+                };
+                assert_eq!(end - start, expected * 3600, "{zone:?} {day:?}");
+                assert_eq!(zone.to_local(start).hour, 0);
+                assert_eq!(zone.date(start), day);
+                assert_eq!(zone.date(end - 1), day);
+                assert_eq!(
+                    zone.slots_in_day(day, Resolution::Minutes15),
+                    expected as u16 * 4
+                );
+                day = day.next();
+            }
+        }
+    }
+
+    #[test]
+    fn slots_of_transition_days() {
+        for (day, quarters) in [
+            (date(2025, 3, 29), 96),
+            (date(2025, 3, 30), 92),
+            (date(2025, 10, 26), 100),
+            (date(2025, 10, 27), 96),
+        ] {
+            for zone in ZONES {
+                let (start, end) = zone.day(day);
+                let resolution = Resolution::Minutes15;
+                for (index, t) in (start..end).step_by(900).enumerate() {
+                    let slot = Slot {
+                        date: day,
+                        index: index as u16,
+                    };
+                    assert_eq!(zone.slot(t, resolution), slot);
+                    assert_eq!(zone.slot(t + 899, resolution), slot);
+                }
+                assert_eq!((end - start) / 900, quarters);
+                assert_eq!(zone.slot(end, resolution).index, 0);
+            }
+        }
+
+        // Both 03:30 in Helsinki on the day summer time ends
+        let first = TimeZone::Eet.slot(time("2025-10-26T00:30Z"), Resolution::Minutes60);
+        let second = TimeZone::Eet.slot(time("2025-10-26T01:30Z"), Resolution::Minutes60);
+        assert_eq!((first.index, second.index), (3, 4));
+    }
+}
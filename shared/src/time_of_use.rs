@@ -0,0 +1,505 @@
+//! Time-of-use transfer fees of grid operators.
+//!
+//! Finnish distribution tariffs are commonly either day/night, where nights are cheaper
+//! every day, or seasonal, where winter weekdays are more expensive. Sundays and
+//! [holidays](crate::holiday) are off-peak in both. A [TimeOfUse] tariff gives the
+//! transfer fee of any time unit so that [Tariff::retail_price_of] is correct per slot.
+//! [Tariff::transfer_schedule] selects one of these two for the device.
+
+use core::cmp::Ordering;
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    holiday::HolidayCalendar,
+    price::{PricePoint, Timestamp},
+    tariff::Tariff,
+    time::{Date, TimeZone},
+    ValidationError,
+};
+
+/// Maximum number of [Rule]s in a [TimeOfUse] tariff
+pub const MAX_RULES: usize = 4;
+
+/// Minutes in a day, end of a [Rule] that lasts until midnight
+pub const MINUTES_PER_DAY: u16 = 24 * 60;
+
+/// Bitmap of days of the week
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Weekdays(pub u8);
+
+impl Weekdays {
+    pub const MONDAY: Self = Self(1 << 0);
+    pub const TUESDAY: Self = Self(1 << 1);
+    pub const WEDNESDAY: Self = Self(1 << 2);
+    pub const THURSDAY: Self = Self(1 << 3);
+    pub const FRIDAY: Self = Self(1 << 4);
+    pub const SATURDAY: Self = Self(1 << 5);
+    pub const SUNDAY: Self = Self(1 << 6);
+    /// Monday to Friday
+    pub const WORKDAYS: Self = Self(0b001_1111);
+    /// Every day except Sunday
+    pub const MONDAY_TO_SATURDAY: Self = Self(0b011_1111);
+    pub const ALL: Self = Self(0b111_1111);
+
+    pub const fn empty() -> Self {
+        Self(0)
+    }
+
+    pub const fn union(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Day `weekday` as returned by [Date::weekday], 0 for Monday
+    pub const fn day(weekday: u8) -> Self {
+        Self(1 << (weekday % 7))
+    }
+}
+
+/// Range of days in a year, inclusive at both ends. Wraps over the new year if it
+/// ends before it starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Season {
+    pub from_month: u8,
+    pub from_day: u8,
+    pub to_month: u8,
+    pub to_day: u8,
+}
+
+impl Season {
+    /// November to March, the winter of Finnish seasonal tariffs
+    pub const FINNISH_WINTER: Self = Self {
+        from_month: 11,
+        from_day: 1,
+        to_month: 3,
+        to_day: 31,
+    };
+
+    pub fn contains(&self, date: Date) -> bool {
+        let day = (date.month, date.day);
+        let from = (self.from_month, self.from_day);
+        let to = (self.to_month, self.to_day);
+        if from <= to {
+            from <= day && day <= to
+        } else {
+            from <= day || day <= to
+        }
+    }
+
+    /// Checks that both ends are days of a leap year
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        let valid = |month, day| Date::new(2000, month, day).is_some();
+        if !valid(self.from_month, self.from_day) || !valid(self.to_month, self.to_day) {
+            return Err(ValidationError::OutOfRange);
+        }
+        Ok(())
+    }
+}
+
+/// Transfer fee of a window of local time
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct Rule {
+    /// Start of the window in minutes since the local midnight
+    pub start: u16,
+    /// End of the window in minutes since the local midnight, exclusive. Window continues
+    /// over midnight if it ends before it starts and covers the whole day if they are equal.
+    pub end: u16,
+    /// Days on which the window starts
+    pub weekdays: Weekdays,
+    /// Rule applies all year if [None]
+    pub season: Option<Season>,
+    /// Transfer fee per kWh without VAT, see [Tariff::transfer_fee]
+    pub fee: i32,
+}
+
+impl Rule {
+    /// Returns true if the rule applies at `minute` on a day that is `weekday`.
+    ///
+    /// The part of a window after midnight belongs to the day it started on,
+    /// so `previous` is the weekday of the day before. Season is checked separately.
+    fn matches(&self, minute: u16, weekday: Weekdays, previous: Weekdays) -> bool {
+        match self.start.cmp(&self.end) {
+            Ordering::Less => {
+                self.weekdays.contains(weekday) && self.start <= minute && minute < self.end
+            }
+            Ordering::Greater => {
+                (self.weekdays.contains(weekday) && minute >= self.start)
+                    || (self.weekdays.contains(previous) && minute < self.end)
+            }
+            Ordering::Equal => self.weekdays.contains(weekday),
+        }
+    }
+
+    /// Checks that the window is within a day, the fee is not negative and the season is valid
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.start >= MINUTES_PER_DAY || self.end > MINUTES_PER_DAY || self.fee < 0 {
+            return Err(ValidationError::OutOfRange);
+        }
+        self.season.as_ref().map_or(Ok(()), Season::validate)
+    }
+}
+
+/// Transfer fee that depends on local time, weekday, season and holidays
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeOfUse {
+    pub time_zone: TimeZone,
+    /// Holidays count as Sundays
+    pub holidays: HolidayCalendar,
+    /// Fee when no rule applies
+    pub base_fee: i32,
+    /// First matching rule sets the fee
+    pub rules: Vec<Rule, MAX_RULES>,
+}
+
+impl TimeOfUse {
+    /// Tariff with the same fee all the time
+    pub const fn new(time_zone: TimeZone, holidays: HolidayCalendar, base_fee: i32) -> Self {
+        Self {
+            time_zone,
+            holidays,
+            base_fee,
+            rules: Vec::new(),
+        }
+    }
+
+    /// Day/night tariff with `night` fee between 22 and 07 every day
+    pub fn finnish_night(day: i32, night: i32) -> Self {
+        let mut tariff = Self::new(TimeZone::Eet, HolidayCalendar::Finland, day);
+        tariff
+            .rules
+            .push(Rule {
+                start: 22 * 60,
+                end: 7 * 60,
+                weekdays: Weekdays::ALL,
+                season: None,
+                fee: night,
+            })
+            .unwrap();
+        tariff
+    }
+
+    /// Seasonal tariff with `winter_weekday` fee between 07 and 22 from Monday to Saturday
+    /// from November to March, except on holidays
+    pub fn finnish_seasonal(winter_weekday: i32, other: i32) -> Self {
+        let mut tariff = Self::new(TimeZone::Eet, HolidayCalendar::Finland, other);
+        tariff
+            .rules
+            .push(Rule {
+                start: 7 * 60,
+                end: 22 * 60,
+                weekdays: Weekdays::MONDAY_TO_SATURDAY,
+                season: Some(Season::FINNISH_WINTER),
+                fee: winter_weekday,
+            })
+            .unwrap();
+        tariff
+    }
+
+    /// Adds `rule` after the existing rules.
+    ///
+    /// # Errors
+    ///
+    /// Returns [ValidationError::OutOfRange] if the rule is not valid or there are
+    /// already [MAX_RULES] rules.
+    pub fn add_rule(&mut self, rule: Rule) -> Result<(), ValidationError> {
+        rule.validate()?;
+        self.rules
+            .push(rule)
+            .map_err(|_| ValidationError::OutOfRange)
+    }
+
+    /// Weekday of `date`, Sunday if it is a holiday
+    fn weekday(&self, date: Date) -> Weekdays {
+        if self.holidays.is_holiday(date) {
+            Weekdays::SUNDAY
+        } else {
+            Weekdays::day(date.weekday())
+        }
+    }
+
+    /// Transfer fee at `timestamp`
+    pub fn fee_at(&self, timestamp: Timestamp) -> i32 {
+        let local = self.time_zone.to_local(timestamp);
+        let minute = local.hour as u16 * 60 + local.minute as u16;
+        let weekday = self.weekday(local.date);
+        let yesterday = Date::from_days(local.date.days() - 1);
+        let previous = self.weekday(yesterday);
+        self.rules
+            .iter()
+            .find(|rule| {
+                // Night that started in the season continues to the first morning after it
+                let season_date = if rule.start > rule.end && minute < rule.end {
+                    yesterday
+                } else {
+                    local.date
+                };
+                rule.season.map_or(true, |s| s.contains(season_date))
+                    && rule.matches(minute, weekday, previous)
+            })
+            .map_or(self.base_fee, |rule| rule.fee)
+    }
+
+    /// Transfer fee of the time unit of `point`, the fee at its start
+    pub fn fee_for(&self, point: &PricePoint) -> i32 {
+        self.fee_at(point.start)
+    }
+
+    /// All-in consumer price of `point` with the transfer fee of its time unit instead
+    /// of [Tariff::transfer_fee]
+    pub fn retail_price(&self, tariff: &Tariff, point: &PricePoint) -> i32 {
+        Tariff {
+            transfer_fee: self.fee_for(point),
+            ..*tariff
+        }
+        .retail_price(point.price)
+    }
+}
+
+/// When a [Tariff] has another transfer fee than [Tariff::transfer_fee]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[repr(C)]
+pub enum TransferSchedule {
+    /// Same fee all the time
+    #[default]
+    Flat,
+    /// This fee between 22 and 07 every day, see [TimeOfUse::finnish_night]
+    Night(i32),
+    /// This fee between 07 and 22 on winter weekdays, see [TimeOfUse::finnish_seasonal]
+    WinterWeekday(i32),
+}
+
+impl TransferSchedule {
+    /// Time-of-use tariff with `fee` when the schedule does not set another one
+    pub fn time_of_use(self, fee: i32) -> TimeOfUse {
+        match self {
+            TransferSchedule::Flat => TimeOfUse::new(TimeZone::Eet, HolidayCalendar::Finland, fee),
+            TransferSchedule::Night(night) => TimeOfUse::finnish_night(fee, night),
+            TransferSchedule::WinterWeekday(winter) => TimeOfUse::finnish_seasonal(winter, fee),
+        }
+    }
+
+    /// Checks that the fee is not negative
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        match *self {
+            TransferSchedule::Night(fee) | TransferSchedule::WinterWeekday(fee) if fee < 0 => {
+                Err(ValidationError::OutOfRange)
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Rule, Season, TimeOfUse, TransferSchedule, Weekdays, MAX_RULES};
+    use crate::{
+        holiday::HolidayCalendar,
+        price::{PricePoint, Timestamp},
+        tariff::{Tariff, DEFAULT_TARIFF},
+        time::{Date, LocalDateTime, TimeZone},
+        ValidationError,
+    };
+
+    const WINTER: i32 = 5000;
+    const OTHER: i32 = 2500;
+
+    /// Finnish local time
+    fn at(year: i32, month: u8, day: u8, hour: u8, minute: u8) -> Timestamp {
+        let date = Date::new(year, month, day).unwrap();
+        let offset = TimeZone::Eet.offset(date.days() * 86_400 + 12 * 3600) as i32;
+        LocalDateTime {
+            date,
+            hour,
+            minute,
+            second: 0,
+            offset,
+        }
+        .timestamp()
+    }
+
+    #[test]
+    fn seasonal_christmas() {
+        let tariff = TimeOfUse::finnish_seasonal(WINTER, OTHER);
+        // Monday 23 December is a normal winter weekday
+        assert_eq!(tariff.fee_at(at(2024, 12, 23, 6, 59)), OTHER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 23, 7, 0)), WINTER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 23, 21, 45)), WINTER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 23, 22, 0)), OTHER);
+        // Christmas Eve, Christmas Day and Boxing Day are off-peak
+        for day in 24..=26 {
+            assert_eq!(tariff.fee_at(at(2024, 12, day, 12, 0)), OTHER);
+        }
+        assert_eq!(tariff.fee_at(at(2024, 12, 27, 12, 0)), WINTER);
+        // Saturday is a weekday but Sunday is not
+        assert_eq!(tariff.fee_at(at(2024, 12, 28, 12, 0)), WINTER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 29, 12, 0)), OTHER);
+        // New Year's Day and Epiphany
+        assert_eq!(tariff.fee_at(at(2025, 1, 1, 12, 0)), OTHER);
+        assert_eq!(tariff.fee_at(at(2025, 1, 6, 12, 0)), OTHER);
+        assert_eq!(tariff.fee_at(at(2025, 1, 7, 12, 0)), WINTER);
+    }
+
+    #[test]
+    fn seasonal_season_boundaries() {
+        let tariff = TimeOfUse::finnish_seasonal(WINTER, OTHER);
+        // Monday 31 March 2025, summer time has already started
+        assert_eq!(tariff.fee_at(at(2025, 3, 31, 21, 59)), WINTER);
+        assert_eq!(tariff.fee_at(at(2025, 4, 1, 12, 0)), OTHER);
+        assert_eq!(tariff.fee_at(at(2025, 10, 31, 12, 0)), OTHER);
+        // Saturday 1 November 2025 is All Saints' Day
+        assert_eq!(tariff.fee_at(at(2025, 11, 1, 12, 0)), OTHER);
+        assert_eq!(tariff.fee_at(at(2025, 11, 3, 7, 0)), WINTER);
+    }
+
+    #[test]
+    fn night_midsummer() {
+        const DAY: i32 = 4000;
+        const NIGHT: i32 = 2000;
+        let tariff = TimeOfUse::finnish_night(DAY, NIGHT);
+        for (eve, day) in [
+            ((2024, 6, 21), (2024, 6, 22)),
+            ((2025, 6, 20), (2025, 6, 21)),
+        ] {
+            let ((year, month, eve), (_, _, day)) = (eve, day);
+            // Nights are cheaper on holidays too, days are not
+            assert_eq!(tariff.fee_at(at(year, month, eve, 12, 0)), DAY);
+            assert_eq!(tariff.fee_at(at(year, month, eve, 22, 0)), NIGHT);
+            assert_eq!(tariff.fee_at(at(year, month, day, 6, 45)), NIGHT);
+            assert_eq!(tariff.fee_at(at(year, month, day, 7, 0)), DAY);
+        }
+    }
+
+    #[test]
+    fn winter_weekday_nights() {
+        // Night rule of a weekday continues past midnight to a holiday
+        let mut tariff = TimeOfUse::new(TimeZone::Eet, HolidayCalendar::Finland, OTHER);
+        let rule = Rule {
+            start: 22 * 60,
+            end: 6 * 60,
+            weekdays: Weekdays::WORKDAYS,
+            season: Some(Season::FINNISH_WINTER),
+            fee: WINTER,
+        };
+        tariff.add_rule(rule).unwrap();
+        // Monday 23 December 2024 to Christmas Eve
+        assert_eq!(tariff.fee_at(at(2024, 12, 23, 23, 0)), WINTER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 24, 5, 59)), WINTER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 24, 23, 0)), OTHER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 25, 1, 0)), OTHER);
+        // Night from the last day of March continues to April
+        assert_eq!(tariff.fee_at(at(2025, 4, 1, 1, 0)), WINTER);
+        assert_eq!(tariff.fee_at(at(2025, 4, 1, 23, 0)), OTHER);
+
+        // First matching rule wins
+        let all_day = Rule {
+            start: 0,
+            end: 0,
+            weekdays: Weekdays::ALL,
+            season: None,
+            fee: 1,
+        };
+        tariff.add_rule(all_day).unwrap();
+        assert_eq!(tariff.fee_at(at(2024, 12, 23, 23, 0)), WINTER);
+        assert_eq!(tariff.fee_at(at(2024, 12, 25, 12, 0)), 1);
+    }
+
+    #[test]
+    fn retail_price_per_slot() {
+        let tariff = TimeOfUse::finnish_seasonal(WINTER, OTHER);
+        let point = |start| PricePoint {
+            start,
+            duration: 900,
+            price: 0,
+        };
+        let with_fee = |transfer_fee| {
+            Tariff {
+                transfer_fee,
+                ..DEFAULT_TARIFF
+            }
+            .retail_price(0)
+        };
+        let christmas = point(at(2024, 12, 25, 12, 0));
+        let boxing_day_after = point(at(2024, 12, 27, 12, 0));
+        assert_eq!(
+            tariff.retail_price(&DEFAULT_TARIFF, &christmas),
+            with_fee(OTHER)
+        );
+        assert_eq!(
+            tariff.retail_price(&DEFAULT_TARIFF, &boxing_day_after),
+            with_fee(WINTER)
+        );
+    }
+
+    #[test]
+    fn rule_validation() {
+        let mut tariff = TimeOfUse::new(TimeZone::Cet, HolidayCalendar::None, 0);
+        let rule = Rule {
+            start: 0,
+            end: 24 * 60,
+            weekdays: Weekdays::ALL,
+            season: None,
+            fee: 0,
+        };
+        assert_eq!(
+            tariff.add_rule(Rule {
+                end: 24 * 60 + 1,
+                ..rule
+            }),
+            Err(ValidationError::OutOfRange)
+        );
+        assert_eq!(
+            tariff.add_rule(Rule { fee: -1, ..rule }),
+            Err(ValidationError::OutOfRange)
+        );
+        let february_30 = Season {
+            from_month: 2,
+            from_day: 30,
+            to_month: 3,
+            to_day: 1,
+        };
+        assert_eq!(
+            tariff.add_rule(Rule {
+                season: Some(february_30),
+                ..rule
+            }),
+            Err(ValidationError::OutOfRange)
+        );
+        for _ in 0..MAX_RULES {
+            tariff.add_rule(rule).unwrap();
+        }
+        assert_eq!(tariff.add_rule(rule), Err(ValidationError::OutOfRange));
+    }
+
+    #[test]
+    fn transfer_schedules() {
+        let christmas_noon = at(2024, 12, 25, 12, 0);
+        let friday_noon = at(2024, 12, 27, 12, 0);
+        let friday_night = at(2024, 12, 27, 23, 0);
+
+        let flat = TransferSchedule::Flat.time_of_use(OTHER);
+        for time in [christmas_noon, friday_noon, friday_night] {
+            assert_eq!(flat.fee_at(time), OTHER);
+        }
+        let night = TransferSchedule::Night(1000).time_of_use(OTHER);
+        assert_eq!(night.fee_at(friday_noon), OTHER);
+        assert_eq!(night.fee_at(friday_night), 1000);
+        let seasonal = TransferSchedule::WinterWeekday(WINTER).time_of_use(OTHER);
+        assert_eq!(seasonal.fee_at(christmas_noon), OTHER);
+        assert_eq!(seasonal.fee_at(friday_noon), WINTER);
+        assert_eq!(seasonal.fee_at(friday_night), OTHER);
+
+        assert_eq!(TransferSchedule::Night(0).validate(), Ok(()));
+        assert_eq!(
+            TransferSchedule::WinterWeekday(-1).validate(),
+            Err(ValidationError::OutOfRange)
+        );
+    }
+}
@@ -0,0 +1,436 @@
+//! Chunked transfer of payloads that do not fit to a single [Message](crate::Message).
+//!
+//! Sender starts with [TransferFrame::Begin] announcing total length of the payload,
+//! continues with [TransferFrame::Chunk]s and finishes with [TransferFrame::End].
+//! Receiver answers every frame with [TransferAck] telling which offset it expects next,
+//! so an interrupted transfer can be resumed by sending the same `Begin` again.
+
+use heapless::Vec;
+use serde::{Deserialize, Serialize};
+
+use crate::CKSUM;
+
+/// Maximum amount of payload bytes in one [TransferFrame::Chunk]
+pub const CHUNK_SIZE: usize = 64;
+
+pub type TransferId = u16;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum TransferFrame {
+    Begin {
+        transfer: TransferId,
+        total_len: u32,
+    },
+    Chunk {
+        transfer: TransferId,
+        offset: u32,
+        data: Vec<u8, CHUNK_SIZE>,
+        /// crc32 of `data`
+        crc: u32,
+    },
+    End {
+        transfer: TransferId,
+    },
+}
+
+/// Receiver's answer to every [TransferFrame]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub struct TransferAck {
+    pub transfer: TransferId,
+    /// Offset of the next byte receiver expects
+    pub next_offset: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[repr(C)]
+pub enum TransferError {
+    /// Frame belongs to a transfer that is not active
+    UnknownTransfer,
+    /// Announced length is bigger than receiver can handle
+    TooLarge,
+    /// Chunk data does not match its checksum
+    ChunkCrcMismatch,
+    /// Chunk starts after the expected offset, some data was skipped
+    OutOfOrder,
+    /// Chunk or end frame does not match the length announced in begin frame
+    LengthMismatch,
+}
+
+impl core::fmt::Display for TransferError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            TransferError::UnknownTransfer => write!(f, "unknown transfer"),
+            TransferError::TooLarge => write!(f, "transfer is too large"),
+            TransferError::ChunkCrcMismatch => write!(f, "chunk checksum mismatch"),
+            TransferError::OutOfOrder => write!(f, "chunk out of order"),
+            TransferError::LengthMismatch => write!(f, "length does not match"),
+        }
+    }
+}
+
+/// What receiver should do after a frame was accepted by [TransferReceiver::accept]
+#[derive(Debug, PartialEq)]
+pub enum Accepted<'a> {
+    /// Transfer was started or resumed
+    Started,
+    /// `data` should be written at `offset` of the payload
+    Write { offset: u32, data: &'a [u8] },
+    /// Chunk was already received earlier, most likely a retry
+    Duplicate,
+    /// All `total_len` bytes have been received
+    Complete { total_len: u32 },
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ActiveTransfer {
+    id: TransferId,
+    total_len: u32,
+    next_offset: u32,
+}
+
+/// State machine for the receiving end of a chunked transfer.
+///
+/// Receiver does not store the payload, data of accepted chunks is returned to the caller
+/// which can write it to RAM, flash or wherever it is needed.
+#[derive(Debug)]
+pub struct TransferReceiver {
+    max_len: u32,
+    active: Option<ActiveTransfer>,
+}
+
+impl TransferReceiver {
+    /// Creates receiver that accepts transfers up to `max_len` bytes
+    pub const fn new(max_len: u32) -> Self {
+        Self {
+            max_len,
+            active: None,
+        }
+    }
+
+    /// Acknowledgement to send after handling a frame of `transfer`
+    pub fn ack(&self, transfer: TransferId) -> TransferAck {
+        let next_offset = match self.active {
+            Some(a) if a.id == transfer => a.next_offset,
+            _ => 0,
+        };
+        TransferAck {
+            transfer,
+            next_offset,
+        }
+    }
+
+    /// Validates `frame` and advances the transfer.
+    ///
+    /// Sending `Begin` for the transfer that is already active with the same length resumes it
+    /// from the current offset, any other `Begin` replaces the active transfer.
+    ///
+    /// # Errors
+    ///
+    /// This function will return an error if the frame does not fit to the active transfer.
+    /// Active transfer is kept so that the sender can retry.
+    pub fn accept<'a>(&mut self, frame: &'a TransferFrame) -> Result<Accepted<'a>, TransferError> {
+        match frame {
+            TransferFrame::Begin {
+                transfer,
+                total_len,
+            } => {
+                if *total_len > self.max_len {
+                    return Err(TransferError::TooLarge);
+                }
+                match self.active {
+                    Some(a) if a.id == *transfer && a.total_len == *total_len => {}
+                    _ => {
+                        self.active = Some(ActiveTransfer {
+                            id: *transfer,
+                            total_len: *total_len,
+                            next_offset: 0,
+                        })
+                    }
+                }
+                Ok(Accepted::Started)
+            }
+            TransferFrame::Chunk {
+                transfer,
+                offset,
+                data,
+                crc,
+            } => {
+                let active = self.active_mut(*transfer)?;
+                if CKSUM.checksum(data) != *crc {
+                    return Err(TransferError::ChunkCrcMismatch);
+                }
+                let end = offset
+                    .checked_add(data.len() as u32)
+                    .ok_or(TransferError::LengthMismatch)?;
+                if end > active.total_len {
+                    return Err(TransferError::LengthMismatch);
+                }
+                if *offset > active.next_offset {
+                    return Err(TransferError::OutOfOrder);
+                }
+                if end <= active.next_offset {
+                    return Ok(Accepted::Duplicate);
+                }
+
+                // Chunk may partially overlap already received data
+                let skip = (active.next_offset - offset) as usize;
+                let write_offset = active.next_offset;
+                active.next_offset = end;
+                Ok(Accepted::Write {
+                    offset: write_offset,
+                    data: &data[skip..],
+                })
+            }
+            TransferFrame::End { transfer } => {
+                let active = self.active_mut(*transfer)?;
+                if active.next_offset != active.total_len {
+                    return Err(TransferError::LengthMismatch);
+                }
+                let total_len = active.total_len;
+                // Keep the finished transfer so that retried end frame is still acknowledged
+                Ok(Accepted::Complete { total_len })
+            }
+        }
+    }
+
+    fn active_mut(&mut self, transfer: TransferId) -> Result<&mut ActiveTransfer, TransferError> {
+        match &mut self.active {
+            Some(a) if a.id == transfer => Ok(a),
+            _ => Err(TransferError::UnknownTransfer),
+        }
+    }
+}
+
+/// Splits `payload` to [TransferFrame]s
+#[derive(Debug)]
+pub struct TransferSender<'a> {
+    id: TransferId,
+    payload: &'a [u8],
+    next_offset: usize,
+}
+
+impl<'a> TransferSender<'a> {
+    pub fn new(id: TransferId, payload: &'a [u8]) -> Self {
+        Self {
+            id,
+            payload,
+            next_offset: 0,
+        }
+    }
+
+    pub fn begin(&self) -> TransferFrame {
+        TransferFrame::Begin {
+            transfer: self.id,
+            total_len: self.payload.len() as u32,
+        }
+    }
+
+    /// Continues from the offset receiver reported in its [TransferAck]
+    pub fn resume_from(&mut self, ack: &TransferAck) {
+        self.next_offset = (ack.next_offset as usize).min(self.payload.len());
+    }
+
+    /// Returns next chunk or [TransferFrame::End] once all chunks have been returned
+    pub fn next_frame(&mut self) -> Option<TransferFrame> {
+        if self.next_offset > self.payload.len() {
+            return None;
+        }
+        if self.next_offset == self.payload.len() {
+            self.next_offset += 1;
+            return Some(TransferFrame::End { transfer: self.id });
+        }
+
+        let end = (self.next_offset + CHUNK_SIZE).min(self.payload.len());
+        let data = &self.payload[self.next_offset..end];
+        let frame = TransferFrame::Chunk {
+            transfer: self.id,
+            offset: self.next_offset as u32,
+            // Chunk is never longer than CHUNK_SIZE
+            data: Vec::from_slice(data).unwrap(),
+            crc: CKSUM.checksum(data),
+        };
+        self.next_offset = end;
+        Some(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    fn payload(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    /// Runs all remaining frames from sender through receiver and appends data to `received`
+    fn receive_all(
+        sender: &mut TransferSender,
+        receiver: &mut TransferReceiver,
+        received: &mut Vec<u8>,
+    ) {
+        while let Some(frame) = sender.next_frame() {
+            match receiver.accept(&frame).unwrap() {
+                Accepted::Write { offset, data } => {
+                    assert_eq!(offset as usize, received.len());
+                    received.extend_from_slice(data);
+                }
+                Accepted::Complete { total_len } => assert_eq!(total_len as usize, received.len()),
+                Accepted::Started | Accepted::Duplicate => {}
+            }
+        }
+    }
+
+    #[test]
+    fn transfer_roundtrip() {
+        let data = payload(1000);
+        let mut sender = TransferSender::new(1, &data);
+        let mut receiver = TransferReceiver::new(4096);
+
+        assert_eq!(receiver.accept(&sender.begin()), Ok(Accepted::Started));
+        let mut received = Vec::new();
+        receive_all(&mut sender, &mut receiver, &mut received);
+        assert_eq!(received, data);
+        assert_eq!(receiver.ack(1).next_offset, 1000);
+    }
+
+    #[test]
+    fn frames_fit_to_message() {
+        use crate::{serialize_crc_cobs, Envelope, Message, MESSAGE_SIZE};
+
+        let data = payload(CHUNK_SIZE);
+        let mut sender = TransferSender::new(1, &data);
+        let frame = sender.next_frame().unwrap();
+        let mut buf = [0u8; MESSAGE_SIZE];
+        serialize_crc_cobs::<Envelope<Message>, MESSAGE_SIZE>(
+            Envelope::new(u16::MAX, Message::Transfer(frame)),
+            &mut buf,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn resume_after_interruption() {
+        let data = payload(300);
+        let mut sender = TransferSender::new(7, &data);
+        let mut receiver = TransferReceiver::new(4096);
+        receiver.accept(&sender.begin()).unwrap();
+
+        let mut received = Vec::new();
+        for _ in 0..2 {
+            if let Accepted::Write { data, .. } =
+                receiver.accept(&sender.next_frame().unwrap()).unwrap()
+            {
+                received.extend_from_slice(data);
+            }
+        }
+
+        // Sender restarts from scratch and resumes from where receiver is
+        let mut sender = TransferSender::new(7, &data);
+        assert_eq!(receiver.accept(&sender.begin()), Ok(Accepted::Started));
+        let ack = receiver.ack(7);
+        assert_eq!(ack.next_offset as usize, 2 * CHUNK_SIZE);
+        sender.resume_from(&ack);
+
+        receive_all(&mut sender, &mut receiver, &mut received);
+        assert_eq!(received, data);
+    }
+
+    #[test]
+    fn retried_chunk_is_duplicate() {
+        let data = payload(100);
+        let mut sender = TransferSender::new(2, &data);
+        let mut receiver = TransferReceiver::new(4096);
+        receiver.accept(&sender.begin()).unwrap();
+
+        let chunk = sender.next_frame().unwrap();
+        assert!(matches!(
+            receiver.accept(&chunk),
+            Ok(Accepted::Write { offset: 0, .. })
+        ));
+        assert_eq!(receiver.accept(&chunk), Ok(Accepted::Duplicate));
+    }
+
+    #[test]
+    fn corrupted_chunk_is_rejected() {
+        let data = payload(100);
+        let mut sender = TransferSender::new(3, &data);
+        let mut receiver = TransferReceiver::new(4096);
+        receiver.accept(&sender.begin()).unwrap();
+
+        let Some(TransferFrame::Chunk {
+            transfer,
+            offset,
+            mut data,
+            crc,
+        }) = sender.next_frame()
+        else {
+            panic!("Expected chunk");
+        };
+        data[5] ^= 0xFF;
+        let corrupted = TransferFrame::Chunk {
+            transfer,
+            offset,
+            data,
+            crc,
+        };
+        assert_eq!(
+            receiver.accept(&corrupted),
+            Err(TransferError::ChunkCrcMismatch)
+        );
+        assert_eq!(receiver.ack(3).next_offset, 0);
+    }
+
+    #[test]
+    fn length_is_validated() {
+        let mut receiver = TransferReceiver::new(100);
+        let too_big = TransferFrame::Begin {
+            transfer: 1,
+            total_len: 101,
+        };
+        assert_eq!(receiver.accept(&too_big), Err(TransferError::TooLarge));
+
+        let data = payload(100);
+        let mut sender = TransferSender::new(1, &data);
+        receiver.accept(&sender.begin()).unwrap();
+        sender.next_frame().unwrap();
+        sender.next_frame().unwrap();
+        // Receiver did not get any chunks so end is too early
+        assert_eq!(
+            receiver.accept(&sender.next_frame().unwrap()),
+            Err(TransferError::LengthMismatch)
+        );
+
+        let past_end = TransferFrame::Chunk {
+            transfer: 1,
+            offset: 90,
+            data: heapless::Vec::from_slice(&[0; 20]).unwrap(),
+            crc: CKSUM.checksum(&[0; 20]),
+        };
+        assert_eq!(
+            receiver.accept(&past_end),
+            Err(TransferError::LengthMismatch)
+        );
+    }
+
+    #[test]
+    fn skipped_chunk_is_out_of_order() {
+        let data = payload(200);
+        let mut sender = TransferSender::new(4, &data);
+        let mut receiver = TransferReceiver::new(4096);
+        receiver.accept(&sender.begin()).unwrap();
+
+        sender.next_frame().unwrap();
+        assert_eq!(
+            receiver.accept(&sender.next_frame().unwrap()),
+            Err(TransferError::OutOfOrder)
+        );
+        assert_eq!(
+            receiver.accept(&TransferFrame::End { transfer: 5 }),
+            Err(TransferError::UnknownTransfer)
+        );
+    }
+}